@@ -0,0 +1,229 @@
+//! Consistency of the `enzyme_reaction` table with the kinetic model and the
+//! kcat and km priors.
+//!
+//! A reaction may be catalyzed by several isoenzymes, each with its own kcat,
+//! and each enzyme needs a km for the metabolites of the reactions it catalyzes.
+//! Priors of a pair that is not in `enzyme_reaction` are never used by Maud.
+use lsp_types::Diagnostic;
+use toml::Spanned;
+
+use crate::maud_data::ReactionMechanism;
+use crate::priors::Priors;
+use crate::rules::Rule;
+use crate::state::{span_to_range, KineticModelState};
+use crate::symbols::SymbolIndex;
+
+fn diagnostic(text: &str, span: (usize, usize), rule: Rule, message: String) -> Diagnostic {
+    Diagnostic {
+        range: span_to_range(text, span),
+        severity: Some(rule.default_severity()),
+        code: Some(rule.diagnostic_code()),
+        message,
+        ..Default::default()
+    }
+}
+
+/// Rows of `enzyme_reaction` that refer to an enzyme or a reaction that is not
+/// defined, or to a drain, which is not catalyzed.
+pub fn enzyme_reaction_diagnostics(kinetic_state: &KineticModelState) -> Vec<Diagnostic> {
+    let text = kinetic_state.borrow_file_str();
+    let symbols = kinetic_state.borrow_symbols();
+    let mut diagnostics = Vec::new();
+    for er in kinetic_state.borrow_kinetic_model().enzyme_reaction.iter() {
+        let enzyme: &Spanned<&str> = &er.enzyme_id;
+        if symbols.enzyme(enzyme.get_ref()).is_none() {
+            diagnostics.push(diagnostic(
+                text,
+                enzyme.span(),
+                Rule::EnzymeReactionMismatch,
+                format!(
+                    "Enzyme {} is not defined: add it to the enzymes or fix the enzyme_reaction.",
+                    enzyme.get_ref()
+                ),
+            ));
+        }
+        let reaction = &er.reaction_id;
+        match symbols.reaction(reaction.get_ref()).map(|reac| &reac.mechanism) {
+            None => diagnostics.push(diagnostic(
+                text,
+                reaction.span(),
+                Rule::EnzymeReactionMismatch,
+                format!(
+                    "Reaction {} is not defined: add it to the reactions or fix the enzyme_reaction.",
+                    reaction.get_ref()
+                ),
+            )),
+            Some(ReactionMechanism::Drain) => diagnostics.push(diagnostic(
+                text,
+                reaction.span(),
+                Rule::EnzymeReactionMismatch,
+                format!(
+                    "Reaction {} is a drain, which is not catalyzed by enzymes.",
+                    reaction.get_ref()
+                ),
+            )),
+            Some(_) => (),
+        }
+    }
+    diagnostics
+}
+
+/// Why the kcat prior of an enzyme for a reaction is unused, if it is.
+pub fn kcat_mismatch(symbols: &SymbolIndex, enzyme: &str, reaction: &str) -> Option<String> {
+    if symbols.enzyme(enzyme).is_none() {
+        return Some(format!(
+            "kcat prior of {enzyme} for {reaction} is unused: enzyme {enzyme} is not defined."
+        ));
+    }
+    if symbols.catalyzes(enzyme, reaction) {
+        return None;
+    }
+    Some(match symbols.enzymes_of_reaction(reaction) {
+        [] => format!(
+            "Enzyme {enzyme} does not catalyze reaction {reaction}: the pair is not in enzyme_reaction."
+        ),
+        catalysts => format!(
+            "Enzyme {enzyme} does not catalyze reaction {reaction}, which is catalyzed by {}.",
+            catalysts.join(", ")
+        ),
+    })
+}
+
+/// Why the km prior of an enzyme for a metabolite in compartment (`g6p_c`) is
+/// unused, if it is.
+pub fn km_mismatch(symbols: &SymbolIndex, enzyme: &str, met_comp: &str) -> Option<String> {
+    if symbols.enzyme(enzyme).is_none() {
+        Some(format!(
+            "km prior of {enzyme} for {met_comp} is unused: enzyme {enzyme} is not defined."
+        ))
+    } else if symbols.has_km(enzyme, met_comp) {
+        None
+    } else {
+        Some(format!(
+            "km prior of {enzyme} for {met_comp} is unused: none of {enzyme}'s reactions involve {met_comp}."
+        ))
+    }
+}
+
+/// Kcat priors of an enzyme and a reaction that are not paired in
+/// `enzyme_reaction`, and km priors of a metabolite that none of the reactions
+/// of the enzyme involve.
+pub fn prior_pair_diagnostics(
+    text: &str,
+    priors: &Priors,
+    symbols: &SymbolIndex,
+) -> Vec<Diagnostic> {
+    let kcats = priors.kcat.iter().filter_map(|kcat| {
+        let prior = kcat.get_ref();
+        kcat_mismatch(symbols, &prior.enzyme, &prior.reaction).map(|message| (kcat.span(), message))
+    });
+    let kms = priors.km.iter().filter_map(|km| {
+        let prior = km.get_ref();
+        let met_comp = format!("{}_{}", prior.metabolite, prior.compartment);
+        km_mismatch(symbols, &prior.enzyme, &met_comp).map(|message| (km.span(), message))
+    });
+    kcats
+        .chain(kms)
+        .map(|(span, message)| diagnostic(text, span, Rule::EnzymeReactionMismatch, message))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{KineticModelState, PriorsState};
+
+    const MODEL: &str = r#"
+metabolite = [
+  {id = "g6p", name = "D-Glucose 6-phosphate", inchi_key = "A"},
+  {id = "f6p", name = "D-Fructose 6-phosphate", inchi_key = "B"},
+]
+enzyme = [
+  {id = "pgi1", name = "Glucose-6-phosphate isomerase", subunits = 1},
+  {id = "pgi2", name = "Glucose-6-phosphate isomerase 2", subunits = 1},
+  {id = "pfk", name = "Phosphofructokinase", subunits = 1},
+]
+enzyme_reaction = [
+  {enzyme_id = "pgi1", reaction_id = "PGI"},
+  {enzyme_id = "pgi2", reaction_id = "PGI"},
+  {enzyme_id = "pgi3", reaction_id = "f6pdrain"},
+]
+
+[[reaction]]
+id = "PGI"
+name = "Glucose-6-phosphate isomerase"
+stoichiometry = {g6p_c = -1, f6p_c = 1}
+mechanism = "reversible_michaelis_menten"
+
+[[reaction]]
+id = "f6pdrain"
+name = "f6pdrain"
+stoichiometry = {f6p_c = -1}
+mechanism = "drain"
+"#;
+
+    #[test]
+    fn isoenzymes_are_paired_with_their_reaction() {
+        let state = KineticModelState::try_from_str(MODEL.to_string()).unwrap();
+        let symbols = state.borrow_symbols();
+        assert_eq!(symbols.enzymes_of_reaction("PGI"), ["pgi1", "pgi2"]);
+        assert!(symbols.has_km("pgi2", "g6p_c"));
+        let messages: Vec<String> = enzyme_reaction_diagnostics(&state)
+            .into_iter()
+            .map(|diag| diag.message)
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "Enzyme pgi3 is not defined: add it to the enzymes or fix the enzyme_reaction.",
+                "Reaction f6pdrain is a drain, which is not catalyzed by enzymes."
+            ]
+        );
+    }
+
+    #[test]
+    fn priors_of_unpaired_enzymes_are_reported() {
+        let state = KineticModelState::try_from_str(MODEL.to_string()).unwrap();
+        let priors = PriorsState::try_from_str(
+            r#"kcat = [
+  {enzyme = "pgi1", reaction = "PGI", exploc = 126.0, scale = 0.2},
+  {enzyme = "pgi2", reaction = "PGI", exploc = 100.0, scale = 0.2},
+  {enzyme = "pfk", reaction = "PGI", exploc = 110.0, scale = 0.2},
+  {enzyme = "pgi3", reaction = "PGI", exploc = 110.0, scale = 0.2},
+]
+km = [
+  {metabolite = "g6p", compartment = "c", enzyme = "pgi2", exploc = 3.0, scale = 0.2},
+  {metabolite = "atp", compartment = "c", enzyme = "pgi1", exploc = 0.06, scale = 0.2},
+]
+"#
+            .to_string(),
+        )
+        .unwrap();
+        let diagnostics = prior_pair_diagnostics(
+            priors.borrow_file_str(),
+            priors.borrow_priors(),
+            state.borrow_symbols(),
+        );
+        let messages: Vec<(u32, &str)> = diagnostics
+            .iter()
+            .map(|diag| (diag.range.start.line, diag.message.as_str()))
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                (
+                    3,
+                    "Enzyme pfk does not catalyze reaction PGI, which is catalyzed by pgi1, pgi2."
+                ),
+                (
+                    4,
+                    "kcat prior of pgi3 for PGI is unused: enzyme pgi3 is not defined."
+                ),
+                (
+                    8,
+                    "km prior of pgi1 for atp_c is unused: none of pgi1's reactions involve atp_c."
+                ),
+            ]
+        );
+    }
+}
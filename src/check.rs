@@ -0,0 +1,221 @@
+//! Headless run of the diagnostics over a Maud project, for CI and pre-commit hooks.
+use std::error::Error;
+use std::io::{self, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use lsp_types::{Diagnostic, DiagnosticSeverity, NumberOrString, PublishDiagnosticsParams, Url};
+use serde_json::json;
+
+use crate::settings::DEFAULT_CONFIG_FILE;
+use crate::state::ProjectState;
+
+/// Output format of the `check` subcommand.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Format {
+    Human,
+    Json,
+    Sarif,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "human" => Ok(Format::Human),
+            "json" => Ok(Format::Json),
+            "sarif" => Ok(Format::Sarif),
+            _ => Err(format!(
+                "Invalid format '{s}', expected one of: human, json, sarif"
+            )),
+        }
+    }
+}
+
+/// Load the project at `root_dir`, print its diagnostics and return whether any is an error.
+pub fn check(root_dir: &Path, format: &Format) -> Result<bool, Box<dyn Error + Sync + Send>> {
+    let root_dir = root_dir.canonicalize().map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("unable to read the project {}: {e}", root_dir.display()),
+        )
+    })?;
+    let project = ProjectState::load(&root_dir, DEFAULT_CONFIG_FILE)?;
+    let reports: Vec<PublishDiagnosticsParams> = project
+        .uris()
+        .into_iter()
+        .filter_map(|uri| {
+            project
+                .document_diagnostics(uri)
+                .map(|diagnostics| PublishDiagnosticsParams {
+                    uri: uri.clone(),
+                    diagnostics,
+                    version: None,
+                })
+        })
+        .collect();
+    let out = match format {
+        Format::Human => render_human(&root_dir, &reports),
+        Format::Json => serde_json::to_string_pretty(&reports)? + "\n",
+        Format::Sarif => serde_json::to_string_pretty(&render_sarif(&root_dir, &reports))? + "\n",
+    };
+    match std::io::stdout().lock().write_all(out.as_bytes()) {
+        // the output was piped to a command that exited, like `head`
+        Err(e) if e.kind() != io::ErrorKind::BrokenPipe => return Err(e.into()),
+        _ => (),
+    }
+    Ok(reports
+        .iter()
+        .flat_map(|report| report.diagnostics.iter())
+        .any(is_error))
+}
+
+/// Diagnostics without a severity are shown as errors, like editors do.
+fn is_error(diagnostic: &Diagnostic) -> bool {
+    matches!(diagnostic.severity, Some(DiagnosticSeverity::ERROR) | None)
+}
+
+/// Path of the document relative to the project, as shown to the user.
+fn relative_path(root_dir: &Path, uri: &Url) -> String {
+    uri.to_file_path()
+        .ok()
+        .and_then(|path| {
+            path.strip_prefix(root_dir)
+                .ok()
+                .map(|p| p.display().to_string())
+        })
+        .unwrap_or_else(|| uri.to_string())
+}
+
+fn severity_str(diagnostic: &Diagnostic) -> &'static str {
+    match diagnostic.severity {
+        Some(DiagnosticSeverity::WARNING) => "warning",
+        Some(DiagnosticSeverity::INFORMATION) => "info",
+        Some(DiagnosticSeverity::HINT) => "hint",
+        _ => "error",
+    }
+}
+
+fn code_str(diagnostic: &Diagnostic) -> String {
+    match &diagnostic.code {
+        Some(NumberOrString::Number(n)) => n.to_string(),
+        Some(NumberOrString::String(s)) => s.clone(),
+        None => String::from("maud"),
+    }
+}
+
+/// `file:line:column: severity[code]: message`, with 1-based lines and columns.
+fn render_human(root_dir: &Path, reports: &[PublishDiagnosticsParams]) -> String {
+    let mut out = String::new();
+    let mut errors = 0;
+    let mut warnings = 0;
+    for report in reports {
+        let path = relative_path(root_dir, &report.uri);
+        for diag in &report.diagnostics {
+            if is_error(diag) {
+                errors += 1;
+            } else if diag.severity == Some(DiagnosticSeverity::WARNING) {
+                warnings += 1;
+            }
+            out.push_str(&format!(
+                "{path}:{}:{}: {}[{}]: {}\n",
+                diag.range.start.line + 1,
+                diag.range.start.character + 1,
+                severity_str(diag),
+                code_str(diag),
+                diag.message,
+            ));
+        }
+    }
+    out.push_str(&format!("{errors} error(s), {warnings} warning(s)\n"));
+    out
+}
+
+/// SARIF 2.1.0 log, understood by code scanning tools.
+fn render_sarif(root_dir: &Path, reports: &[PublishDiagnosticsParams]) -> serde_json::Value {
+    let results: Vec<_> = reports
+        .iter()
+        .flat_map(|report| {
+            let path = relative_path(root_dir, &report.uri);
+            report.diagnostics.iter().map(move |diag| {
+                json!({
+                    "ruleId": code_str(diag),
+                    "level": match severity_str(diag) {
+                        "info" | "hint" => "note",
+                        level => level,
+                    },
+                    "message": { "text": diag.message },
+                    "locations": [{
+                        "physicalLocation": {
+                            "artifactLocation": { "uri": path },
+                            "region": {
+                                "startLine": diag.range.start.line + 1,
+                                "startColumn": diag.range.start.character + 1,
+                                "endLine": diag.range.end.line + 1,
+                                "endColumn": diag.range.end.character + 1,
+                            }
+                        }
+                    }]
+                })
+            })
+        })
+        .collect();
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": env!("CARGO_PKG_REPOSITORY"),
+                }
+            },
+            "results": results,
+        }]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mock_project_has_errors() {
+        let has_errors = check(Path::new("tests/mock"), &Format::Json).unwrap();
+        // the mock priors contain duplicates
+        assert!(has_errors);
+    }
+
+    #[test]
+    fn diagnostics_without_severity_are_errors() {
+        let diagnostic = Diagnostic {
+            message: "Duplicated prior.".to_string(),
+            ..Default::default()
+        };
+        assert!(is_error(&diagnostic));
+        let report = PublishDiagnosticsParams {
+            uri: Url::from_file_path("/project/priors.toml").unwrap(),
+            diagnostics: vec![diagnostic],
+            version: None,
+        };
+        let out = render_human(Path::new("/project"), &[report]);
+        assert_eq!(
+            out,
+            "priors.toml:1:1: error[maud]: Duplicated prior.\n1 error(s), 0 warning(s)\n"
+        );
+    }
+
+    #[test]
+    fn missing_projects_are_named() {
+        let err = check(Path::new("tests/no_such_project"), &Format::Human).unwrap_err();
+        assert!(err.to_string().contains("tests/no_such_project"));
+    }
+
+    #[test]
+    fn formats_are_parsed() {
+        assert_eq!("SARIF".parse::<Format>(), Ok(Format::Sarif));
+        assert!("xml".parse::<Format>().is_err());
+    }
+}
@@ -1,11 +1,20 @@
-use lsp_types::{ClientCapabilities, InitializeParams};
+use lsp_types::{ClientCapabilities, InitializeParams, TraceValue};
 use std::path::PathBuf;
 
+use crate::logging::LogOptions;
+use crate::settings::Settings;
+
 /// One-time initialized Config for the LSP.
 #[derive(Clone)]
 pub struct Config {
     pub caps: ClientCapabilities,
     pub root_dir: PathBuf,
+    /// Settings sent in the `initializationOptions`.
+    pub settings: Settings,
+    /// Level and file of the logs sent in the `log` initialization option.
+    pub log: LogOptions,
+    /// Initial trace of the client, changed with `$/setTrace`.
+    pub trace: TraceValue,
 }
 
 impl Config {
@@ -14,9 +23,50 @@ impl Config {
             Some(it) => it,
             None => std::env::current_dir()?,
         };
+        let mut settings = Settings::default();
+        if let Some(options) = init_params.initialization_options.as_ref() {
+            settings.update(options);
+        }
+        let log = init_params
+            .initialization_options
+            .as_ref()
+            .and_then(|options| options.get("log"))
+            .map(|log| LogOptions::from_json(log, &root_path))
+            .unwrap_or_default();
         Ok(Config {
             root_dir: root_path,
             caps: init_params.capabilities,
+            settings,
+            log,
+            trace: init_params.trace.unwrap_or_default(),
         })
     }
+
+    /// Whether the client pulls diagnostics (LSP 3.17) instead of waiting for them.
+    pub fn pull_diagnostics(&self) -> bool {
+        self.caps
+            .text_document
+            .as_ref()
+            .and_then(|caps| caps.diagnostic.as_ref())
+            .is_some()
+    }
+
+    /// Whether the client can be asked to pull the diagnostics again.
+    pub fn diagnostic_refresh(&self) -> bool {
+        self.caps
+            .workspace
+            .as_ref()
+            .and_then(|caps| caps.diagnostic.as_ref())
+            .and_then(|caps| caps.refresh_support)
+            .unwrap_or(false)
+    }
+
+    /// Whether the settings can be requested with `workspace/configuration`.
+    pub fn workspace_configuration(&self) -> bool {
+        self.caps
+            .workspace
+            .as_ref()
+            .and_then(|caps| caps.configuration)
+            .unwrap_or(false)
+    }
 }
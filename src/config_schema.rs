@@ -0,0 +1,684 @@
+//! Schema of the Maud `config.toml`, to validate it and document its keys.
+//!
+//! The config is parsed as TOML and its lines are scanned, like the formatter
+//! does, so that every diagnostic points to its key or value.
+use std::path::{Path, PathBuf};
+
+use lsp_types::{Diagnostic, Position, Range};
+
+use crate::formatting::{key_value, split_comment, Scan};
+use crate::rules::Rule;
+use crate::state::span_to_range;
+
+/// Type of the value of a key.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Kind {
+    Str,
+    /// Path relative to the directory of the config.
+    File,
+    Bool,
+    Int,
+    /// Integers are accepted as floats, like Maud does.
+    Float,
+    Table,
+    Any,
+}
+
+impl Kind {
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Str => "a string",
+            Kind::File => "a path",
+            Kind::Bool => "a boolean",
+            Kind::Int => "an integer",
+            Kind::Float => "a number",
+            Kind::Table => "a table",
+            Kind::Any => "any value",
+        }
+    }
+}
+
+/// Documentation and constraints of a key.
+struct KeySpec {
+    key: &'static str,
+    kind: Kind,
+    doc: &'static str,
+    min: Option<f64>,
+    max: Option<f64>,
+    /// Whether the bounds are excluded.
+    strict: bool,
+    /// Keys of a table, free-form if empty.
+    fields: &'static [KeySpec],
+}
+
+const fn key(key: &'static str, kind: Kind, doc: &'static str) -> KeySpec {
+    KeySpec {
+        key,
+        kind,
+        doc,
+        min: None,
+        max: None,
+        strict: false,
+        fields: &[],
+    }
+}
+
+impl KeySpec {
+    const fn at_least(mut self, min: f64) -> Self {
+        self.min = Some(min);
+        self
+    }
+
+    const fn between(mut self, min: f64, max: f64) -> Self {
+        self.min = Some(min);
+        self.max = Some(max);
+        self
+    }
+
+    const fn strictly(mut self) -> Self {
+        self.strict = true;
+        self
+    }
+
+    const fn with_fields(mut self, fields: &'static [KeySpec]) -> Self {
+        self.fields = fields;
+        self
+    }
+
+    /// Allowed values of a number, e.g. "between 0 and 1 (excluded)".
+    fn bounds(&self) -> Option<String> {
+        let excluded = if self.strict { " (excluded)" } else { "" };
+        match (self.min, self.max) {
+            (Some(min), Some(max)) => Some(format!("between {min} and {max}{excluded}")),
+            (Some(min), None) if self.strict => Some(format!("greater than {min}")),
+            (Some(min), None) => Some(format!("at least {min}")),
+            (None, Some(max)) if self.strict => Some(format!("less than {max}")),
+            (None, Some(max)) => Some(format!("at most {max}")),
+            (None, None) => None,
+        }
+    }
+
+    fn in_bounds(&self, x: f64) -> bool {
+        let above = self
+            .min
+            .is_none_or(|min| if self.strict { x > min } else { x >= min });
+        let below = self
+            .max
+            .is_none_or(|max| if self.strict { x < max } else { x <= max });
+        above && below
+    }
+
+    fn hover(&self) -> String {
+        let mut text = format!("`{}`: {}\n\n{}", self.key, self.kind.name(), self.doc);
+        if let Some(bounds) = self.bounds() {
+            text.push_str(&format!("\n\nMust be {bounds}."));
+        }
+        if !self.fields.is_empty() {
+            let keys: Vec<String> = self.fields.iter().map(|f| format!("`{}`", f.key)).collect();
+            text.push_str(&format!("\n\nKeys: {}.", keys.join(", ")));
+        }
+        text
+    }
+}
+
+/// Arguments of the CmdStanPy sampler.
+const CMDSTANPY: [KeySpec; 17] = [
+    key("chains", Kind::Int, "Number of Markov chains.").at_least(1.),
+    key("parallel_chains", Kind::Int, "Number of chains run in parallel.").at_least(1.),
+    key("threads_per_chain", Kind::Int, "Number of threads of each chain.").at_least(1.),
+    key("iter_warmup", Kind::Int, "Number of warmup iterations of each chain.").at_least(0.),
+    key("iter_sampling", Kind::Int, "Number of draws of each chain after the warmup.").at_least(1.),
+    key("save_warmup", Kind::Bool, "Whether to keep the warmup draws in the output."),
+    key("thin", Kind::Int, "Period between the saved draws.").at_least(1.),
+    key("max_treedepth", Kind::Int, "Maximum depth of the trees of the NUTS sampler.").at_least(1.),
+    key("adapt_delta", Kind::Float, "Target acceptance rate of the step size adaptation. Higher values mean smaller steps and fewer divergences.")
+        .between(0., 1.)
+        .strictly(),
+    key("adapt_engaged", Kind::Bool, "Whether to adapt the step size and metric during warmup."),
+    key("step_size", Kind::Float, "Initial step size of the sampler.").at_least(0.).strictly(),
+    key("metric", Kind::Str, "Metric of the sampler: `diag_e`, `dense_e` or `unit_e`."),
+    key("seed", Kind::Int, "Seed of the random number generator.").between(0., 4294967295.),
+    key("refresh", Kind::Int, "Period of the progress messages, in iterations.").at_least(1.),
+    key("sig_figs", Kind::Int, "Significant figures of the saved draws.").between(1., 18.),
+    key("show_progress", Kind::Bool, "Whether to show progress bars."),
+    key("inits", Kind::Any, "Initial values of the parameters, or a radius around 0."),
+];
+
+/// Settings of the ODE solver.
+const ODE: [KeySpec; 4] = [
+    key(
+        "abs_tol",
+        Kind::Float,
+        "Absolute tolerance of the ODE solver.",
+    )
+    .at_least(0.)
+    .strictly(),
+    key(
+        "rel_tol",
+        Kind::Float,
+        "Relative tolerance of the ODE solver.",
+    )
+    .at_least(0.)
+    .strictly(),
+    key(
+        "max_num_steps",
+        Kind::Int,
+        "Maximum number of steps of the ODE solver.",
+    )
+    .at_least(1.),
+    key(
+        "timepoint",
+        Kind::Float,
+        "Time at which the ODE solution should be at steady state.",
+    )
+    .at_least(0.)
+    .strictly(),
+];
+
+/// Keys of the config, as read by Maud.
+const CONFIG: [KeySpec; 23] = [
+    key("name", Kind::Str, "Name of the model, used in the name of the output directory."),
+    key("description", Kind::Str, "Free text describing the model."),
+    key("kinetic_model_file", Kind::File, "TOML file of the kinetic model, relative to this config."),
+    key("priors_file", Kind::File, "TOML or CSV file of the priors, relative to this config."),
+    key("experiments_file", Kind::File, "TOML or CSV file of the experiments and their measurements, relative to this config."),
+    key("likelihood", Kind::Bool, "Whether to use the measurements. Without them, Maud samples from the priors."),
+    key("cmdstanpy_config", Kind::Table, "Arguments of the CmdStanPy `sample` method.")
+        .with_fields(&CMDSTANPY),
+    key("cmdstanpy_config_predict", Kind::Table, "Arguments of the CmdStanPy `sample` method for out-of-sample predictions.")
+        .with_fields(&CMDSTANPY),
+    key("variational_options", Kind::Table, "Arguments of the CmdStanPy `variational` method."),
+    key("stanc_options", Kind::Table, "Options of the Stan compiler."),
+    key("cpp_options", Kind::Table, "Options of the C++ compiler, e.g. `STAN_THREADS`."),
+    key("ode_config", Kind::Table, "Settings of the ODE solver that finds the steady states.")
+        .with_fields(&ODE),
+    key("ode_solver_config", Kind::Table, "Settings of the ODE solver that finds the steady states.")
+        .with_fields(&ODE),
+    key("reject_non_steady", Kind::Bool, "Whether to reject the draws whose ODE solution is not at steady state."),
+    key("penalize_non_steady", Kind::Bool, "Whether to penalize, instead of rejecting, the draws that are not at steady state."),
+    key("steady_state_threshold_abs", Kind::Float, "Absolute rate of change of the concentrations under which they are at steady state.")
+        .at_least(0.)
+        .strictly(),
+    key("steady_state_threshold_rel", Kind::Float, "Rate of change of the concentrations, relative to them, under which they are at steady state.")
+        .at_least(0.)
+        .strictly(),
+    key("steady_state_penalty_rel", Kind::Float, "Relative penalty of the draws that are not at steady state.")
+        .at_least(0.),
+    key("drain_small_conc_corrector", Kind::Float, "Small number that keeps the drains from making concentrations negative.")
+        .at_least(0.),
+    key("user_inits_file", Kind::File, "TOML or CSV file of initial values of the parameters, relative to this config."),
+    key("dgf_mean_file", Kind::File, "CSV file of the means of the formation energies, relative to this config."),
+    key("dgf_covariance_file", Kind::File, "CSV file of the covariance matrix of the formation energies, relative to this config."),
+    key("tool", Kind::Table, "Settings of other tools, like `[tool.maud-lsp]`."),
+];
+
+const REQUIRED: [&str; 3] = ["kinetic_model_file", "priors_file", "experiments_file"];
+
+fn find_spec<'a>(specs: &'a [KeySpec], key: &str) -> Option<&'a KeySpec> {
+    specs.iter().find(|spec| spec.key == key)
+}
+
+/// `key = value` of the config, in the table of the last header.
+struct Entry<'a> {
+    table: Option<&'a str>,
+    key: &'a str,
+    /// Whole value, even if it spans several lines.
+    value: String,
+    line: usize,
+    key_range: Range,
+    /// Range of the first line of the value.
+    value_range: Range,
+}
+
+impl<'a> Entry<'a> {
+    /// Keys from the root of the config, e.g. `["cmdstanpy_config", "chains"]`
+    /// for `chains` under `[cmdstanpy_config]` or `cmdstanpy_config.chains`.
+    fn path(&self) -> Vec<&'a str> {
+        let mut path = self.table.map(key_path).unwrap_or_default();
+        path.extend(key_path(self.key));
+        path
+    }
+}
+
+/// Keys of a dotted key, e.g. `["tool", "maud-lsp"]` for `tool."maud-lsp"`.
+fn key_path(key: &str) -> Vec<&str> {
+    key.split('.')
+        .map(|part| part.trim().trim_matches(['"', '\'']))
+        .collect()
+}
+
+/// Table header of a line, e.g. `cmdstanpy_config` for `[cmdstanpy_config]`.
+fn header(line: &str) -> Option<&str> {
+    let content = split_comment(line).0.trim();
+    let name = content.strip_prefix('[')?.strip_suffix(']')?;
+    Some(name.trim_matches(['[', ']']).trim())
+}
+
+/// Range of a part of a line, in UTF-16 columns like [`span_to_range`].
+fn range_in_line(line: &str, line_number: usize, part: &str) -> Range {
+    // the parts are slices of the line
+    let start = part.as_ptr() as usize - line.as_ptr() as usize;
+    let mut range = span_to_range(line, (start, start + part.len()));
+    range.start.line = line_number as u32;
+    range.end.line = line_number as u32;
+    range
+}
+
+fn entries(text: &str) -> (Vec<Entry<'_>>, Vec<(usize, &str)>) {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut entries = Vec::new();
+    let mut headers = Vec::new();
+    let mut table = None;
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        if let Some(name) = header(line) {
+            headers.push((i, name));
+            table = Some(name);
+            i += 1;
+            continue;
+        }
+        let start = i;
+        let mut scan = Scan::default();
+        scan.line(line);
+        i += 1;
+        while scan.open() && i < lines.len() {
+            scan.line(lines[i]);
+            i += 1;
+        }
+        let content = split_comment(line).0;
+        let Some((key, first)) = key_value(content) else {
+            continue;
+        };
+        let mut value = first.to_string();
+        for next in lines[start + 1..i].iter() {
+            value.push('\n');
+            value.push_str(next);
+        }
+        let key = key.trim_matches(['"', '\'']);
+        entries.push(Entry {
+            table,
+            key,
+            value: split_comment(&value).0.trim().to_string(),
+            line: start,
+            key_range: range_in_line(line, start, key),
+            value_range: range_in_line(line, start, first),
+        });
+    }
+    (entries, headers)
+}
+
+fn diagnostic(range: Range, rule: Rule, message: String) -> Diagnostic {
+    Diagnostic {
+        range,
+        severity: Some(rule.default_severity()),
+        code: Some(rule.diagnostic_code()),
+        message,
+        ..Default::default()
+    }
+}
+
+/// Why a value does not match its key, if it does not.
+fn check_value(spec: &KeySpec, value: &toml::Value) -> Option<String> {
+    let key = spec.key;
+    let number = match (spec.kind, value) {
+        (Kind::Any, _)
+        | (Kind::Str | Kind::File, toml::Value::String(_))
+        | (Kind::Bool, toml::Value::Boolean(_))
+        | (Kind::Table, toml::Value::Table(_)) => None,
+        (Kind::Int, toml::Value::Integer(int)) => Some(*int as f64),
+        (Kind::Float, toml::Value::Integer(int)) => Some(*int as f64),
+        (Kind::Float, toml::Value::Float(float)) => Some(*float),
+        (Kind::Int, toml::Value::Float(float)) if float.fract() == 0. && float.abs() < 1e18 => {
+            return Some(format!(
+                "`{key}` must be an integer, not a float: write {} instead.",
+                *float as i64
+            ))
+        }
+        (kind, value) => {
+            return Some(format!(
+                "`{key}` must be {}, not {}.",
+                kind.name(),
+                value.type_str()
+            ))
+        }
+    };
+    let number = number?;
+    (!spec.in_bounds(number)).then(|| format!("`{key}` must be {}.", spec.bounds().unwrap()))
+}
+
+fn parse_value(value: &str) -> Option<toml::Value> {
+    let mut table: toml::value::Table = toml::from_str(&format!("value = {value}")).ok()?;
+    table.remove("value")
+}
+
+/// Where a key of the config and its value are written.
+struct Location {
+    key: Range,
+    value: Range,
+    /// Whether the key is a table header, like `[cmdstanpy_config]`.
+    header: bool,
+}
+
+/// Lines of the config, scanned to locate the keys of its parsed value.
+struct Lines<'a> {
+    text: &'a str,
+    entries: Vec<Entry<'a>>,
+    headers: Vec<(usize, &'a str)>,
+}
+
+impl<'a> Lines<'a> {
+    fn new(text: &'a str) -> Self {
+        let (entries, headers) = entries(text);
+        Lines {
+            text,
+            entries,
+            headers,
+        }
+    }
+
+    /// Location of the key at `path`, or of the inline table that contains it.
+    fn locate(&self, path: &[&str]) -> Option<Location> {
+        if let Some(entry) = self.entries.iter().find(|entry| entry.path() == path) {
+            return Some(Location {
+                key: entry.key_range,
+                value: entry.value_range,
+                header: false,
+            });
+        }
+        if let Some(entry) = self
+            .entries
+            .iter()
+            .find(|entry| path.starts_with(&entry.path()))
+        {
+            return Some(Location {
+                key: entry.value_range,
+                value: entry.value_range,
+                header: false,
+            });
+        }
+        let (line, name) = self
+            .headers
+            .iter()
+            .find(|(_, name)| key_path(name) == path)?;
+        let range = range_in_line(self.text.lines().nth(*line)?, *line, name);
+        Some(Location {
+            key: range,
+            value: range,
+            header: true,
+        })
+    }
+
+    fn value_range(&self, path: &[&str]) -> Range {
+        self.locate(path)
+            .map(|location| location.value)
+            .unwrap_or_default()
+    }
+}
+
+/// Check the value of a key, and the keys of its table.
+fn check_entry(
+    path: &[&str],
+    spec: &KeySpec,
+    value: &toml::Value,
+    root_dir: &Path,
+    lines: &Lines,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    if let Some(message) = check_value(spec, value) {
+        diagnostics.push(diagnostic(
+            lines.value_range(path),
+            Rule::InvalidConfig,
+            message,
+        ));
+        return;
+    }
+    match (value, spec.kind) {
+        (toml::Value::String(file), Kind::File) if !root_dir.join(file).exists() => diagnostics
+            .push(diagnostic(
+                lines.value_range(path),
+                Rule::MissingFile,
+                format!("File {file} does not exist."),
+            )),
+        (toml::Value::Table(table), _) if !spec.fields.is_empty() => {
+            for (key, value) in table {
+                let field_path = [path, &[key.as_str()]].concat();
+                match find_spec(spec.fields, key) {
+                    Some(field) => diagnostics.extend(check_value(field, value).map(|message| {
+                        diagnostic(lines.value_range(&field_path), Rule::InvalidConfig, message)
+                    })),
+                    None => diagnostics.push(diagnostic(
+                        lines
+                            .locate(&field_path)
+                            .map(|location| location.key)
+                            .unwrap_or_default(),
+                        Rule::UnknownConfigKey,
+                        format!("Unknown key `{key}` in `{}`.", spec.key),
+                    )),
+                }
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Unknown keys, wrong values and missing files of the config.
+///
+/// The config is parsed as TOML, so that dotted keys and inline tables are
+/// understood like Maud does, and its lines are only scanned for the ranges.
+pub fn config_diagnostics(text: &str, root_dir: &Path) -> Vec<Diagnostic> {
+    let config: toml::value::Table = match toml::from_str(text) {
+        Ok(config) => config,
+        Err(e) => {
+            let position = e
+                .line_col()
+                .map(|(line, col)| Position::new(line as u32, col as u32))
+                .unwrap_or_default();
+            return vec![diagnostic(
+                Range::new(position, position),
+                Rule::InvalidConfig,
+                format!("Invalid config: {e}"),
+            )];
+        }
+    };
+    let lines = Lines::new(text);
+    let mut diagnostics = Vec::new();
+    for (key, value) in config.iter() {
+        match find_spec(&CONFIG, key) {
+            // the tables of other tools, like `[tool.maud-lsp]`, are free-form
+            Some(spec) => check_entry(&[key], spec, value, root_dir, &lines, &mut diagnostics),
+            None => {
+                let location = lines.locate(&[key]);
+                let (range, header) = location
+                    .map(|location| (location.key, location.header))
+                    .unwrap_or_default();
+                let kind = if header { "table" } else { "key" };
+                diagnostics.push(diagnostic(
+                    range,
+                    Rule::UnknownConfigKey,
+                    format!("Unknown {kind} `{key}` in the Maud config."),
+                ));
+            }
+        }
+    }
+    // the parsed tables are sorted by key
+    diagnostics.sort_by_key(|diag| (diag.range.start.line, diag.range.start.character));
+    for required in REQUIRED {
+        if !config.contains_key(required) {
+            diagnostics.push(diagnostic(
+                Range::default(),
+                Rule::InvalidConfig,
+                format!("Missing required key `{required}`."),
+            ));
+        }
+    }
+    diagnostics
+}
+
+/// Paths of the file-valued keys of the config, with the range of the path
+/// inside its quotes. Paths are relative to `root_dir`.
+pub fn config_file_links(text: &str, root_dir: &Path) -> Vec<(Range, PathBuf)> {
+    let (entries, _) = entries(text);
+    entries
+        .iter()
+        .filter(|entry| {
+            entry.table.is_none()
+                && find_spec(&CONFIG, entry.key).is_some_and(|spec| spec.kind == Kind::File)
+        })
+        .filter_map(|entry| {
+            let toml::Value::String(path) = parse_value(&entry.value)? else {
+                return None;
+            };
+            let mut range = entry.value_range;
+            // without the quotes
+            range.start.character += 1;
+            range.end.character = range.start.character + path.encode_utf16().count() as u32;
+            Some((range, root_dir.join(path)))
+        })
+        .collect()
+}
+
+/// Documentation of the key or table header at a position of the config.
+pub fn config_hover(text: &str, position: Position) -> Option<String> {
+    let line = position.line as usize;
+    let column = position.character;
+    let (entries, headers) = entries(text);
+    if let Some((_, name)) = headers.iter().find(|(l, _)| *l == line) {
+        return find_spec(&CONFIG, name).map(KeySpec::hover);
+    }
+    let entry = entries.iter().find(|entry| {
+        entry.line == line
+            && entry.key_range.start.character <= column
+            && column <= entry.key_range.end.character
+    })?;
+    let spec = match entry.path()[..] {
+        [key] => find_spec(&CONFIG, key),
+        [table, key] => find_spec(&CONFIG, table).and_then(|parent| find_spec(parent.fields, key)),
+        _ => None,
+    };
+    spec.map(KeySpec::hover)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG_TOML: &str = r#"name = "ecoli"
+kinetic_model_file = "ecoli_kinetic_model.toml"
+priors_file = "priors.toml"
+experiments_file = "missing.toml"
+likelihood = "yes"
+colour = "blue"
+
+[cmdstanpy_config]
+chains = 0
+adapt_delta = 0.95
+
+[ode_config]
+max_num_steps = 1e9
+"#;
+
+    #[test]
+    fn config_is_validated() {
+        let diagnostics = config_diagnostics(CONFIG_TOML, Path::new("tests/mock"));
+        let messages: Vec<(u32, &str)> = diagnostics
+            .iter()
+            .map(|diag| (diag.range.start.line, diag.message.as_str()))
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                (3, "File missing.toml does not exist."),
+                (4, "`likelihood` must be a boolean, not string."),
+                (5, "Unknown key `colour` in the Maud config."),
+                (8, "`chains` must be at least 1."),
+                (
+                    12,
+                    "`max_num_steps` must be an integer, not a float: write 1000000000 instead."
+                ),
+            ]
+        );
+        assert_eq!(
+            diagnostics[2].code,
+            Some(Rule::UnknownConfigKey.diagnostic_code())
+        );
+    }
+
+    #[test]
+    fn dotted_keys_and_inline_tables_are_understood() {
+        let text = r#"kinetic_model_file = "ecoli_kinetic_model.toml"
+priors_file = "priors.toml"
+experiments_file = "experiments.toml"
+cmdstanpy_config.chains = 4
+cmdstanpy_config.adapt_delta = 1.5
+ode_config = {max_num_steps = 100, colour = 1}
+
+[tool.maud-lsp.diagnostics]
+missing-kcat = "off"
+"#;
+        let diagnostics = config_diagnostics(text, Path::new("tests/mock"));
+        let messages: Vec<(Range, &str)> = diagnostics
+            .iter()
+            .map(|diag| (diag.range, diag.message.as_str()))
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                (
+                    Range::new(Position::new(4, 31), Position::new(4, 34)),
+                    "`adapt_delta` must be between 0 and 1 (excluded)."
+                ),
+                (
+                    Range::new(Position::new(5, 13), Position::new(5, 46)),
+                    "Unknown key `colour` in `ode_config`."
+                ),
+            ]
+        );
+        assert!(config_hover(text, Position::new(3, 18))
+            .unwrap()
+            .starts_with("`chains`"));
+    }
+
+    #[test]
+    fn ranges_are_in_utf16_columns() {
+        let text = r#"kinetic_model_file = "ecoli_kinetic_model.toml"
+priors_file = "priors.toml"
+experiments_file = "experiments.toml"
+"modèle" = "é" # "modèle"
+"#;
+        let diagnostics = config_diagnostics(text, Path::new("tests/mock"));
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].range,
+            Range::new(Position::new(3, 1), Position::new(3, 7))
+        );
+    }
+
+    #[test]
+    fn syntax_errors_are_reported() {
+        let diagnostics = config_diagnostics("name = \"ecoli\nlikelihood = true\n", Path::new("."));
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].message.starts_with("Invalid config"));
+    }
+
+    #[test]
+    fn file_keys_are_linked() {
+        let links = config_file_links(CONFIG_TOML, Path::new("tests/mock"));
+        assert_eq!(links.len(), 3);
+        assert_eq!(
+            links[1].0,
+            Range::new(Position::new(2, 15), Position::new(2, 26))
+        );
+        assert_eq!(links[1].1, Path::new("tests/mock/priors.toml"));
+    }
+
+    #[test]
+    fn keys_are_documented() {
+        let hover = config_hover(CONFIG_TOML, Position::new(9, 3)).unwrap();
+        assert!(hover.starts_with("`adapt_delta`: a number"));
+        assert!(hover.contains("between 0 and 1 (excluded)"));
+        assert!(config_hover(CONFIG_TOML, Position::new(7, 3))
+            .unwrap()
+            .contains("`chains`"));
+    }
+}
@@ -0,0 +1,671 @@
+//! Maud CSV inputs: priors and measurements tables.
+//!
+//! Columns are recognized by their header, so that both the old
+//! (`metabolite_id`, `mic_id`, `experiment_id`...) and the short names
+//! (`metabolite`, `experiment`...) are understood.
+use std::collections::HashSet;
+
+use lsp_types::{CompletionItem, CompletionItemKind, Diagnostic, Position};
+
+use crate::catalysis::{kcat_mismatch, km_mismatch};
+use crate::logging::span;
+use crate::priors::{describe_distribution, PriorCoverage};
+use crate::rules::Rule;
+use crate::state::{position_to_offset, span_to_range};
+use crate::symbols::SymbolIndex;
+
+pub const PARAMETER_TYPES: [&str; 5] = ["kcat", "km", "conc_enzyme", "conc_unbalanced", "drain"];
+pub const MEASUREMENT_TYPES: [&str; 3] = ["mic", "flux", "enzyme"];
+
+/// A field of the CSV with the span of its contents in the document.
+#[derive(Debug, Clone, Default)]
+pub struct Cell {
+    pub value: String,
+    /// Byte range of the value, without the surrounding quotes.
+    pub span: (usize, usize),
+}
+
+/// What the values of a column refer to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnKind {
+    Metabolite,
+    Compartment,
+    /// Metabolite in a compartment, e.g. `g6p_c`.
+    MetComp,
+    Enzyme,
+    Reaction,
+    Experiment,
+    /// Metabolite in compartment, reaction or enzyme, depending on the measurement type.
+    Target,
+    ParameterType,
+    MeasurementType,
+    Number,
+    Other,
+}
+
+impl ColumnKind {
+    pub(crate) fn from_header(header: &str) -> Self {
+        match header.trim().to_lowercase().as_str() {
+            "metabolite" | "metabolite_id" => ColumnKind::Metabolite,
+            "compartment" | "compartment_id" => ColumnKind::Compartment,
+            "mic" | "mic_id" | "met_comp" => ColumnKind::MetComp,
+            "enzyme" | "enzyme_id" => ColumnKind::Enzyme,
+            "reaction" | "reaction_id" | "drain" | "drain_id" => ColumnKind::Reaction,
+            "experiment" | "experiment_id" => ColumnKind::Experiment,
+            "target_id" | "target" => ColumnKind::Target,
+            "parameter_type" | "parameter" | "prior_type" => ColumnKind::ParameterType,
+            "measurement_type" | "target_type" => ColumnKind::MeasurementType,
+            "location" | "exploc" | "scale" | "pct1" | "pct99" | "measurement" | "value"
+            | "error_scale" | "error" => ColumnKind::Number,
+            _ => ColumnKind::Other,
+        }
+    }
+
+    pub(crate) fn describe(&self) -> &'static str {
+        match self {
+            ColumnKind::Metabolite => "metabolite",
+            ColumnKind::Compartment => "compartment",
+            ColumnKind::MetComp => "metabolite in compartment",
+            ColumnKind::Enzyme => "enzyme",
+            ColumnKind::Reaction => "reaction",
+            ColumnKind::Experiment => "experiment",
+            ColumnKind::Target => "target",
+            ColumnKind::ParameterType => "parameter type",
+            ColumnKind::MeasurementType => "measurement type",
+            ColumnKind::Number => "number",
+            ColumnKind::Other => "value",
+        }
+    }
+}
+
+/// Identifiers defined in the project, to resolve the cells against.
+#[derive(Default)]
+pub struct KnownIds {
+    pub metabolites: HashSet<String>,
+    pub compartments: HashSet<String>,
+    pub met_comps: HashSet<String>,
+    pub reactions: HashSet<String>,
+    pub enzymes: HashSet<String>,
+    pub experiments: HashSet<String>,
+}
+
+impl KnownIds {
+    fn of_kind(&self, kind: ColumnKind) -> Option<&HashSet<String>> {
+        match kind {
+            ColumnKind::Metabolite => Some(&self.metabolites),
+            ColumnKind::Compartment => Some(&self.compartments),
+            ColumnKind::MetComp => Some(&self.met_comps),
+            ColumnKind::Enzyme => Some(&self.enzymes),
+            ColumnKind::Reaction => Some(&self.reactions),
+            ColumnKind::Experiment => Some(&self.experiments),
+            _ => None,
+        }
+    }
+}
+
+/// Prior of a row of a priors table.
+pub struct CsvPrior<'a> {
+    pub parameter: &'a str,
+    pub enzyme: Option<&'a str>,
+    pub reaction: Option<&'a str>,
+    pub met_comp: Option<String>,
+    pub experiment: Option<&'a str>,
+    pub distribution: String,
+    /// Byte offset of the row.
+    pub start: usize,
+}
+
+/// Parsed CSV document.
+pub struct CsvTable {
+    pub text: String,
+    pub header: Vec<Cell>,
+    pub kinds: Vec<ColumnKind>,
+    pub rows: Vec<Vec<Cell>>,
+}
+
+/// Split the text in records of cells, honoring quotes (`""` escapes a quote).
+fn parse_records(text: &str) -> Vec<Vec<Cell>> {
+    let bytes = text.as_bytes();
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut i = 0;
+    while i <= bytes.len() {
+        let (cell, next) = if bytes.get(i) == Some(&b'"') {
+            let start = i + 1;
+            let mut value = String::new();
+            let mut j = start;
+            let mut chunk = start;
+            while j < bytes.len() {
+                if bytes[j] == b'"' {
+                    value.push_str(&text[chunk..j]);
+                    if bytes.get(j + 1) == Some(&b'"') {
+                        value.push('"');
+                        j += 2;
+                        chunk = j;
+                        continue;
+                    }
+                    break;
+                }
+                j += 1;
+            }
+            if j >= bytes.len() {
+                value.push_str(&text[chunk.min(bytes.len())..]);
+            }
+            let end = j.min(bytes.len());
+            // skip whatever is between the closing quote and the delimiter
+            let next = text[end..]
+                .find([',', '\n'])
+                .map_or(bytes.len(), |k| end + k);
+            (
+                Cell {
+                    value,
+                    span: (start.min(end), end),
+                },
+                next,
+            )
+        } else {
+            let next = text[i.min(bytes.len())..]
+                .find([',', '\n'])
+                .map_or(bytes.len(), |k| i + k);
+            let raw = &text[i.min(bytes.len())..next];
+            let trimmed = raw.trim_end_matches('\r');
+            (
+                Cell {
+                    value: trimmed.to_string(),
+                    span: (i, i + trimmed.len()),
+                },
+                next,
+            )
+        };
+        record.push(cell);
+        match bytes.get(next) {
+            Some(b',') => i = next + 1,
+            _ => {
+                // skip blank lines
+                if !(record.len() == 1 && record[0].value.trim().is_empty()) {
+                    records.push(std::mem::take(&mut record));
+                } else {
+                    record.clear();
+                }
+                i = next + 1;
+            }
+        }
+    }
+    records
+}
+
+impl CsvTable {
+    pub fn parse(text: String) -> Self {
+        let _span = span("parse CSV table");
+        let mut records = parse_records(&text).into_iter();
+        let header = records.next().unwrap_or_default();
+        let kinds = header
+            .iter()
+            .map(|cell| ColumnKind::from_header(&cell.value))
+            .collect();
+        CsvTable {
+            header,
+            kinds,
+            rows: records.collect(),
+            text,
+        }
+    }
+
+    fn column(&self, kind: ColumnKind) -> Option<usize> {
+        self.kinds.iter().position(|k| *k == kind)
+    }
+
+    pub(crate) fn value<'a>(&self, row: &'a [Cell], kind: ColumnKind) -> Option<&'a str> {
+        row.get(self.column(kind)?)
+            .map(|cell| cell.value.trim())
+            .filter(|value| !value.is_empty())
+    }
+
+    /// Number in the column with this header.
+    pub(crate) fn number(&self, row: &[Cell], header: &str) -> Option<f64> {
+        let column = self
+            .header
+            .iter()
+            .position(|cell| cell.value.trim() == header)?;
+        row.get(column)?.value.trim().parse().ok()
+    }
+
+    /// Whether this is a priors table, as opposed to a measurements table.
+    pub fn is_priors(&self) -> bool {
+        self.column(ColumnKind::ParameterType).is_some()
+    }
+
+    /// What a cell refers to, resolving the target of measurements.
+    fn cell_kind(&self, row: &[Cell], column: usize) -> ColumnKind {
+        match self.kinds.get(column) {
+            Some(ColumnKind::Target) => match self.value(row, ColumnKind::MeasurementType) {
+                Some("mic") => ColumnKind::MetComp,
+                Some("flux") => ColumnKind::Reaction,
+                Some("enzyme") => ColumnKind::Enzyme,
+                _ => ColumnKind::Other,
+            },
+            Some(kind) => *kind,
+            None => ColumnKind::Other,
+        }
+    }
+
+    /// Row, column and cell under the byte offset.
+    fn cell_at(&self, offset: usize) -> Option<(&[Cell], usize, &Cell)> {
+        self.rows.iter().find_map(|row| {
+            row.iter()
+                .position(|cell| cell.span.0 <= offset && offset <= cell.span.1)
+                .map(|col| (row.as_slice(), col, &row[col]))
+        })
+    }
+
+    /// Identifier under the cursor and what it refers to.
+    pub fn symbol_at(&self, position: Position) -> Option<(ColumnKind, &str)> {
+        let (row, col, cell) = self.cell_at(position_to_offset(&self.text, position))?;
+        let value = cell.value.trim();
+        (!value.is_empty()).then(|| (self.cell_kind(row, col), value))
+    }
+
+    /// Spans of the cells, or parts of metabolite-in-compartment cells, that
+    /// refer to the identifier of an entity of some kind.
+    pub fn references(&self, kind: ColumnKind, id: &str) -> Vec<(usize, usize)> {
+        let mut spans = Vec::new();
+        for row in self.rows.iter() {
+            for (col, cell) in row.iter().enumerate() {
+                let value = cell.value.trim();
+                let start = cell.span.0 + cell.value.find(value).unwrap_or(0);
+                let cell_kind = self.cell_kind(row, col);
+                if cell_kind == kind && value == id {
+                    spans.push((start, start + id.len()));
+                } else if cell_kind == ColumnKind::MetComp {
+                    // invalid ids may have underscores, so the cell is not split
+                    let met = value.strip_suffix(id).and_then(|met| met.strip_suffix('_'));
+                    let comp = value
+                        .strip_prefix(id)
+                        .and_then(|comp| comp.strip_prefix('_'));
+                    match kind {
+                        ColumnKind::Metabolite if comp.is_some_and(|comp| !comp.is_empty()) => {
+                            spans.push((start, start + id.len()))
+                        }
+                        ColumnKind::Compartment if met.is_some_and(|met| !met.is_empty()) => {
+                            spans.push((start + value.len() - id.len(), start + value.len()))
+                        }
+                        _ => (),
+                    }
+                }
+            }
+        }
+        spans
+    }
+
+    /// Identifiers of the experiments referenced in the table.
+    pub fn experiments(&self) -> Vec<String> {
+        let mut seen = HashSet::new();
+        self.rows
+            .iter()
+            .filter_map(|row| self.value(row, ColumnKind::Experiment))
+            .filter(|exp| seen.insert(*exp))
+            .map(String::from)
+            .collect()
+    }
+
+    /// Metabolite in compartment of a row, from a `mic_id` or from its two parts.
+    pub(crate) fn met_comp(&self, row: &[Cell]) -> Option<String> {
+        self.value(row, ColumnKind::MetComp)
+            .map(String::from)
+            .or_else(|| {
+                Some(format!(
+                    "{}_{}",
+                    self.value(row, ColumnKind::Metabolite)?,
+                    self.value(row, ColumnKind::Compartment)?
+                ))
+            })
+    }
+
+    /// Parameters with a prior in this table.
+    pub fn prior_coverage(&self) -> PriorCoverage {
+        let mut coverage = PriorCoverage::default();
+        for row in &self.rows {
+            let reaction = self.value(row, ColumnKind::Reaction).map(String::from);
+            let enzyme = self.value(row, ColumnKind::Enzyme).map(String::from);
+            let experiment = self.value(row, ColumnKind::Experiment).map(String::from);
+            match self.value(row, ColumnKind::ParameterType) {
+                Some("kcat") => coverage.kcat.extend(enzyme.zip(reaction)),
+                Some("km") => coverage.km.extend(enzyme.zip(self.met_comp(row))),
+                Some("conc_enzyme") => coverage.conc_enzyme.extend(enzyme.zip(experiment)),
+                Some("drain") => coverage.drain.extend(reaction.zip(experiment)),
+                _ => (),
+            }
+        }
+        coverage
+    }
+
+    /// Priors of the rows with a parameter type, in the order of the table.
+    pub fn priors(&self) -> Vec<CsvPrior<'_>> {
+        self.rows
+            .iter()
+            .filter_map(|row| {
+                let number = |header: &str| self.number(row, header);
+                Some(CsvPrior {
+                    parameter: self.value(row, ColumnKind::ParameterType)?,
+                    enzyme: self.value(row, ColumnKind::Enzyme),
+                    reaction: self.value(row, ColumnKind::Reaction),
+                    met_comp: self.met_comp(row),
+                    experiment: self.value(row, ColumnKind::Experiment),
+                    distribution: describe_distribution(
+                        number("location").or_else(|| number("exploc")),
+                        number("scale"),
+                        number("pct1"),
+                        number("pct99"),
+                    ),
+                    start: row[0].span.0,
+                })
+            })
+            .collect()
+    }
+
+    /// Kcat and km priors of enzymes that are not paired with their reaction or
+    /// metabolite in the `enzyme_reaction` of the kinetic model.
+    pub fn prior_pair_diagnostics(&self, symbols: &SymbolIndex) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        for row in &self.rows {
+            let Some(enzyme) = self.value(row, ColumnKind::Enzyme) else {
+                continue;
+            };
+            let message = match self.value(row, ColumnKind::ParameterType) {
+                Some("kcat") => self
+                    .value(row, ColumnKind::Reaction)
+                    .and_then(|reaction| kcat_mismatch(symbols, enzyme, reaction)),
+                Some("km") => self
+                    .met_comp(row)
+                    .and_then(|met_comp| km_mismatch(symbols, enzyme, &met_comp)),
+                _ => None,
+            };
+            if let Some(message) = message {
+                let span = (row[0].span.0, row[row.len() - 1].span.1);
+                diagnostics.push(self.diagnostic(span, Rule::EnzymeReactionMismatch, message));
+            }
+        }
+        diagnostics
+    }
+
+    fn diagnostic(&self, span: (usize, usize), rule: Rule, message: String) -> Diagnostic {
+        Diagnostic {
+            range: span_to_range(&self.text, span),
+            severity: Some(rule.default_severity()),
+            code: Some(rule.diagnostic_code()),
+            message,
+            ..Default::default()
+        }
+    }
+
+    /// Malformed rows, invalid values and identifiers not found in the project.
+    pub fn diagnostics(&self, known: &KnownIds) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        for row in &self.rows {
+            if row.len() != self.header.len() {
+                let span = (row[0].span.0, row[row.len() - 1].span.1);
+                diagnostics.push(self.diagnostic(
+                    span,
+                    Rule::InvalidCsv,
+                    format!(
+                        "Expected {} fields, found {}.",
+                        self.header.len(),
+                        row.len()
+                    ),
+                ));
+            }
+            for (col, cell) in row.iter().enumerate() {
+                let value = cell.value.trim();
+                if value.is_empty() {
+                    continue;
+                }
+                let kind = self.cell_kind(row, col);
+                let message = match kind {
+                    ColumnKind::Number if value.parse::<f64>().is_err() => {
+                        Some((Rule::InvalidCsv, format!("'{value}' is not a number.")))
+                    }
+                    ColumnKind::ParameterType if !PARAMETER_TYPES.contains(&value) => Some((
+                        Rule::InvalidCsv,
+                        format!(
+                            "Unknown parameter type '{value}', expected one of {PARAMETER_TYPES:?}."
+                        ),
+                    )),
+                    ColumnKind::MeasurementType if !MEASUREMENT_TYPES.contains(&value) => Some((
+                        Rule::InvalidCsv,
+                        format!(
+                            "Unknown measurement type '{value}', expected one of {MEASUREMENT_TYPES:?}."
+                        ),
+                    )),
+                    _ => known
+                        .of_kind(kind)
+                        .filter(|ids| !ids.contains(value))
+                        .map(|_| {
+                            (
+                                Rule::UnknownId,
+                                format!("Unknown {} '{value}'.", kind.describe()),
+                            )
+                        }),
+                };
+                if let Some((rule, message)) = message {
+                    diagnostics.push(self.diagnostic(cell.span, rule, message));
+                }
+            }
+        }
+        diagnostics
+    }
+
+    /// Values that fit the cell under the cursor.
+    pub fn completions(&self, position: Position, known: &KnownIds) -> Vec<CompletionItem> {
+        let offset = position_to_offset(&self.text, position);
+        let (kind, prefix) = match self.cell_at(offset) {
+            Some((row, col, cell)) => (
+                self.cell_kind(row, col),
+                self.text
+                    .get(cell.span.0..offset)
+                    .unwrap_or_default()
+                    .trim(),
+            ),
+            None => return Vec::new(),
+        };
+        let candidates: Vec<&str> = match kind {
+            ColumnKind::ParameterType => PARAMETER_TYPES.to_vec(),
+            ColumnKind::MeasurementType => MEASUREMENT_TYPES.to_vec(),
+            kind => match known.of_kind(kind) {
+                Some(ids) => ids.iter().map(String::as_str).collect(),
+                None => Vec::new(),
+            },
+        };
+        let mut items: Vec<CompletionItem> = candidates
+            .into_iter()
+            .filter(|candidate| candidate.starts_with(prefix))
+            .map(|candidate| CompletionItem {
+                label: candidate.to_string(),
+                kind: Some(match kind {
+                    ColumnKind::ParameterType | ColumnKind::MeasurementType => {
+                        CompletionItemKind::ENUM_MEMBER
+                    }
+                    _ => CompletionItemKind::REFERENCE,
+                }),
+                detail: Some(kind.describe().to_string()),
+                ..Default::default()
+            })
+            .collect();
+        items.sort_by(|a, b| a.label.cmp(&b.label));
+        items
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::KineticModelState;
+
+    const MEASUREMENTS: &str = "measurement_type,target_id,experiment_id,measurement,error_scale
+mic,g6p_c,condition1,0.5,0.1
+flux,\"PGI\",condition1,4,0.1
+enzyme,pgi,condition1,oops,0.1
+mic,g6p_c,condition2
+";
+
+    fn known() -> KnownIds {
+        KnownIds {
+            met_comps: HashSet::from(["g6p_c".to_string()]),
+            reactions: HashSet::from(["PGI".to_string()]),
+            enzymes: HashSet::from(["pgi".to_string()]),
+            experiments: HashSet::from(["condition1".to_string()]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn cells_are_spanned_without_quotes() {
+        let table = CsvTable::parse(MEASUREMENTS.to_string());
+        assert_eq!(table.rows.len(), 4);
+        let cell = &table.rows[1][1];
+        assert_eq!(cell.value, "PGI");
+        assert_eq!(&table.text[cell.span.0..cell.span.1], "PGI");
+        assert_eq!(
+            table.symbol_at(Position::new(2, 7)),
+            Some((ColumnKind::Reaction, "PGI"))
+        );
+    }
+
+    #[test]
+    fn unknown_ids_and_bad_values_are_reported() {
+        let table = CsvTable::parse(MEASUREMENTS.to_string());
+        let messages: Vec<String> = table
+            .diagnostics(&known())
+            .into_iter()
+            .map(|diag| diag.message)
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "'oops' is not a number.",
+                "Expected 5 fields, found 3.",
+                "Unknown experiment 'condition2'."
+            ]
+        );
+    }
+
+    #[test]
+    fn completes_ids_of_the_column() {
+        let table = CsvTable::parse(MEASUREMENTS.to_string());
+        let items = table.completions(Position::new(1, 4), &known());
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].label, "g6p_c");
+    }
+
+    #[test]
+    fn references_are_found_by_kind() {
+        let table = CsvTable::parse(
+            "measurement_type,target_id,experiment_id,measurement,error_scale
+mic,pgi_c,condition1,0.5,0.1
+flux,pgi,condition1,0.5,0.1
+enzyme,pgi,condition1,0.5,0.1
+"
+            .to_string(),
+        );
+        let text = &table.text;
+        let texts = |spans: Vec<(usize, usize)>| {
+            spans
+                .into_iter()
+                .map(|(start, end)| (text[..start].matches('\n').count(), &text[start..end]))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            texts(table.references(ColumnKind::Metabolite, "pgi")),
+            [(1, "pgi")]
+        );
+        assert_eq!(
+            texts(table.references(ColumnKind::Compartment, "c")),
+            [(1, "c")]
+        );
+        assert_eq!(
+            texts(table.references(ColumnKind::Enzyme, "pgi")),
+            [(3, "pgi")]
+        );
+    }
+
+    #[test]
+    fn priors_table_covers_parameters() {
+        let table = CsvTable::parse(
+            "parameter_type,enzyme_id,reaction_id,mic_id,experiment_id,location,scale
+kcat,pgi,PGI,,,100,0.1
+km,pgi,,g6p_c,,1,0.1
+"
+            .to_string(),
+        );
+        assert!(table.is_priors());
+        let coverage = table.prior_coverage();
+        assert!(coverage
+            .kcat
+            .contains(&("pgi".to_string(), "PGI".to_string())));
+        assert!(coverage
+            .km
+            .contains(&("pgi".to_string(), "g6p_c".to_string())));
+        let priors: Vec<(&str, Option<String>, String)> = table
+            .priors()
+            .into_iter()
+            .map(|prior| (prior.parameter, prior.met_comp, prior.distribution))
+            .collect();
+        assert_eq!(
+            priors,
+            [
+                ("kcat", None, "location 100, scale 0.1".to_string()),
+                (
+                    "km",
+                    Some("g6p_c".to_string()),
+                    "location 1, scale 0.1".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn priors_of_unpaired_enzymes_are_reported() {
+        let kinetic_state = KineticModelState::try_from_str(
+            r#"
+metabolite = [{id = "g6p", name = "D-Glucose 6-phosphate", inchi_key = "A"}]
+enzyme = [{id = "pgi", name = "Glucose-6-phosphate isomerase", subunits = 1}]
+enzyme_reaction = [{enzyme_id = "pgi", reaction_id = "PGI"}]
+
+[[reaction]]
+id = "PGI"
+name = "Glucose-6-phosphate isomerase"
+stoichiometry = {g6p_c = -1}
+mechanism = "reversible_michaelis_menten"
+"#
+            .to_string(),
+        )
+        .unwrap();
+        let table = CsvTable::parse(
+            "parameter_type,enzyme_id,reaction_id,mic_id,experiment_id,location,scale
+kcat,pgi,PGI,,,100,0.1
+kcat,pfk,PGI,,,100,0.1
+km,pgi,,g6p_c,,1,0.1
+km,pgi,,atp_c,,1,0.1
+"
+            .to_string(),
+        );
+        let diagnostics: Vec<(u32, String)> = table
+            .prior_pair_diagnostics(kinetic_state.borrow_symbols())
+            .into_iter()
+            .map(|diag| (diag.range.start.line, diag.message))
+            .collect();
+        assert_eq!(
+            diagnostics,
+            [
+                (
+                    2,
+                    "kcat prior of pfk for PGI is unused: enzyme pfk is not defined.".to_string()
+                ),
+                (
+                    4,
+                    "km prior of pgi for atp_c is unused: none of pgi's reactions involve atp_c."
+                        .to_string()
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,140 @@
+//! Incremental recomputation of the data derived from the project files.
+//!
+//! Every input of the project (a file or the diagnostic settings) has a
+//! revision, increased when it changes. Derived data, like the identifiers of
+//! the kinetic model or the diagnostics of a document, is memoized with the
+//! revisions of the inputs it was computed from and only recomputed after one
+//! of them changed. Editing the priors does not re-run the diagnostics of the
+//! config.toml, nor re-index the kinetic model.
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+
+/// Something the derived data of a project is computed from.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Input {
+    KineticModel,
+    /// The priors, in TOML or CSV.
+    Priors,
+    /// The experiment ids, from the TOML or the CSV experiments.
+    Experiments,
+    Config,
+    Inits,
+    /// A CSV table of the project, by path.
+    Csv(String),
+    /// Severities of the diagnostic rules.
+    Settings,
+}
+
+/// Revisions of the inputs of a project.
+///
+/// Revisions come from a single counter, so the latest revision of a set of
+/// inputs changes whenever any of them does.
+#[derive(Clone, Debug, Default)]
+pub struct Revisions {
+    counter: u64,
+    inputs: HashMap<Input, u64>,
+}
+
+impl Revisions {
+    /// Mark an input as changed.
+    pub fn bump(&mut self, input: Input) {
+        self.counter += 1;
+        self.inputs.insert(input, self.counter);
+    }
+
+    /// Latest revision of any input.
+    pub fn current(&self) -> u64 {
+        self.counter
+    }
+
+    /// Revisions of some inputs, 0 for those that never changed.
+    pub fn of(&self, inputs: &[Input]) -> Vec<u64> {
+        inputs
+            .iter()
+            .map(|input| self.inputs.get(input).copied().unwrap_or_default())
+            .collect()
+    }
+
+    /// Latest revision of some inputs, identifying the data computed from them.
+    pub fn latest(&self, inputs: &[Input]) -> u64 {
+        self.of(inputs).into_iter().max().unwrap_or_default()
+    }
+}
+
+/// A result along with the revisions of the inputs it was computed from.
+type Memoized<T> = (Vec<u64>, Arc<T>);
+
+/// Memoized results of a query, by key.
+pub struct Memo<K, T> {
+    results: Mutex<HashMap<K, Memoized<T>>>,
+}
+
+impl<K, T> Default for Memo<K, T> {
+    fn default() -> Self {
+        Memo {
+            results: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl<K: Eq + Hash, T> Memo<K, T> {
+    /// The memoized result if its inputs are still at `revisions`, else a new one.
+    pub fn get(&self, key: K, revisions: Vec<u64>, compute: impl FnOnce() -> T) -> Arc<T> {
+        if let Some((known, result)) = self.results.lock().unwrap().get(&key) {
+            if *known == revisions {
+                return Arc::clone(result);
+            }
+        }
+        // computed without the lock, queries may depend on other queries
+        let result = Arc::new(compute());
+        self.results
+            .lock()
+            .unwrap()
+            .insert(key, (revisions, Arc::clone(&result)));
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn results_are_reused_until_an_input_changes() {
+        let mut revisions = Revisions::default();
+        let memo: Memo<(), usize> = Memo::default();
+        let runs = Cell::new(0);
+        let query = |revisions: &Revisions| {
+            *memo.get(
+                (),
+                revisions.of(&[Input::KineticModel, Input::Priors]),
+                || {
+                    runs.set(runs.get() + 1);
+                    runs.get()
+                },
+            )
+        };
+        assert_eq!(query(&revisions), 1);
+        assert_eq!(query(&revisions), 1);
+        revisions.bump(Input::Config);
+        assert_eq!(query(&revisions), 1);
+        revisions.bump(Input::Priors);
+        assert_eq!(query(&revisions), 2);
+        assert_eq!(query(&revisions), 2);
+    }
+
+    #[test]
+    fn latest_revision_changes_with_any_input() {
+        let mut revisions = Revisions::default();
+        let inputs = [Input::Csv("/a.csv".to_string()), Input::Settings];
+        assert_eq!(revisions.latest(&inputs), 0);
+        revisions.bump(Input::Settings);
+        let before = revisions.latest(&inputs);
+        revisions.bump(Input::KineticModel);
+        assert_eq!(revisions.latest(&inputs), before);
+        revisions.bump(Input::Csv("/a.csv".to_string()));
+        assert!(revisions.latest(&inputs) > before);
+    }
+}
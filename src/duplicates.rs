@@ -0,0 +1,309 @@
+//! Detection of duplicated identifiers in the kinetic model and duplicated priors.
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use lsp_types::{
+    CodeAction, CodeActionKind, CodeActionOrCommand, Diagnostic, DiagnosticRelatedInformation,
+    Location, Range, TextEdit, Url, WorkspaceEdit,
+};
+use toml::Spanned;
+
+use crate::rules::Rule;
+use crate::state::{span_to_range, KineticModelState, PriorsState};
+use crate::symbol_parser::{entry_removal_span, entry_span};
+
+/// An entry that was already defined before in the same file.
+pub struct Duplicate {
+    /// Human readable description of what is duplicated.
+    pub what: String,
+    /// Span reported in the diagnostic.
+    pub span: (usize, usize),
+    /// Span of the first definition.
+    pub first: (usize, usize),
+    /// Span of text to delete to get rid of the duplicate.
+    pub removal: (usize, usize),
+    /// Duplicated identifiers and priors are reported by different rules.
+    rule: Rule,
+}
+
+/// Pair every repeated key with the span of its first appearance.
+fn repeated<K: Eq + Hash>(
+    items: impl Iterator<Item = (K, (usize, usize))>,
+) -> Vec<((usize, usize), (usize, usize))> {
+    let mut seen = HashMap::new();
+    items
+        .filter_map(|(key, span)| match seen.get(&key) {
+            Some(&first) => Some((first, span)),
+            None => {
+                seen.insert(key, span);
+                None
+            }
+        })
+        .collect()
+}
+
+fn ids<'a, T: 'a>(
+    items: &'a [T],
+    id: impl Fn(&'a T) -> &'a Spanned<&'a str> + 'a,
+) -> impl Iterator<Item = (&'a str, (usize, usize))> + 'a {
+    items.iter().map(move |item| {
+        let spanned = id(item);
+        (*spanned.get_ref(), spanned.span())
+    })
+}
+
+/// Metabolites, reactions, enzymes and enzyme-reaction pairs defined more than once.
+pub fn kinetic_model_duplicates(kinetic_state: &KineticModelState) -> Vec<Duplicate> {
+    let kinetic_model = kinetic_state.borrow_kinetic_model();
+    let text = kinetic_state.borrow_file_str();
+    let to_duplicate = |what: &str, (first, span): ((usize, usize), (usize, usize))| Duplicate {
+        what: format!("{what} '{}'", text[span.0..span.1].trim_matches('"')),
+        span,
+        first,
+        removal: entry_removal_span(text, span.0),
+        rule: Rule::DuplicateId,
+    };
+    let enzyme_reactions = kinetic_model.enzyme_reaction.iter().map(|er| {
+        (
+            (*er.enzyme_id.get_ref(), *er.reaction_id.get_ref()),
+            er.enzyme_id.span(),
+        )
+    });
+    repeated(ids(&kinetic_model.metabolites, |met| &met.id))
+        .into_iter()
+        .map(|dup| to_duplicate("metabolite", dup))
+        .chain(
+            repeated(ids(&kinetic_model.reactions, |reac| &reac.id))
+                .into_iter()
+                .map(|dup| to_duplicate("reaction", dup)),
+        )
+        .chain(
+            repeated(ids(&kinetic_model.enzymes, |enz| &enz.id))
+                .into_iter()
+                .map(|dup| to_duplicate("enzyme", dup)),
+        )
+        .chain(
+            repeated(enzyme_reactions)
+                .into_iter()
+                .map(|dup| to_duplicate("enzyme_reaction for enzyme", dup)),
+        )
+        .collect()
+}
+
+/// Spans of the priors of a kind. Those written as `[[kind]]` tables are not
+/// spanned by toml, their span is the block of their header.
+fn prior_spans<P>(text: &str, kind: &str, priors: &[Spanned<P>]) -> Vec<(usize, usize)> {
+    let header = format!("[[{kind}]]");
+    let mut offset = 0;
+    let mut blocks = Vec::new();
+    for line in text.split_inclusive('\n') {
+        if line.split('#').next().unwrap_or_default().trim() == header {
+            blocks.push(entry_span(text, offset));
+        }
+        offset += line.len();
+    }
+    let mut blocks = blocks.into_iter();
+    priors
+        .iter()
+        .map(|prior| match prior.span() {
+            (0, 0) => blocks.next().unwrap_or_default(),
+            span => span,
+        })
+        .collect()
+}
+
+/// Priors defined more than once for the same parameter.
+pub fn priors_duplicates(priors_state: &PriorsState) -> Vec<Duplicate> {
+    let priors = priors_state.borrow_priors();
+    let text = priors_state.borrow_file_str();
+    let to_duplicate = |what: &str, (first, span): ((usize, usize), (usize, usize))| Duplicate {
+        what: what.to_string(),
+        span,
+        first,
+        removal: entry_removal_span(text, span.0),
+        rule: Rule::DuplicatePrior,
+    };
+    let kcat = priors
+        .kcat
+        .iter()
+        .map(|p| (&p.get_ref().enzyme, &p.get_ref().reaction))
+        .zip(prior_spans(text, "kcat", &priors.kcat));
+    let km = priors
+        .km
+        .iter()
+        .map(|p| {
+            let km = p.get_ref();
+            (&km.metabolite, &km.compartment, &km.enzyme)
+        })
+        .zip(prior_spans(text, "km", &priors.km));
+    let conc_enzyme = priors
+        .conc_enzyme
+        .iter()
+        .map(|p| (&p.get_ref().enzyme, &p.get_ref().experiment))
+        .zip(prior_spans(text, "conc_enzyme", &priors.conc_enzyme));
+    let conc_unbalanced = priors
+        .conc_unbalanced
+        .iter()
+        .map(|p| {
+            let conc = p.get_ref();
+            (&conc.metabolite, &conc.compartment, &conc.experiment)
+        })
+        .zip(prior_spans(
+            text,
+            "conc_unbalanced",
+            &priors.conc_unbalanced,
+        ));
+    let drain = priors
+        .drain
+        .iter()
+        .map(|p| (&p.get_ref().reaction, &p.get_ref().experiment))
+        .zip(prior_spans(text, "drain", &priors.drain));
+    repeated(kcat)
+        .into_iter()
+        .map(|dup| to_duplicate("kcat prior", dup))
+        .chain(
+            repeated(km)
+                .into_iter()
+                .map(|dup| to_duplicate("km prior", dup)),
+        )
+        .chain(
+            repeated(conc_enzyme)
+                .into_iter()
+                .map(|dup| to_duplicate("conc_enzyme prior", dup)),
+        )
+        .chain(
+            repeated(conc_unbalanced)
+                .into_iter()
+                .map(|dup| to_duplicate("conc_unbalanced prior", dup)),
+        )
+        .chain(
+            repeated(drain)
+                .into_iter()
+                .map(|dup| to_duplicate("drain prior", dup)),
+        )
+        .collect()
+}
+
+impl Duplicate {
+    pub fn to_diagnostic(&self, text: &str, uri: &Url) -> Diagnostic {
+        Diagnostic {
+            range: span_to_range(text, self.span),
+            severity: Some(self.rule.default_severity()),
+            code: Some(self.rule.diagnostic_code()),
+            message: format!("Duplicate {}.", self.what),
+            related_information: Some(vec![DiagnosticRelatedInformation {
+                location: Location {
+                    uri: uri.clone(),
+                    range: span_to_range(text, self.first),
+                },
+                message: "First defined here.".to_string(),
+            }]),
+            ..Default::default()
+        }
+    }
+
+    /// Quick fix that deletes the duplicated entry.
+    pub fn to_code_action(&self, text: &str, uri: &Url) -> CodeActionOrCommand {
+        CodeActionOrCommand::CodeAction(CodeAction {
+            title: format!("Remove duplicate {}", self.what),
+            kind: Some(CodeActionKind::QUICKFIX),
+            diagnostics: Some(vec![self.to_diagnostic(text, uri)]),
+            edit: Some(WorkspaceEdit {
+                changes: Some(HashMap::from([(
+                    uri.clone(),
+                    vec![TextEdit {
+                        range: span_to_range(text, self.removal),
+                        new_text: String::new(),
+                    }],
+                )])),
+                ..Default::default()
+            }),
+            is_preferred: Some(true),
+            ..Default::default()
+        })
+    }
+
+    /// Whether the reported span overlaps with an LSP range.
+    pub fn intersects(&self, text: &str, range: &Range) -> bool {
+        let own = span_to_range(text, self.span);
+        own.start <= range.end && range.start <= own.end
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_duplicated_priors() {
+        let priors_state = PriorsState::from_path(
+            std::env::current_dir()
+                .unwrap()
+                .join("tests/mock/priors.toml"),
+        );
+        let duplicates = priors_duplicates(&priors_state);
+        // 4 kms, 5 conc_enzymes
+        assert_eq!(
+            duplicates
+                .iter()
+                .filter(|dup| dup.what == "km prior")
+                .count(),
+            4
+        );
+        assert_eq!(
+            duplicates
+                .iter()
+                .filter(|dup| dup.what == "conc_enzyme prior")
+                .count(),
+            5
+        );
+    }
+
+    #[test]
+    fn finds_duplicated_priors_in_tables() {
+        let text = r#"[[kcat]]
+enzyme = "E1"
+reaction = "PGI"
+exploc = 126.0
+scale = 0.2
+
+[[kcat]]
+enzyme = "E1"
+reaction = "PGI"
+exploc = 110.0
+scale = 0.2
+"#;
+        let priors_state = PriorsState::try_from_str(text.to_string()).unwrap();
+        let duplicates = priors_duplicates(&priors_state);
+        assert_eq!(duplicates.len(), 1);
+        let dup = &duplicates[0];
+        assert_eq!(dup.first.0, 0);
+        assert!(text[dup.span.0..]
+            .starts_with("[[kcat]]\nenzyme = \"E1\"\nreaction = \"PGI\"\nexploc = 110.0"));
+        assert_eq!(&text[dup.removal.0..dup.removal.1], &text[dup.span.0..]);
+    }
+
+    #[test]
+    fn removes_whole_line_of_duplicated_prior() {
+        let priors_state = PriorsState::from_path(
+            std::env::current_dir()
+                .unwrap()
+                .join("tests/mock/priors.toml"),
+        );
+        let text = priors_state.borrow_file_str();
+        let dup = &priors_duplicates(&priors_state)[0];
+        let removed = &text[dup.removal.0..dup.removal.1];
+        assert!(removed.starts_with("  {"));
+        assert!(removed.ends_with("},\n"));
+    }
+
+    #[test]
+    fn no_duplicates_in_kinetic_model() {
+        let kinetic_state = KineticModelState::from_path(
+            std::env::current_dir()
+                .unwrap()
+                .join("tests/mock/ecoli_kinetic_model.toml"),
+        );
+        assert!(kinetic_model_duplicates(&kinetic_state).is_empty());
+    }
+}
@@ -24,7 +24,7 @@ struct Measurement<'a> {
 
 #[derive(Deserialize)]
 pub(crate) struct ExperimentData<'a> {
-    #[serde(rename = "experiment", borrow)]
+    #[serde(rename = "experiment", borrow, default)]
     experiments: Vec<Experiment<'a>>,
 }
 
@@ -0,0 +1,394 @@
+//! Export of the metabolite-reaction network to Graphviz DOT, GraphML and Cytoscape.js JSON.
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::io::{self, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use serde_json::{json, Value};
+
+use crate::maud_data::KineticModel;
+use crate::network::edges;
+use crate::settings::DEFAULT_CONFIG_FILE;
+use crate::state::ProjectState;
+
+/// Graph formats of the network export.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum GraphFormat {
+    Dot,
+    GraphMl,
+    Cytoscape,
+}
+
+impl FromStr for GraphFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "dot" | "graphviz" => Ok(GraphFormat::Dot),
+            "graphml" => Ok(GraphFormat::GraphMl),
+            "cytoscape" | "json" => Ok(GraphFormat::Cytoscape),
+            _ => Err(format!(
+                "Invalid format '{s}', expected one of: dot, graphml, cytoscape"
+            )),
+        }
+    }
+}
+
+/// Node of the network, either a metabolite in compartment or a reaction.
+struct Node {
+    id: String,
+    label: String,
+    /// "metabolite" or "reaction"
+    kind: &'static str,
+    data: BTreeMap<&'static str, String>,
+}
+
+/// Edge of the network: stoichiometric (substrate to reaction to product) or
+/// allosteric (regulator to regulated reaction).
+struct Edge {
+    source: String,
+    target: String,
+    /// "stoichiometry", "activation" or "inhibition"
+    kind: String,
+    data: BTreeMap<&'static str, String>,
+}
+
+struct Graph {
+    nodes: Vec<Node>,
+    edges: Vec<Edge>,
+}
+
+impl Graph {
+    /// Nodes and edges are sorted so that the export is reproducible.
+    fn from_model(kinetic_model: &KineticModel) -> Self {
+        let mut metabolites: BTreeMap<String, Node> = BTreeMap::new();
+        let mut met_comp_node = |met_comp: &str| {
+            metabolites.entry(met_comp.to_string()).or_insert_with(|| {
+                let (met_id, comp_id) = met_comp.rsplit_once('_').unwrap_or((met_comp, ""));
+                let label = kinetic_model
+                    .metabolites
+                    .iter()
+                    .find(|met| met.id.get_ref() == &met_id)
+                    .map_or(met_comp, |met| met.name);
+                let balanced = kinetic_model
+                    .metabolite_in_compartment
+                    .iter()
+                    .find(|row| {
+                        row.metabolite_id.get_ref() == &met_id && row.compartment_id == comp_id
+                    })
+                    .map_or("unknown".to_string(), |row| row.balanced.to_string());
+                Node {
+                    id: met_comp.to_string(),
+                    label: label.to_string(),
+                    kind: "metabolite",
+                    data: BTreeMap::from([
+                        ("metabolite", met_id.to_string()),
+                        ("compartment", comp_id.to_string()),
+                        ("balanced", balanced),
+                    ]),
+                }
+            });
+        };
+        for row in kinetic_model.metabolite_in_compartment.iter() {
+            met_comp_node(&format!(
+                "{}_{}",
+                row.metabolite_id.get_ref(),
+                row.compartment_id
+            ));
+        }
+        let mut edges_out = Vec::new();
+        for edge in edges(&kinetic_model.reactions) {
+            met_comp_node(edge.met_comp);
+            let reaction = edge.reaction.id.get_ref().to_string();
+            let (source, target) = if edge.coefficient > 0. {
+                (reaction, edge.met_comp.to_string())
+            } else {
+                (edge.met_comp.to_string(), reaction)
+            };
+            edges_out.push(Edge {
+                source,
+                target,
+                kind: "stoichiometry".to_string(),
+                data: BTreeMap::from([("coefficient", edge.coefficient.abs().to_string())]),
+            });
+        }
+        let enzymes_of = |reaction: &str| -> Vec<&str> {
+            kinetic_model
+                .enzyme_reaction
+                .iter()
+                .filter(|er| er.reaction_id.get_ref() == &reaction)
+                .map(|er| *er.enzyme_id.get_ref())
+                .collect()
+        };
+        for allostery in kinetic_model.allostery.iter() {
+            let met_comp = format!("{}_{}", allostery.metabolite_id, allostery.compartment_id);
+            met_comp_node(&met_comp);
+            // the regulated enzyme is drawn on the reactions it catalyses
+            for er in kinetic_model
+                .enzyme_reaction
+                .iter()
+                .filter(|er| er.enzyme_id.get_ref() == &allostery.enzyme_id)
+            {
+                edges_out.push(Edge {
+                    source: met_comp.clone(),
+                    target: er.reaction_id.get_ref().to_string(),
+                    kind: allostery.modification_type.to_string(),
+                    data: BTreeMap::from([("enzyme", allostery.enzyme_id.to_string())]),
+                });
+            }
+        }
+        let mut nodes: Vec<Node> = metabolites.into_values().collect();
+        let mut reactions: Vec<Node> = kinetic_model
+            .reactions
+            .iter()
+            .map(|reac| Node {
+                id: reac.id.get_ref().to_string(),
+                label: reac.name.to_string(),
+                kind: "reaction",
+                data: BTreeMap::from([
+                    ("mechanism", reac.mechanism.to_string()),
+                    ("enzymes", enzymes_of(reac.id.get_ref()).join(",")),
+                ]),
+            })
+            .collect();
+        reactions.sort_by(|a, b| a.id.cmp(&b.id));
+        nodes.extend(reactions);
+        edges_out
+            .sort_by(|a, b| (&a.source, &a.target, &a.kind).cmp(&(&b.source, &b.target, &b.kind)));
+        Graph {
+            nodes,
+            edges: edges_out,
+        }
+    }
+
+    fn to_dot(&self) -> String {
+        let quote = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+        let attributes = |data: &BTreeMap<&str, String>| {
+            data.iter()
+                .map(|(key, value)| format!(", {key}={}", quote(value)))
+                .collect::<String>()
+        };
+        let mut out = String::from("digraph maud {\n");
+        for node in self.nodes.iter() {
+            let shape = if node.kind == "reaction" {
+                "box"
+            } else {
+                "ellipse"
+            };
+            out.push_str(&format!(
+                "  {} [label={}, kind={}, shape={shape}{}];\n",
+                quote(&node.id),
+                quote(&node.label),
+                node.kind,
+                attributes(&node.data),
+            ));
+        }
+        for edge in self.edges.iter() {
+            let style = match edge.kind.as_str() {
+                "stoichiometry" => "",
+                "activation" => ", style=dashed, arrowhead=empty",
+                _ => ", style=dashed, arrowhead=tee",
+            };
+            out.push_str(&format!(
+                "  {} -> {} [kind={}{style}{}];\n",
+                quote(&edge.source),
+                quote(&edge.target),
+                quote(&edge.kind),
+                attributes(&edge.data),
+            ));
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    fn to_graphml(&self) -> String {
+        let escape = |s: &str| {
+            s.replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;")
+                .replace('"', "&quot;")
+        };
+        let mut out = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+        );
+        for (scope, keys) in [
+            (
+                "node",
+                [
+                    "label",
+                    "kind",
+                    "metabolite",
+                    "compartment",
+                    "balanced",
+                    "mechanism",
+                    "enzymes",
+                ]
+                .as_slice(),
+            ),
+            ("edge", ["kind", "coefficient", "enzyme"].as_slice()),
+        ] {
+            for key in keys {
+                out.push_str(&format!(
+                    "  <key id=\"{scope}_{key}\" for=\"{scope}\" attr.name=\"{key}\" attr.type=\"string\"/>\n"
+                ));
+            }
+        }
+        out.push_str("  <graph id=\"maud\" edgedefault=\"directed\">\n");
+        let data = |scope: &str, key: &str, value: &str| {
+            format!(
+                "      <data key=\"{scope}_{key}\">{}</data>\n",
+                escape(value)
+            )
+        };
+        for node in self.nodes.iter() {
+            out.push_str(&format!("    <node id=\"{}\">\n", escape(&node.id)));
+            out.push_str(&data("node", "label", &node.label));
+            out.push_str(&data("node", "kind", node.kind));
+            for (key, value) in node.data.iter() {
+                out.push_str(&data("node", key, value));
+            }
+            out.push_str("    </node>\n");
+        }
+        for edge in self.edges.iter() {
+            out.push_str(&format!(
+                "    <edge source=\"{}\" target=\"{}\">\n",
+                escape(&edge.source),
+                escape(&edge.target)
+            ));
+            out.push_str(&data("edge", "kind", &edge.kind));
+            for (key, value) in edge.data.iter() {
+                out.push_str(&data("edge", key, value));
+            }
+            out.push_str("    </edge>\n");
+        }
+        out.push_str("  </graph>\n</graphml>\n");
+        out
+    }
+
+    /// Elements of Cytoscape.js, to be passed as `cytoscape({ elements })`.
+    fn to_cytoscape(&self) -> Value {
+        let nodes: Vec<Value> = self
+            .nodes
+            .iter()
+            .map(|node| {
+                let mut data = json!({"id": node.id, "label": node.label, "kind": node.kind});
+                for (key, value) in node.data.iter() {
+                    data[key] = json!(value);
+                }
+                json!({ "data": data })
+            })
+            .collect();
+        let edges: Vec<Value> = self
+            .edges
+            .iter()
+            .map(|edge| {
+                let mut data = json!({
+                    "id": format!("{}->{}:{}", edge.source, edge.target, edge.kind),
+                    "source": edge.source,
+                    "target": edge.target,
+                    "kind": edge.kind,
+                });
+                for (key, value) in edge.data.iter() {
+                    data[key] = json!(value);
+                }
+                json!({ "data": data })
+            })
+            .collect();
+        json!({ "nodes": nodes, "edges": edges })
+    }
+}
+
+/// Render the metabolite-reaction network of the kinetic model in `format`.
+pub fn export_network(kinetic_model: &KineticModel, format: GraphFormat) -> String {
+    let graph = Graph::from_model(kinetic_model);
+    match format {
+        GraphFormat::Dot => graph.to_dot(),
+        GraphFormat::GraphMl => graph.to_graphml(),
+        GraphFormat::Cytoscape => {
+            serde_json::to_string_pretty(&graph.to_cytoscape()).unwrap_or_default()
+        }
+    }
+}
+
+/// Export the network of the project at `root_dir` to `output`, or to stdout.
+pub fn export_project(
+    root_dir: &Path,
+    format: GraphFormat,
+    output: Option<&Path>,
+) -> Result<(), Box<dyn Error + Sync + Send>> {
+    // the files of the project are located by URI, from an absolute path
+    let project = ProjectState::load(&std::path::absolute(root_dir)?, DEFAULT_CONFIG_FILE)?;
+    let graph = export_network(project.kinetic_state.borrow_kinetic_model(), format);
+    match output {
+        Some(path) => std::fs::write(path, graph)?,
+        None => match std::io::stdout().lock().write_all(graph.as_bytes()) {
+            // the output was piped to a command that exited, like `head`
+            Err(e) if e.kind() != io::ErrorKind::BrokenPipe => return Err(e.into()),
+            _ => (),
+        },
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::KineticModelState;
+
+    fn mock_model() -> KineticModelState {
+        KineticModelState::from_path(
+            std::env::current_dir()
+                .unwrap()
+                .join("tests/mock/ecoli_kinetic_model.toml"),
+        )
+    }
+
+    #[test]
+    fn dot_has_stoichiometric_edges() {
+        let dot = export_network(mock_model().borrow_kinetic_model(), GraphFormat::Dot);
+        assert!(dot.starts_with("digraph maud {"));
+        assert!(dot.contains("\"g6p_c\" -> \"PGI\""));
+        assert!(dot.contains("\"PGI\" -> \"f6p_c\""));
+        assert!(dot.contains("balanced=\"false\""));
+        assert!(dot.contains("enzymes=\"E1\""));
+    }
+
+    #[test]
+    fn allosteric_edges_are_exported() {
+        let text = std::fs::read_to_string("tests/mock/ecoli_kinetic_model.toml")
+            .unwrap()
+            .replace(
+                "allostery = []",
+                "allostery = [{enzyme_id=\"E1\", metabolite_id=\"atp\", compartment_id=\"c\", modification_type=\"inhibition\"}]",
+            );
+        let state = KineticModelState::try_from_str(text).unwrap();
+        let json: Value = serde_json::from_str(&export_network(
+            state.borrow_kinetic_model(),
+            GraphFormat::Cytoscape,
+        ))
+        .unwrap();
+        let inhibitions: Vec<&Value> = json["edges"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|edge| edge["data"]["kind"] == "inhibition")
+            .collect();
+        // E1 catalyses PGI, PFK and FBP
+        assert_eq!(inhibitions.len(), 3);
+        assert!(inhibitions
+            .iter()
+            .all(|edge| edge["data"]["source"] == "atp_c"));
+    }
+
+    #[test]
+    fn graphml_is_well_formed() {
+        let graphml = export_network(mock_model().borrow_kinetic_model(), GraphFormat::GraphMl);
+        assert_eq!(
+            graphml.matches("<node ").count(),
+            graphml.matches("</node>").count()
+        );
+        assert!(graphml.contains("<data key=\"node_mechanism\">reversible</data>"));
+    }
+}
@@ -0,0 +1,540 @@
+//! Formatting of Maud TOML files.
+//!
+//! The formatter works on lines so that comments are kept:
+//! - `key=value` is spaced as `key = value` and trailing whitespace is removed.
+//! - Keys of `[[table]]` entries are ordered canonically (`id`, `name`, identifiers,
+//!   other keys, distribution parameters).
+//! - Arrays of inline tables, like the priors, get one table per line, with their keys
+//!   ordered canonically and their columns aligned.
+//!
+//! Values are never rewritten, so the output is stable across runs. Ordering and
+//! aligning can be turned off with the `formatting` setting of the client.
+use lsp_types::{Position, Range, TextEdit};
+use serde::Deserialize;
+
+/// Choices of the user about the formatting.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct FormattingStyle {
+    /// Order the keys of tables and inline tables canonically.
+    pub sort_keys: bool,
+    /// Align the columns of arrays of inline tables.
+    pub align_columns: bool,
+}
+
+impl Default for FormattingStyle {
+    fn default() -> Self {
+        FormattingStyle {
+            sort_keys: true,
+            align_columns: true,
+        }
+    }
+}
+
+/// Keys in canonical order; unknown keys go after the identifiers, in their original order.
+const IDENTIFIER_KEYS: [&str; 13] = [
+    "id",
+    "name",
+    "metabolite_id",
+    "metabolite",
+    "compartment_id",
+    "compartment",
+    "enzyme_id",
+    "enzyme",
+    "reaction_id",
+    "reaction",
+    "experiment_id",
+    "experiment",
+    "target_id",
+];
+const DISTRIBUTION_KEYS: [&str; 5] = ["exploc", "location", "scale", "pct1", "pct99"];
+
+fn rank(key: &str) -> usize {
+    let key = key.trim_matches('"');
+    if let Some(i) = IDENTIFIER_KEYS.iter().position(|k| *k == key) {
+        i
+    } else if let Some(i) = DISTRIBUTION_KEYS.iter().position(|k| *k == key) {
+        IDENTIFIER_KEYS.len() + 1 + i
+    } else {
+        IDENTIFIER_KEYS.len()
+    }
+}
+
+/// Scanner of TOML text that skips strings and comments.
+#[derive(Default)]
+pub(crate) struct Scan {
+    depth: i32,
+    /// Inside a `"""` string spanning several lines.
+    multiline: bool,
+}
+
+impl Scan {
+    /// Update the bracket depth with a line.
+    pub(crate) fn line(&mut self, line: &str) {
+        let mut chars = line.char_indices().peekable();
+        while let Some((i, c)) = chars.next() {
+            if self.multiline {
+                if line[i..].starts_with("\"\"\"") {
+                    self.multiline = false;
+                    chars.nth(1);
+                }
+                continue;
+            }
+            match c {
+                '#' => return,
+                '"' if line[i..].starts_with("\"\"\"") => {
+                    self.multiline = true;
+                    chars.nth(1);
+                }
+                '"' | '\'' => {
+                    while let Some((_, d)) = chars.next() {
+                        if d == '\\' && c == '"' {
+                            chars.next();
+                        } else if d == c {
+                            break;
+                        }
+                    }
+                }
+                '[' | '{' => self.depth += 1,
+                ']' | '}' => self.depth -= 1,
+                _ => (),
+            }
+        }
+    }
+
+    pub(crate) fn open(&self) -> bool {
+        self.depth > 0 || self.multiline
+    }
+}
+
+/// Split `s` on `sep` outside of strings and nested brackets.
+fn split_top_level(s: &str, sep: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        if let Some(q) = quote {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' && q == '"' {
+                escaped = true;
+            } else if c == q {
+                quote = None;
+            }
+            continue;
+        }
+        match c {
+            c if c == sep && depth == 0 => {
+                parts.push(&s[start..i]);
+                start = i + c.len_utf8();
+            }
+            '"' | '\'' => quote = Some(c),
+            '[' | '{' => depth += 1,
+            ']' | '}' => depth -= 1,
+            _ => (),
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
+/// Split a line into its content and its trailing comment (with the `#`).
+pub(crate) fn split_comment(line: &str) -> (&str, Option<&str>) {
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        if let Some(q) = quote {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' && q == '"' {
+                escaped = true;
+            } else if c == q {
+                quote = None;
+            }
+        } else if c == '"' || c == '\'' {
+            quote = Some(c);
+        } else if c == '#' {
+            return (line[..i].trim_end(), Some(line[i..].trim_end()));
+        }
+    }
+    (line.trim_end(), None)
+}
+
+/// Key and value of `key = value`, if the text is a key-value pair.
+pub(crate) fn key_value(text: &str) -> Option<(&str, &str)> {
+    // values may contain '=' only inside strings or brackets
+    let [key, value] = split_top_level(text, '=')[..] else {
+        return None;
+    };
+    let key = key.trim();
+    (!key.is_empty()).then(|| (key, value.trim()))
+}
+
+/// `key=value` spaced as `key = value`, keeping the comment.
+fn format_key_line(line: &str) -> String {
+    let (content, comment) = split_comment(line);
+    let content = match content.split_once('=') {
+        Some((key, value)) if !key.contains(['"', '\'']) => {
+            format!("{} = {}", key.trim(), value.trim())
+        }
+        _ => content.trim().to_string(),
+    };
+    with_comment(content, comment)
+}
+
+fn with_comment(content: String, comment: Option<&str>) -> String {
+    match comment {
+        Some(comment) if content.is_empty() => comment.to_string(),
+        Some(comment) => format!("{content} {comment}"),
+        None => content,
+    }
+}
+
+/// Row of an array of inline tables.
+pub(crate) enum Row<'a> {
+    Table {
+        pairs: Vec<(&'a str, &'a str)>,
+        comment: Option<&'a str>,
+    },
+    Comment(&'a str),
+    Blank,
+}
+
+pub(crate) fn parse_row(line: &str) -> Option<Row<'_>> {
+    parse_row_in(line, &FormattingStyle::default())
+}
+
+/// Row of an array of inline tables, with its keys ordered if the style says so.
+fn parse_row_in<'l>(line: &'l str, style: &FormattingStyle) -> Option<Row<'l>> {
+    let (content, comment) = split_comment(line);
+    let content = content.trim();
+    if content.is_empty() {
+        return Some(comment.map_or(Row::Blank, Row::Comment));
+    }
+    let inner = content
+        .strip_suffix(',')
+        .unwrap_or(content)
+        .trim_end()
+        .strip_prefix('{')?
+        .strip_suffix('}')?;
+    // one table per line
+    if split_top_level(inner, '}').len() > 1 {
+        return None;
+    }
+    let mut pairs = split_top_level(inner, ',')
+        .into_iter()
+        .filter(|pair| !pair.trim().is_empty())
+        .map(|pair| key_value(pair.trim()))
+        .collect::<Option<Vec<_>>>()?;
+    if style.sort_keys {
+        pairs.sort_by_key(|(key, _)| rank(key));
+    }
+    Some(Row::Table { pairs, comment })
+}
+
+/// Format the rows of an array of inline tables, `None` if it has other values.
+pub(crate) fn format_rows(rows: &[&str], style: &FormattingStyle) -> Option<Vec<String>> {
+    let rows = rows
+        .iter()
+        .map(|row| parse_row_in(row, style))
+        .collect::<Option<Vec<_>>>()?;
+    let mut widths: Vec<(&str, usize)> = Vec::new();
+    for row in rows.iter().filter(|_| style.align_columns) {
+        let Row::Table { pairs, .. } = row else {
+            continue;
+        };
+        // the last cell is not padded
+        for (key, value) in pairs.iter().take(pairs.len().saturating_sub(1)) {
+            let width = key.chars().count() + value.chars().count() + 4;
+            match widths.iter_mut().find(|(k, _)| k == key) {
+                Some((_, w)) => *w = (*w).max(width),
+                None => widths.push((key, width)),
+            }
+        }
+    }
+    let width = |key: &str| {
+        widths
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map_or(0, |(_, w)| *w)
+    };
+    Some(
+        rows.iter()
+            .map(|row| match row {
+                Row::Table { pairs, comment } => {
+                    let mut out = String::from("  {");
+                    for (i, (key, value)) in pairs.iter().enumerate() {
+                        if i + 1 == pairs.len() {
+                            out.push_str(&format!("{key} = {value}"));
+                        } else {
+                            let cell = format!("{key} = {value},");
+                            out.push_str(&format!("{cell:<w$} ", w = width(key)));
+                        }
+                    }
+                    out.push_str("},");
+                    with_comment(out, *comment)
+                }
+                Row::Comment(comment) => format!("  {comment}"),
+                Row::Blank => String::new(),
+            })
+            .collect(),
+    )
+}
+
+/// Lines of an entry: a key whose value may span several lines.
+fn format_entry(lines: &[&str], style: &FormattingStyle) -> Vec<String> {
+    let first = format_key_line(lines[0]);
+    if lines.len() == 1 {
+        return vec![first];
+    }
+    let (content, _) = split_comment(lines[0]);
+    let last = lines[lines.len() - 1].trim();
+    let rows = &lines[1..lines.len() - 1];
+    match content.trim_end().ends_with('[') && last.starts_with(']') {
+        true => match format_rows(rows, style) {
+            Some(rows) => std::iter::once(first)
+                .chain(rows)
+                .chain(std::iter::once(last.to_string()))
+                .collect(),
+            None => std::iter::once(first)
+                .chain(lines[1..].iter().map(|line| line.trim_end().to_string()))
+                .collect(),
+        },
+        // e.g. multi-line strings, which are kept verbatim
+        false => std::iter::once(first)
+            .chain(lines[1..].iter().map(|line| line.trim_end().to_string()))
+            .collect(),
+    }
+}
+
+/// Consecutive lines that are formatted together.
+struct Unit {
+    start: usize,
+    end: usize,
+    formatted: Vec<String>,
+}
+
+/// Key entry of a table, with the comments above it.
+struct Entry {
+    key: String,
+    lines: Vec<String>,
+}
+
+/// Sort the entries of a table, within groups separated by blank lines.
+fn sort_entries(
+    lines: &[&str],
+    entries: Vec<(usize, usize)>,
+    style: &FormattingStyle,
+) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut group: Vec<Entry> = Vec::new();
+    let mut pending: Vec<String> = Vec::new();
+    let flush = |group: &mut Vec<Entry>, out: &mut Vec<String>| {
+        if style.sort_keys {
+            group.sort_by_key(|entry| rank(&entry.key));
+        }
+        out.extend(group.drain(..).flat_map(|entry| entry.lines));
+    };
+    let mut next = 0;
+    for (start, end) in entries {
+        for line in lines[next..start].iter().map(|line| line.trim()) {
+            if line.is_empty() {
+                flush(&mut group, &mut out);
+                out.append(&mut pending);
+                out.push(String::new());
+            } else {
+                pending.push(line.to_string());
+            }
+        }
+        let formatted = format_entry(&lines[start..end], style);
+        let key = key_value(split_comment(lines[start]).0)
+            .map_or("", |(key, _)| key)
+            .to_string();
+        pending.extend(formatted);
+        group.push(Entry {
+            key,
+            lines: std::mem::take(&mut pending),
+        });
+        next = end;
+    }
+    flush(&mut group, &mut out);
+    for line in lines[next..].iter().map(|line| line.trim()) {
+        out.push(line.to_string());
+    }
+    out
+}
+
+/// Split the document into units: each top-level entry and each table.
+fn units(text: &str, style: &FormattingStyle) -> Vec<Unit> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut units = Vec::new();
+    // start of the current table and its entries
+    let mut table: Option<(usize, Vec<(usize, usize)>)> = None;
+    let close_table =
+        |table: Option<(usize, Vec<(usize, usize)>)>, end: usize, units: &mut Vec<Unit>| {
+            if let Some((start, entries)) = table {
+                let entries = entries
+                    .into_iter()
+                    .map(|(s, e)| (s - start - 1, e - start - 1))
+                    .collect();
+                let mut formatted = vec![lines[start].trim().to_string()];
+                formatted.extend(sort_entries(&lines[start + 1..end], entries, style));
+                units.push(Unit {
+                    start,
+                    end,
+                    formatted,
+                });
+            }
+        };
+    let mut i = 0;
+    while i < lines.len() {
+        let trimmed = lines[i].trim();
+        if trimmed.starts_with('[') {
+            close_table(table.take(), i, &mut units);
+            table = Some((i, Vec::new()));
+            i += 1;
+            continue;
+        }
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            if table.is_none() {
+                units.push(Unit {
+                    start: i,
+                    end: i + 1,
+                    formatted: vec![trimmed.to_string()],
+                });
+            }
+            i += 1;
+            continue;
+        }
+        let start = i;
+        let mut scan = Scan::default();
+        scan.line(lines[i]);
+        i += 1;
+        while scan.open() && i < lines.len() {
+            scan.line(lines[i]);
+            i += 1;
+        }
+        match table.as_mut() {
+            Some((_, entries)) => entries.push((start, i)),
+            None => units.push(Unit {
+                start,
+                end: i,
+                formatted: format_entry(&lines[start..i], style),
+            }),
+        }
+    }
+    close_table(table, lines.len(), &mut units);
+    units
+}
+
+/// Formatted document.
+#[cfg(test)]
+fn format_document(text: &str) -> String {
+    let mut out: String = units(text, &FormattingStyle::default())
+        .into_iter()
+        .flat_map(|unit| unit.formatted)
+        .map(|line| line + "\n")
+        .collect();
+    // a single newline at the end
+    out.truncate(out.trim_end().len());
+    out.push('\n');
+    out
+}
+
+/// Edits that format the whole document or, if given, the units touching `range`.
+pub fn formatting_edits(
+    text: &str,
+    range: Option<Range>,
+    style: &FormattingStyle,
+) -> Vec<TextEdit> {
+    let lines: Vec<&str> = text.lines().collect();
+    units(text, style)
+        .into_iter()
+        .filter(|unit| {
+            range.is_none_or(|range| {
+                (unit.start as u32) <= range.end.line && range.start.line < unit.end as u32
+            })
+        })
+        .filter(|unit| lines[unit.start..unit.end] != unit.formatted[..])
+        .map(|unit| TextEdit {
+            range: Range::new(
+                Position::new(unit.start as u32, 0),
+                Position::new(unit.end as u32, 0),
+            ),
+            new_text: unit
+                .formatted
+                .iter()
+                .map(|line| format!("{line}\n"))
+                .collect(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn priors_are_aligned_and_stable() {
+        let text = std::fs::read_to_string("tests/mock/priors.toml").unwrap();
+        let formatted = format_document(&text);
+        assert!(formatted.contains(
+            "  {metabolite = \"g6p\",  compartment = \"c\", enzyme = \"E1\", exploc = 3.0,   scale = 0.2},\n"
+        ));
+        assert!(formatted.contains(
+            "  {reaction = \"g3pdrain\", experiment = \"Evo04Evo01EP\",         pct1 = 0.3, pct99 = 1.2},\n"
+        ));
+        // comments are kept
+        assert!(formatted.contains("scale = 0.2}, # mod\n"));
+        assert_eq!(format_document(&formatted), formatted);
+        assert!(toml::from_str::<crate::priors::Priors>(&formatted).is_ok());
+    }
+
+    #[test]
+    fn table_keys_are_ordered() {
+        let text = "[[reaction]]\n# the mechanism\nmechanism = \"drain\"\nname=\"g3pdrain\"\nid = \"g3pdrain\"  # drain\nstoichiometry = { g3p_c = -1 }\n";
+        assert_eq!(
+            format_document(text),
+            "[[reaction]]\nid = \"g3pdrain\" # drain\nname = \"g3pdrain\"\n# the mechanism\nmechanism = \"drain\"\nstoichiometry = { g3p_c = -1 }\n"
+        );
+    }
+
+    #[test]
+    fn kinetic_model_is_stable() {
+        let text = std::fs::read_to_string("tests/mock/ecoli_kinetic_model.toml").unwrap();
+        let formatted = format_document(&text);
+        assert_eq!(format_document(&formatted), formatted);
+        // commented out metabolites are kept as they are
+        assert!(formatted.contains("  #   {metabolite=\"nad\""));
+        assert!(crate::state::KineticModelState::try_from_str(formatted).is_ok());
+    }
+
+    #[test]
+    fn range_formats_only_touched_units() {
+        let text = "kcat = [\n  {reaction=\"A\", enzyme=\"E\"},\n]\nkm = [\n  {enzyme=\"E\"},\n]\n";
+        let edits = formatting_edits(
+            text,
+            Some(Range::new(Position::new(4, 0), Position::new(4, 1))),
+            &FormattingStyle::default(),
+        );
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].range.start.line, 3);
+        assert_eq!(edits[0].new_text, "km = [\n  {enzyme = \"E\"},\n]\n");
+    }
+
+    #[test]
+    fn style_can_keep_the_order_and_not_align() {
+        let text = "kcat = [\n  {reaction=\"A\", enzyme=\"E\", exploc = 1.0},\n  {reaction=\"PGI\", enzyme=\"E\", exploc = 1.0},\n]\n";
+        let style = FormattingStyle {
+            sort_keys: false,
+            align_columns: false,
+        };
+        let edits = formatting_edits(text, None, &style);
+        assert_eq!(
+            edits[0].new_text,
+            "kcat = [\n  {reaction = \"A\", enzyme = \"E\", exploc = 1.0},\n  {reaction = \"PGI\", enzyme = \"E\", exploc = 1.0},\n]\n"
+        );
+    }
+}
@@ -0,0 +1,552 @@
+//! Markdown hovers of the kinetic model entities, with links to their definitions.
+use lsp_types::Url;
+use serde::Deserialize;
+use toml::Spanned;
+
+use crate::csv_data::{CsvPrior, CsvTable};
+use crate::maud_data::{Enzyme, Metabolite, Reaction};
+use crate::metabolic::{to_reaction_str, Entity, MetabolicMetComp};
+use crate::priors::Prior;
+use crate::state::{offset_to_position, ProjectState};
+
+/// How much the hovers show, from the `hover` setting of the client.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HoverVerbosity {
+    /// The entity, its links and its priors.
+    #[default]
+    Full,
+    /// The entity and its links, without the table of priors.
+    Brief,
+}
+
+/// Renders links to lines of a document.
+struct Linker<'a> {
+    uri: &'a Url,
+    text: &'a str,
+}
+
+impl Linker<'_> {
+    fn link(&self, label: &str, offset: usize) -> String {
+        let line = offset_to_position(self.text, offset).line + 1;
+        format!("[{label}]({}#L{line})", self.uri)
+    }
+
+    /// Entries written as `[[tables]]` are not spanned, so they cannot be linked.
+    fn link_spanned<T>(&self, label: &str, spanned: &Spanned<T>) -> String {
+        match spanned.span() {
+            (0, 0) => label.to_string(),
+            (start, _) => self.link(label, start),
+        }
+    }
+}
+
+/// Row of the table of priors.
+struct PriorRow {
+    parameter: String,
+    target: String,
+    experiment: String,
+    distribution: String,
+}
+
+impl PriorRow {
+    fn new<P: Prior>(
+        linker: &Linker,
+        parameter: &str,
+        target: String,
+        experiment: &str,
+        prior: &Spanned<P>,
+    ) -> Self {
+        PriorRow {
+            parameter: linker.link_spanned(parameter, prior),
+            target,
+            experiment: experiment.to_string(),
+            distribution: prior.get_ref().distribution(),
+        }
+    }
+}
+
+/// Rows of the priors of a CSV priors file, grouped by parameter type, with the
+/// targets `target` names and without the priors it returns `None` for.
+fn csv_prior_rows(
+    linker: &Linker,
+    table: &CsvTable,
+    parameters: &[&str],
+    target: impl Fn(&CsvPrior) -> Option<String>,
+) -> Vec<PriorRow> {
+    let priors = table.priors();
+    let mut rows = Vec::new();
+    for parameter in parameters {
+        for prior in priors.iter().filter(|prior| prior.parameter == *parameter) {
+            if let Some(target) = target(prior) {
+                rows.push(PriorRow {
+                    parameter: linker.link(prior.parameter, prior.start),
+                    target,
+                    experiment: prior.experiment.unwrap_or_default().to_string(),
+                    distribution: prior.distribution.clone(),
+                });
+            }
+        }
+    }
+    rows
+}
+
+/// Km, ki and unbalanced concentration priors of metabolites in compartments.
+fn met_comp_prior_rows(
+    project: &ProjectState,
+    priors: &Linker,
+    met_comps: &[String],
+) -> Vec<PriorRow> {
+    if let Some(table) = project.csv_document(&project.priors_uri) {
+        return csv_prior_rows(priors, table, &["km", "conc_unbalanced"], |prior| {
+            let met_comp = prior
+                .met_comp
+                .as_ref()
+                .filter(|mc| met_comps.contains(mc))?;
+            match prior.parameter {
+                "km" => prior.enzyme.map(|enzyme| format!("{enzyme} {met_comp}")),
+                _ => Some(met_comp.clone()),
+            }
+        });
+    }
+    let prior_index = project.priors_state.borrow_prior_index();
+    // in the order of the priors file
+    let mut kms: Vec<_> = met_comps
+        .iter()
+        .flat_map(|met_comp| prior_index.kms_of_met_comp(met_comp))
+        .collect();
+    kms.sort_by_key(|km| km.span().0);
+    let mut kis: Vec<_> = met_comps
+        .iter()
+        .flat_map(|met_comp| prior_index.kis_of_met_comp(met_comp))
+        .collect();
+    kis.sort_by_key(|ki| ki.span().0);
+    let mut concs: Vec<_> = met_comps
+        .iter()
+        .flat_map(|met_comp| prior_index.conc_unbalanced_of_met_comp(met_comp))
+        .collect();
+    concs.sort_by_key(|conc| conc.span().0);
+    let kms = kms.into_iter().map(|km| {
+        let prior = km.get_ref();
+        let target = format!(
+            "{} {}_{}",
+            prior.enzyme, prior.metabolite, prior.compartment
+        );
+        PriorRow::new(priors, "km", target, "", km)
+    });
+    let kis = kis.into_iter().map(|ki| {
+        let prior = ki.get_ref();
+        let target = format!(
+            "{} {}_{}",
+            prior.enzyme, prior.metabolite, prior.compartment
+        );
+        PriorRow::new(priors, "ki", target, "", ki)
+    });
+    let concs = concs.into_iter().map(|conc| {
+        let prior = conc.get_ref();
+        let target = format!("{}_{}", prior.metabolite, prior.compartment);
+        PriorRow::new(priors, "conc_unbalanced", target, &prior.experiment, conc)
+    });
+    kms.chain(kis).chain(concs).collect()
+}
+
+fn render_priors(rows: &[PriorRow], verbosity: HoverVerbosity) -> String {
+    if verbosity == HoverVerbosity::Brief {
+        return String::new();
+    }
+    if rows.is_empty() {
+        return String::from("\nNo priors.\n");
+    }
+    let mut out =
+        String::from("\n| prior | target | experiment | distribution |\n|---|---|---|---|\n");
+    for row in rows {
+        out.push_str(&format!(
+            "| {} | {} | {} | {} |\n",
+            row.parameter, row.target, row.experiment, row.distribution
+        ));
+    }
+    out
+}
+
+fn join_or_none(items: Vec<String>) -> String {
+    if items.is_empty() {
+        String::from("none")
+    } else {
+        items.join(", ")
+    }
+}
+
+/// Metabolite of a metabolite in compartment, e.g. `g6p` in `g6p_c`.
+fn metabolite_of(met_comp: &str) -> &str {
+    met_comp.rsplit_once('_').map_or(met_comp, |(met, _)| met)
+}
+
+fn metabolite_hover(
+    project: &ProjectState,
+    model: &Linker,
+    priors: &Linker,
+    met: &Metabolite,
+) -> String {
+    let kinetic_model = project.kinetic_state.borrow_kinetic_model();
+    let id = *met.id.get_ref();
+    let mut met_comps = Vec::new();
+    let mut compartments: Vec<String> = kinetic_model
+        .metabolite_in_compartment
+        .iter()
+        .filter(|mic| mic.metabolite_id.get_ref() == &id)
+        .map(|mic| {
+            met_comps.push(format!("{id}_{}", mic.compartment_id));
+            let balanced = if mic.balanced {
+                "balanced"
+            } else {
+                "unbalanced"
+            };
+            format!("`{}` ({balanced})", mic.compartment_id)
+        })
+        .collect();
+    let mut produced_by = Vec::new();
+    let mut consumed_by = Vec::new();
+    let symbols = project.kinetic_state.borrow_symbols();
+    for reac in symbols.reactions_of_metabolite(id) {
+        for (met_comp, coefficient) in reac.stoichiometry.iter() {
+            if metabolite_of(met_comp) != id {
+                continue;
+            }
+            let compartment = format!("`{}`", &met_comp[id.len()..].trim_start_matches('_'));
+            if !compartments.iter().any(|c| c.starts_with(&compartment)) {
+                compartments.push(compartment);
+                met_comps.push(met_comp.to_string());
+            }
+            let link = model.link(reac.id.get_ref(), reac.id.span().0);
+            if *coefficient > 0. {
+                produced_by.push(link);
+            } else {
+                consumed_by.push(link);
+            }
+        }
+    }
+    let rows = met_comp_prior_rows(project, priors, &met_comps);
+    format!(
+        "**metabolite** `{id}`: {}\n\n- inchi_key: {}\n- compartments: {}\n- produced by: {}\n- consumed by: {}\n{}",
+        met.name,
+        met.inchi_key,
+        join_or_none(compartments),
+        join_or_none(produced_by),
+        join_or_none(consumed_by),
+        render_priors(&rows, project.hover_verbosity()),
+    )
+}
+
+fn met_comp_hover(
+    project: &ProjectState,
+    model: &Linker,
+    priors: &Linker,
+    met_comp: &MetabolicMetComp,
+) -> String {
+    let met = met_comp.metabolite;
+    let id = format!("{}_{}", met.id.get_ref(), met_comp.compartment_id);
+    let compartment = match met_comp.compartment {
+        Some(comp) => format!(
+            "{}: {}, volume {}",
+            model.link(comp.id.get_ref(), comp.id.span().0),
+            comp.name,
+            comp.volume
+        ),
+        None => format!("`{}` (not defined)", met_comp.compartment_id),
+    };
+    let balanced = match met_comp.row {
+        Some(row) => model.link(
+            if row.balanced {
+                "balanced"
+            } else {
+                "unbalanced"
+            },
+            row.metabolite_id.span().0,
+        ),
+        None => String::from("unknown, there is no `metabolite_in_compartment` row"),
+    };
+    let mut produced_by = Vec::new();
+    let mut consumed_by = Vec::new();
+    let symbols = project.kinetic_state.borrow_symbols();
+    for reac in symbols.reactions_of_met_comp(&id) {
+        if let Some(coefficient) = reac.stoichiometry.get(id.as_str()) {
+            let link = model.link(reac.id.get_ref(), reac.id.span().0);
+            if *coefficient > 0. {
+                produced_by.push(link);
+            } else {
+                consumed_by.push(link);
+            }
+        }
+    }
+    let rows = met_comp_prior_rows(project, priors, std::slice::from_ref(&id));
+    format!(
+        "**metabolite in compartment** `{id}`\n\n- metabolite: {}: {}\n- compartment: {compartment}\n- balanced: {balanced}\n- produced by: {}\n- consumed by: {}\n{}",
+        model.link(met.id.get_ref(), met.id.span().0),
+        met.name,
+        join_or_none(produced_by),
+        join_or_none(consumed_by),
+        render_priors(&rows, project.hover_verbosity()),
+    )
+}
+
+fn reaction_hover(
+    project: &ProjectState,
+    model: &Linker,
+    priors: &Linker,
+    reac: &Reaction,
+) -> String {
+    let symbols = project.kinetic_state.borrow_symbols();
+    let id = *reac.id.get_ref();
+    let enzymes = symbols.enzymes_of_reaction(id);
+    let enzyme_links = enzymes
+        .iter()
+        .filter_map(|enzyme| symbols.enzyme(enzyme))
+        .map(|enz| model.link(enz.id.get_ref(), enz.id.span().0))
+        .collect();
+    let prior_index = project.priors_state.borrow_prior_index();
+    let kcats = prior_index
+        .kcats_of_reaction(id)
+        .iter()
+        .map(|kcat| PriorRow::new(priors, "kcat", kcat.get_ref().enzyme.clone(), "", kcat));
+    let mut kms: Vec<_> = enzymes
+        .iter()
+        .flat_map(|enzyme| prior_index.kms_of_enzyme(enzyme))
+        .filter(|km| {
+            let km = km.get_ref();
+            reac.stoichiometry
+                .contains_key(format!("{}_{}", km.metabolite, km.compartment).as_str())
+        })
+        .collect();
+    // in the order of the priors file
+    kms.sort_by_key(|km| km.span().0);
+    let kms = kms.into_iter().map(|km| {
+        let target = format!(
+            "{} {}_{}",
+            km.get_ref().enzyme,
+            km.get_ref().metabolite,
+            km.get_ref().compartment
+        );
+        PriorRow::new(priors, "km", target, "", km)
+    });
+    let drains = prior_index.drains_of_reaction(id).iter().map(|drain| {
+        PriorRow::new(
+            priors,
+            "drain",
+            id.to_string(),
+            &drain.get_ref().experiment,
+            drain,
+        )
+    });
+    let csv_target = |prior: &CsvPrior| match prior.parameter {
+        "kcat" if prior.reaction == Some(id) => prior.enzyme.map(String::from),
+        "km" => {
+            let enzyme = prior.enzyme.filter(|enzyme| enzymes.contains(enzyme))?;
+            let met_comp = prior.met_comp.as_deref()?;
+            reac.stoichiometry
+                .contains_key(met_comp)
+                .then(|| format!("{enzyme} {met_comp}"))
+        }
+        "drain" if prior.reaction == Some(id) => Some(id.to_string()),
+        _ => None,
+    };
+    // a CSV priors file leaves the priors state empty
+    let rows = match project.csv_document(&project.priors_uri) {
+        Some(table) => csv_prior_rows(priors, table, &["kcat", "km", "drain"], csv_target),
+        None => kcats.chain(kms).chain(drains).collect(),
+    };
+    format!(
+        "**reaction** `{id}`: {}\n\n- mechanism: {}\n- equation: `{}`\n- enzymes: {}\n{}",
+        reac.name,
+        reac.mechanism,
+        to_reaction_str(&reac.stoichiometry),
+        join_or_none(enzyme_links),
+        render_priors(&rows, project.hover_verbosity()),
+    )
+}
+
+fn enzyme_hover(
+    project: &ProjectState,
+    model: &Linker,
+    priors: &Linker,
+    enz: &Enzyme,
+    reactions: &[&str],
+) -> String {
+    let symbols = project.kinetic_state.borrow_symbols();
+    let id = *enz.id.get_ref();
+    let reaction_links = reactions
+        .iter()
+        .filter_map(|reaction| symbols.reaction(reaction))
+        .map(|reac| model.link(reac.id.get_ref(), reac.id.span().0))
+        .collect();
+    let prior_index = project.priors_state.borrow_prior_index();
+    let kcats = prior_index
+        .kcats_of_enzyme(id)
+        .iter()
+        .map(|kcat| PriorRow::new(priors, "kcat", kcat.get_ref().reaction.clone(), "", kcat));
+    let kms = prior_index.kms_of_enzyme(id).iter().map(|km| {
+        let target = format!("{}_{}", km.get_ref().metabolite, km.get_ref().compartment);
+        PriorRow::new(priors, "km", target, "", km)
+    });
+    let kis = prior_index.kis_of_enzyme(id).iter().map(|ki| {
+        let target = format!("{}_{}", ki.get_ref().metabolite, ki.get_ref().compartment);
+        PriorRow::new(priors, "ki", target, "", ki)
+    });
+    let concs = prior_index.concentrations_of_enzyme(id).iter().map(|conc| {
+        PriorRow::new(
+            priors,
+            "conc_enzyme",
+            id.to_string(),
+            &conc.get_ref().experiment,
+            conc,
+        )
+    });
+    let csv_target = |prior: &CsvPrior| match prior.parameter {
+        _ if prior.enzyme != Some(id) => None,
+        "kcat" => prior.reaction.map(String::from),
+        "km" => prior.met_comp.clone(),
+        _ => Some(id.to_string()),
+    };
+    let rows = match project.csv_document(&project.priors_uri) {
+        Some(table) => csv_prior_rows(priors, table, &["kcat", "km", "conc_enzyme"], csv_target),
+        None => kcats.chain(kms).chain(kis).chain(concs).collect(),
+    };
+    format!(
+        "**enzyme** `{id}`: {}\n\n- subunits: {}\n- catalyses: {}\n{}",
+        enz.name,
+        enz.subunits,
+        join_or_none(reaction_links),
+        render_priors(&rows, project.hover_verbosity()),
+    )
+}
+
+/// Markdown description of a symbol of the kinetic model, `None` if it is not defined.
+pub fn hover_markdown(project: &ProjectState, symbol: &str) -> Option<String> {
+    let model = Linker {
+        uri: &project.kinetic_model_uri,
+        text: project.kinetic_state.borrow_file_str(),
+    };
+    let priors = Linker {
+        uri: &project.priors_uri,
+        text: match project.csv_document(&project.priors_uri) {
+            Some(table) => &table.text,
+            None => project.priors_state.borrow_file_str(),
+        },
+    };
+    let entities = project.kinetic_state.find_symbol(symbol);
+    let hovers: Vec<String> = entities
+        .iter()
+        .map(|entity| match entity {
+            Entity::Met(met) => metabolite_hover(project, &model, &priors, met),
+            Entity::Reac(reac) => reaction_hover(project, &model, &priors, reac),
+            Entity::Enz(enz) => enzyme_hover(project, &model, &priors, enz.enzyme, &enz.reactions),
+            Entity::MetComp(met_comp) => met_comp_hover(project, &model, &priors, met_comp),
+        })
+        .collect();
+    match hovers.len() {
+        0 => None,
+        1 => hovers.into_iter().next(),
+        _ => {
+            let kinds: Vec<&str> = entities.iter().map(Entity::kind).collect();
+            Some(format!(
+                "`{symbol}` is ambiguous, it is the id of a {}.\n\n---\n\n{}",
+                kinds.join(" and a "),
+                hovers.join("\n---\n\n")
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::csv_data::{CsvPrior, CsvTable};
+    use crate::maud_data::MaudConfig;
+
+    fn mock_project() -> ProjectState {
+        let root_dir = std::env::current_dir().unwrap().join("tests/mock");
+        let config: MaudConfig =
+            toml::from_str(&std::fs::read_to_string(root_dir.join("config.toml")).unwrap())
+                .unwrap();
+        ProjectState::try_from_config(&root_dir, &config).unwrap()
+    }
+
+    #[test]
+    fn metabolite_hover_links_reactions() {
+        let project = mock_project();
+        let hover = hover_markdown(&project, "f6p").unwrap();
+        assert!(hover.contains("**metabolite** `f6p`"));
+        // PGI produces it, PFK consumes it
+        assert!(hover.contains("- produced by: [PGI]("));
+        assert!(hover.contains("- consumed by: [PFK]("));
+        assert!(hover.contains("ecoli_kinetic_model.toml#L"));
+    }
+
+    #[test]
+    fn enzyme_hover_lists_priors() {
+        let project = mock_project();
+        let hover = hover_markdown(&project, "E1").unwrap();
+        assert!(hover.contains("- catalyses: [PGI]("));
+        assert!(hover.contains("| [conc_enzyme]("));
+    }
+
+    #[test]
+    fn hovers_list_priors_of_a_csv_file() {
+        let mut project = mock_project();
+        project.csv_documents.insert(
+            project.priors_uri.clone(),
+            std::sync::Arc::new(CsvTable::parse(
+                "parameter_type,enzyme_id,reaction_id,mic_id,experiment_id,location,scale
+kcat,E1,PGI,,,100,0.1
+km,E1,,g6p_c,,1,0.1
+kcat,E2,PFK,,,100,0.1
+"
+                .to_string(),
+            )),
+        );
+        let hover = hover_markdown(&project, "E1").unwrap();
+        assert!(hover.contains("#L2) | PGI |  | location 100, scale 0.1 |"));
+        assert!(hover.contains("#L3) | g6p_c |  | location 1, scale 0.1 |"));
+        assert!(!hover.contains("#L4)"));
+        let hover = hover_markdown(&project, "PGI").unwrap();
+        assert!(hover.contains("#L2) | E1 |  |"));
+        assert!(hover.contains("#L3) | E1 g6p_c |  |"));
+    }
+
+    #[test]
+    fn brief_hovers_leave_out_the_priors() {
+        let mut project = mock_project();
+        project.set_settings(&crate::settings::Settings {
+            hover: HoverVerbosity::Brief,
+            ..Default::default()
+        });
+        let hover = hover_markdown(&project, "E1").unwrap();
+        assert!(hover.contains("- catalyses: [PGI]("));
+        assert!(!hover.contains("conc_enzyme"));
+    }
+
+    #[test]
+    fn met_comp_hover_shows_balanced_row() {
+        let project = mock_project();
+        let hover = hover_markdown(&project, "g6p_c").unwrap();
+        assert!(hover.contains("cytosol, volume 1"));
+        assert!(hover.contains("- balanced: [unbalanced]("));
+        assert!(hover.contains("- consumed by: [PGI]("));
+    }
+
+    #[test]
+    fn metabolite_hovers_list_priors_of_their_compartments() {
+        let project = mock_project();
+        let hover = hover_markdown(&project, "g6p_c").unwrap();
+        assert!(hover.contains("| E1 g6p_c |  | location 3, scale 0.2 |"));
+        assert!(hover.contains("| [conc_unbalanced]("));
+        assert!(hover.contains("| g6p_c | Evo04Evo01EP |"));
+        let hover = hover_markdown(&project, "g6p").unwrap();
+        assert!(hover.contains("| E1 g6p_c |"));
+        assert!(!hover.contains("| E1 f6p_c |"));
+    }
+
+    #[test]
+    fn nothing_matches() {
+        assert!(hover_markdown(&mock_project(), "nope").is_none());
+    }
+}
@@ -0,0 +1,335 @@
+//! Maud identifier rules.
+//!
+//! Identifiers may only contain ASCII letters and digits: underscores separate
+//! metabolites from compartments and Stan rejects most other characters.
+//! Renaming an identifier edits its references of the same kind in every
+//! document of the project, TOML or CSV.
+use std::collections::{HashMap, HashSet};
+
+use lsp_types::{
+    CodeAction, CodeActionKind, CodeActionOrCommand, Diagnostic, DiagnosticRelatedInformation,
+    Location, Range, TextEdit, Url, WorkspaceEdit,
+};
+use toml::Spanned;
+
+use crate::csv_data::{ColumnKind, CsvTable};
+use crate::rules::Rule;
+use crate::state::{span_to_range, KineticModelState};
+use crate::symbol_parser::{find_references, reference_kind};
+
+/// Identifier in the kinetic model that breaks a Maud rule.
+pub struct InvalidId {
+    pub id: String,
+    /// Kind of entity the identifier names.
+    pub kind: ColumnKind,
+    pub message: String,
+    pub rule: Rule,
+    /// Span of the identifier in the kinetic model.
+    pub span: (usize, usize),
+    /// Span of a clashing identifier, if any.
+    pub clashes_with: Option<(usize, usize)>,
+    /// Compliant identifier proposed for renaming.
+    pub proposal: String,
+}
+
+/// Reason why an identifier is rejected, if it is.
+fn rule_violation(id: &str) -> Option<String> {
+    if id.contains('_') {
+        Some(format!(
+            "Identifier '{id}' contains underscores, which separate metabolites and compartments."
+        ))
+    } else if id.contains(char::is_whitespace) {
+        Some(format!("Identifier '{id}' contains whitespace."))
+    } else {
+        id.chars()
+            .find(|c| !c.is_ascii_alphanumeric())
+            .map(|c| format!("Identifier '{id}' contains '{c}', which is not allowed by Maud."))
+    }
+}
+
+/// Make an identifier compliant and unique (case-insensitively) among `taken`.
+pub(crate) fn propose_id(id: &str, fallback: &str, taken: &HashSet<String>) -> String {
+    let mut base: String = id.chars().filter(char::is_ascii_alphanumeric).collect();
+    if base.is_empty() {
+        base = fallback.to_string();
+    }
+    let mut proposal = base.clone();
+    let mut n = 2;
+    while taken.contains(&proposal.to_lowercase()) {
+        proposal = format!("{base}{n}");
+        n += 1;
+    }
+    proposal
+}
+
+fn check_ids<'a>(
+    kind: ColumnKind,
+    ids: impl Iterator<Item = &'a Spanned<&'a str>>,
+) -> Vec<InvalidId> {
+    let name = kind.describe();
+    let ids: Vec<_> = ids.collect();
+    let taken: HashSet<String> = ids.iter().map(|id| id.get_ref().to_lowercase()).collect();
+    let mut seen: HashMap<String, &Spanned<&str>> = HashMap::new();
+    let mut invalid = Vec::new();
+    for spanned in ids {
+        let id = *spanned.get_ref();
+        if let Some(message) = rule_violation(id) {
+            invalid.push(InvalidId {
+                id: id.to_string(),
+                kind,
+                message,
+                rule: Rule::InvalidId,
+                span: spanned.span(),
+                clashes_with: None,
+                proposal: propose_id(id, name, &taken),
+            });
+        }
+        match seen.get(&id.to_lowercase()) {
+            // exact duplicates are reported on their own
+            Some(first) if first.get_ref() != &id => invalid.push(InvalidId {
+                id: id.to_string(),
+                kind,
+                message: format!(
+                    "{}{} '{id}' only differs in case from '{}'.",
+                    name[..1].to_uppercase(),
+                    &name[1..],
+                    first.get_ref()
+                ),
+                rule: Rule::IdCaseClash,
+                span: spanned.span(),
+                clashes_with: Some(first.span()),
+                proposal: propose_id(id, name, &taken),
+            }),
+            Some(_) => (),
+            None => {
+                seen.insert(id.to_lowercase(), spanned);
+            }
+        }
+    }
+    invalid
+}
+
+/// Metabolites, reactions, enzymes and compartments whose identifiers are not valid in Maud.
+pub fn invalid_identifiers(kinetic_state: &KineticModelState) -> Vec<InvalidId> {
+    let kinetic_model = kinetic_state.borrow_kinetic_model();
+    check_ids(
+        ColumnKind::Metabolite,
+        kinetic_model.metabolites.iter().map(|met| &met.id),
+    )
+    .into_iter()
+    .chain(check_ids(
+        ColumnKind::Reaction,
+        kinetic_model.reactions.iter().map(|reac| &reac.id),
+    ))
+    .chain(check_ids(
+        ColumnKind::Enzyme,
+        kinetic_model.enzymes.iter().map(|enz| &enz.id),
+    ))
+    .chain(check_ids(
+        ColumnKind::Compartment,
+        kinetic_model.compartments.iter().map(|comp| &comp.id),
+    ))
+    .collect()
+}
+
+/// A document of the project where identifiers are renamed.
+pub enum Document<'a> {
+    Toml(&'a Url, &'a str),
+    Csv(&'a Url, &'a CsvTable),
+}
+
+impl InvalidId {
+    pub fn to_diagnostic(&self, text: &str, uri: &Url) -> Diagnostic {
+        Diagnostic {
+            range: span_to_range(text, self.span),
+            severity: Some(self.rule.default_severity()),
+            code: Some(self.rule.diagnostic_code()),
+            message: self.message.clone(),
+            related_information: self.clashes_with.map(|first| {
+                vec![DiagnosticRelatedInformation {
+                    location: Location {
+                        uri: uri.clone(),
+                        range: span_to_range(text, first),
+                    },
+                    message: "Clashing identifier.".to_string(),
+                }]
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// Quick fix that renames the identifier and all its references in `documents`.
+    ///
+    /// The first document must be the kinetic model, where the identifier is defined.
+    /// Only references to an entity of the same kind are renamed. If `ambiguous`,
+    /// the identifier also names an entity of another kind, and references whose
+    /// kind cannot be told are left alone.
+    pub fn to_code_action(&self, documents: &[Document], ambiguous: bool) -> CodeActionOrCommand {
+        let Document::Toml(uri, text) = documents[0] else {
+            unreachable!("the kinetic model is a TOML document")
+        };
+        let changes = documents
+            .iter()
+            .map(|document| {
+                let (uri, text, spans) = match document {
+                    Document::Toml(uri, text) => (
+                        uri,
+                        *text,
+                        find_references(text, &self.id)
+                            .into_iter()
+                            .filter(|&span| match reference_kind(text, span) {
+                                Some(kind) => kind == self.kind,
+                                None => !ambiguous,
+                            })
+                            .collect(),
+                    ),
+                    Document::Csv(uri, table) => (
+                        uri,
+                        table.text.as_str(),
+                        table.references(self.kind, &self.id),
+                    ),
+                };
+                let edits = spans
+                    .into_iter()
+                    .map(|span: (usize, usize)| TextEdit {
+                        range: span_to_range(text, span),
+                        new_text: self.proposal.clone(),
+                    })
+                    .collect::<Vec<_>>();
+                ((*uri).clone(), edits)
+            })
+            .filter(|(_, edits)| !edits.is_empty())
+            .collect();
+        CodeActionOrCommand::CodeAction(CodeAction {
+            title: format!("Rename '{}' to '{}'", self.id, self.proposal),
+            kind: Some(CodeActionKind::QUICKFIX),
+            diagnostics: Some(vec![self.to_diagnostic(text, uri)]),
+            edit: Some(WorkspaceEdit {
+                changes: Some(changes),
+                ..Default::default()
+            }),
+            is_preferred: Some(true),
+            ..Default::default()
+        })
+    }
+
+    /// Whether the reported span overlaps with an LSP range.
+    pub fn intersects(&self, text: &str, range: &Range) -> bool {
+        let own = span_to_range(text, self.span);
+        own.start <= range.end && range.start <= own.end
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn underscores_and_symbols_are_rejected() {
+        assert!(rule_violation("g6p").is_none());
+        assert!(rule_violation("13dpg").is_none());
+        assert!(rule_violation("g6p_c").is_some());
+        assert!(rule_violation("g6 p").is_some());
+        assert!(rule_violation("g6p-").is_some());
+    }
+
+    #[test]
+    fn proposed_ids_are_unique() {
+        let taken = HashSet::from(["g6p".to_string(), "g6p2".to_string()]);
+        assert_eq!(propose_id("g_6p", "Metabolite", &taken), "g6p3");
+        assert_eq!(propose_id("__", "metabolite", &taken), "metabolite");
+    }
+
+    #[test]
+    fn mock_model_has_valid_ids() {
+        let kinetic_state = KineticModelState::from_path(
+            std::env::current_dir()
+                .unwrap()
+                .join("tests/mock/ecoli_kinetic_model.toml"),
+        );
+        assert!(invalid_identifiers(&kinetic_state).is_empty());
+    }
+
+    #[test]
+    fn references_are_found_in_stoichiometries_and_priors() {
+        let model = std::fs::read_to_string("tests/mock/ecoli_kinetic_model.toml").unwrap();
+        let priors = std::fs::read_to_string("tests/mock/priors.toml").unwrap();
+        // definition, one stoichiometry and its metabolite_in_compartment row
+        assert_eq!(find_references(&model, "g6p").len(), 3);
+        // km, two conc_unbalanced and dgf ids
+        assert_eq!(find_references(&priors, "g6p").len(), 4);
+        assert!(find_references(&model, "g6p")
+            .iter()
+            .all(|&(start, end)| &model[start..end] == "g6p"));
+    }
+
+    #[test]
+    fn renames_only_references_of_the_same_kind() {
+        let model = r#"
+metabolite = [{id = "g6p", name = "g6p", inchi_key = "A"}]
+compartment = [{id = "c_1", name = "cytosol", volume = 1}]
+enzyme = [{id = "PG_I", name = "pgi", subunits = 1}]
+enzyme_reaction = [{enzyme_id = "PG_I", reaction_id = "PG_I"}]
+
+[[reaction]]
+id = "PG_I"
+name = "pgi"
+stoichiometry = {g6p_c_1 = -1}
+mechanism = "reversible_michaelis_menten"
+"#;
+        let kinetic_state = KineticModelState::try_from_str(model.to_string()).unwrap();
+        let invalid = invalid_identifiers(&kinetic_state);
+        let kinds: Vec<_> = invalid.iter().map(|inv| inv.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                ColumnKind::Reaction,
+                ColumnKind::Enzyme,
+                ColumnKind::Compartment
+            ]
+        );
+        let uri = Url::parse("file:///model.toml").unwrap();
+        let csv_uri = Url::parse("file:///measurements.csv").unwrap();
+        let table = CsvTable::parse(
+            "measurement_type,target_id,experiment_id,measurement,error_scale
+enzyme,PG_I,condition1,0.5,0.1
+flux,PG_I,condition1,0.5,0.1
+mic,g6p_c_1,condition1,0.5,0.1
+"
+            .to_string(),
+        );
+        let documents = [Document::Toml(&uri, model), Document::Csv(&csv_uri, &table)];
+        let edited_lines = |inv: &InvalidId| {
+            let CodeActionOrCommand::CodeAction(action) = inv.to_code_action(&documents, true)
+            else {
+                panic!("not a code action")
+            };
+            let changes = action.edit.unwrap().changes.unwrap();
+            let mut lines: Vec<(bool, u32)> = changes
+                .iter()
+                .flat_map(|(uri, edits)| {
+                    let is_csv = uri == &csv_uri;
+                    edits
+                        .iter()
+                        .map(move |edit| (is_csv, edit.range.start.line))
+                })
+                .collect();
+            lines.sort();
+            lines
+        };
+        // the definition and the enzyme_reaction row, then the enzyme measurement
+        assert_eq!(
+            edited_lines(&invalid[1]),
+            [(false, 3), (false, 4), (true, 1)]
+        );
+        assert_eq!(
+            edited_lines(&invalid[0]),
+            [(false, 4), (false, 7), (true, 2)]
+        );
+        // the definition, the stoichiometry key and the mic
+        assert_eq!(
+            edited_lines(&invalid[2]),
+            [(false, 2), (false, 9), (true, 3)]
+        );
+    }
+}
@@ -0,0 +1,347 @@
+//! Initial values of the sampler, read from the `user_inits_file` of the config.
+//!
+//! Like the priors, each parameter is an array of tables that identify it,
+//! e.g. `km = [{enzyme = "E1", metabolite = "g6p", compartment = "c", value = 0.3}]`.
+use std::collections::{BTreeMap, HashSet};
+
+use lsp_types::{Diagnostic, Range};
+use serde::Deserialize;
+use toml::Spanned;
+
+use crate::csv_data::{ColumnKind, CsvTable, KnownIds};
+use crate::rules::Rule;
+use crate::state::span_to_range;
+use crate::symbols::SymbolIndex;
+
+/// Initial value of a parameter.
+#[derive(Debug, Deserialize)]
+struct Init {
+    enzyme: Option<String>,
+    reaction: Option<String>,
+    metabolite: Option<String>,
+    compartment: Option<String>,
+    experiment: Option<String>,
+    #[serde(alias = "init")]
+    value: Option<f64>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Field {
+    Enzyme,
+    Reaction,
+    Metabolite,
+    Compartment,
+    Experiment,
+}
+
+impl Field {
+    fn name(self) -> &'static str {
+        match self {
+            Field::Enzyme => "enzyme",
+            Field::Reaction => "reaction",
+            Field::Metabolite => "metabolite",
+            Field::Compartment => "compartment",
+            Field::Experiment => "experiment",
+        }
+    }
+
+    fn of(self, init: &Init) -> Option<&str> {
+        match self {
+            Field::Enzyme => init.enzyme.as_deref(),
+            Field::Reaction => init.reaction.as_deref(),
+            Field::Metabolite => init.metabolite.as_deref(),
+            Field::Compartment => init.compartment.as_deref(),
+            Field::Experiment => init.experiment.as_deref(),
+        }
+    }
+
+    fn known(self, known: &KnownIds) -> &HashSet<String> {
+        match self {
+            Field::Enzyme => &known.enzymes,
+            Field::Reaction => &known.reactions,
+            Field::Metabolite => &known.metabolites,
+            Field::Compartment => &known.compartments,
+            Field::Experiment => &known.experiments,
+        }
+    }
+}
+
+use Field::*;
+
+/// Parameters that can be initialized, with the fields that identify them.
+const PARAMETERS: [(&str, &[Field]); 10] = [
+    ("kcat", &[Enzyme, Reaction]),
+    ("km", &[Enzyme, Metabolite, Compartment]),
+    ("ki", &[Enzyme, Metabolite, Compartment]),
+    ("dissociation_constant", &[Enzyme, Metabolite, Compartment]),
+    ("transfer_constant", &[Enzyme]),
+    ("dgf", &[Metabolite]),
+    ("conc_enzyme", &[Enzyme, Experiment]),
+    ("conc_unbalanced", &[Metabolite, Compartment, Experiment]),
+    ("drain", &[Reaction, Experiment]),
+    ("psi", &[Experiment]),
+];
+
+/// Why an init does not match the model, with the rule it breaks.
+fn check_init(
+    parameter: &str,
+    fields: &[Field],
+    init: &Init,
+    known: &KnownIds,
+    symbols: &SymbolIndex,
+) -> Vec<(Rule, String)> {
+    let mut problems = Vec::new();
+    for field in fields {
+        match field.of(init) {
+            None => problems.push((
+                Rule::InvalidInit,
+                format!("Missing {} of the {parameter} init.", field.name()),
+            )),
+            Some(id) if !field.known(known).contains(id) => {
+                problems.push((Rule::UnknownId, format!("Unknown {} {id}.", field.name())))
+            }
+            Some(_) => (),
+        }
+    }
+    if init.value.is_none() {
+        problems.push((
+            Rule::InvalidInit,
+            format!("Missing value of the {parameter} init."),
+        ));
+    }
+    if !problems.is_empty() {
+        return problems;
+    }
+    let enzyme = init.enzyme.clone().unwrap_or_default();
+    let met_comp = init
+        .metabolite
+        .as_ref()
+        .zip(init.compartment.as_ref())
+        .map(|(met, comp)| format!("{met}_{comp}"));
+    match (parameter, met_comp) {
+        ("kcat", _) => {
+            let reaction = init.reaction.clone().unwrap_or_default();
+            if !symbols.catalyzes(&enzyme, &reaction) {
+                problems.push((
+                    Rule::InvalidInit,
+                    format!("Enzyme {enzyme} does not catalyze reaction {reaction}."),
+                ));
+            }
+        }
+        ("km", Some(met_comp)) if !symbols.has_km(&enzyme, &met_comp) => {
+            problems.push((
+                Rule::InvalidInit,
+                format!(
+                    "km init of {enzyme} for {met_comp} is unused: none of {enzyme}'s reactions involve {met_comp}."
+                ),
+            ));
+        }
+        ("km", _) => (),
+        (_, Some(met_comp)) if !known.met_comps.contains(&met_comp) => {
+            problems.push((
+                Rule::UnknownId,
+                format!("Metabolite in compartment {met_comp} is not in the kinetic model."),
+            ));
+        }
+        _ => (),
+    }
+    problems
+}
+
+/// Diagnostics of a TOML inits file against the kinetic model and the experiments.
+pub fn inits_diagnostics(text: &str, symbols: &SymbolIndex, known: &KnownIds) -> Vec<Diagnostic> {
+    let diagnostic = |range: Range, rule: Rule, message: String| Diagnostic {
+        range,
+        severity: Some(rule.default_severity()),
+        code: Some(rule.diagnostic_code()),
+        message,
+        ..Default::default()
+    };
+    let inits: BTreeMap<String, Vec<Spanned<Init>>> = match toml::from_str(text) {
+        Ok(inits) => inits,
+        Err(e) => {
+            let range = e
+                .line_col()
+                .map(|(line, col)| {
+                    let position = lsp_types::Position::new(line as u32, col as u32);
+                    Range::new(position, position)
+                })
+                .unwrap_or_default();
+            return vec![diagnostic(range, Rule::InvalidInit, e.to_string())];
+        }
+    };
+    let mut diagnostics = Vec::new();
+    for (parameter, rows) in inits.iter() {
+        let Some((_, fields)) = PARAMETERS.iter().find(|(name, _)| name == parameter) else {
+            diagnostics.extend(rows.first().map(|row| {
+                diagnostic(
+                    span_to_range(text, row.span()),
+                    Rule::InvalidInit,
+                    format!("Maud has no parameter {parameter}."),
+                )
+            }));
+            continue;
+        };
+        for row in rows {
+            for (rule, message) in check_init(parameter, fields, row.get_ref(), known, symbols) {
+                diagnostics.push(diagnostic(span_to_range(text, row.span()), rule, message));
+            }
+        }
+    }
+    diagnostics
+}
+
+/// Diagnostics of a CSV inits file, with a row per init and a `parameter` column.
+///
+/// Unknown identifiers are already reported on their cells by [`CsvTable::diagnostics`].
+pub fn csv_inits_diagnostics(
+    table: &CsvTable,
+    symbols: &SymbolIndex,
+    known: &KnownIds,
+) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for row in &table.rows {
+        let Some(parameter) = table.value(row, ColumnKind::ParameterType) else {
+            continue;
+        };
+        let span = (row[0].span.0, row[row.len() - 1].span.1);
+        let Some((_, fields)) = PARAMETERS.iter().find(|(name, _)| *name == parameter) else {
+            diagnostics.push((
+                span,
+                Rule::InvalidInit,
+                format!("Maud has no parameter {parameter}."),
+            ));
+            continue;
+        };
+        // a metabolite in compartment may be in a single `mic_id` column
+        let met_comp = table.value(row, ColumnKind::MetComp);
+        let (metabolite, compartment) = match met_comp.and_then(|mc| mc.rsplit_once('_')) {
+            Some((met, comp)) => (Some(met), Some(comp)),
+            None => (
+                table.value(row, ColumnKind::Metabolite),
+                table.value(row, ColumnKind::Compartment),
+            ),
+        };
+        let init = Init {
+            enzyme: table.value(row, ColumnKind::Enzyme).map(String::from),
+            reaction: table.value(row, ColumnKind::Reaction).map(String::from),
+            metabolite: metabolite.map(String::from),
+            compartment: compartment.map(String::from),
+            experiment: table.value(row, ColumnKind::Experiment).map(String::from),
+            value: table
+                .number(row, "value")
+                .or_else(|| table.number(row, "init")),
+        };
+        for (rule, message) in check_init(parameter, fields, &init, known, symbols) {
+            if rule != Rule::UnknownId {
+                diagnostics.push((span, rule, message));
+            }
+        }
+    }
+    diagnostics
+        .into_iter()
+        .map(|(span, rule, message)| Diagnostic {
+            range: span_to_range(&table.text, span),
+            severity: Some(rule.default_severity()),
+            code: Some(rule.diagnostic_code()),
+            message,
+            ..Default::default()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::KineticModelState;
+
+    #[test]
+    fn inits_are_checked_against_the_model() {
+        let kinetic_state = KineticModelState::from_path(
+            std::env::current_dir()
+                .unwrap()
+                .join("tests/mock/ecoli_kinetic_model.toml"),
+        );
+        let known = KnownIds {
+            enzymes: HashSet::from(["E1".to_string()]),
+            reactions: HashSet::from(["PGI".to_string(), "TPI".to_string()]),
+            metabolites: HashSet::from(["g6p".to_string(), "dhap".to_string()]),
+            compartments: HashSet::from(["c".to_string()]),
+            experiments: HashSet::from(["A".to_string()]),
+            ..Default::default()
+        };
+        let text = r#"kcat = [
+  {enzyme = "E1", reaction = "PGI", value = 100.0},
+  {enzyme = "E1", reaction = "TPI", value = 100.0},
+]
+km = [
+  {enzyme = "E1", metabolite = "dhap", compartment = "c", value = 1.0},
+  {enzyme = "E1", metabolite = "g6p", compartment = "c"},
+]
+conc_enzyme = [
+  {enzyme = "E1", experiment = "B", value = 0.1},
+]
+"#;
+        let diagnostics = inits_diagnostics(text, kinetic_state.borrow_symbols(), &known);
+        let messages: Vec<(u32, &str)> = diagnostics
+            .iter()
+            .map(|diag| (diag.range.start.line, diag.message.as_str()))
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                (9, "Unknown experiment B."),
+                (2, "Enzyme E1 does not catalyze reaction TPI."),
+                (
+                    5,
+                    "km init of E1 for dhap_c is unused: none of E1's reactions involve dhap_c."
+                ),
+                (6, "Missing value of the km init."),
+            ]
+        );
+    }
+
+    #[test]
+    fn csv_inits_are_checked_against_the_model() {
+        let kinetic_state = KineticModelState::from_path(
+            std::env::current_dir()
+                .unwrap()
+                .join("tests/mock/ecoli_kinetic_model.toml"),
+        );
+        let known = KnownIds {
+            enzymes: HashSet::from(["E1".to_string()]),
+            reactions: HashSet::from(["PGI".to_string(), "TPI".to_string()]),
+            metabolites: HashSet::from(["dhap".to_string()]),
+            compartments: HashSet::from(["c".to_string()]),
+            ..Default::default()
+        };
+        let table = CsvTable::parse(
+            "parameter,enzyme_id,reaction_id,mic_id,experiment_id,value
+kcat,E1,PGI,,,100
+kcat,E1,TPI,,,100
+km,E1,,dhap_c,,1
+kcat,E9,PGI,,,100
+kcat,E1,PGI,,,
+psy,,,,A,1
+"
+            .to_string(),
+        );
+        let diagnostics = csv_inits_diagnostics(&table, kinetic_state.borrow_symbols(), &known);
+        let messages: Vec<(u32, &str)> = diagnostics
+            .iter()
+            .map(|diag| (diag.range.start.line, diag.message.as_str()))
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                (2, "Enzyme E1 does not catalyze reaction TPI."),
+                (
+                    3,
+                    "km init of E1 for dhap_c is unused: none of E1's reactions involve dhap_c."
+                ),
+                (5, "Missing value of the kcat init."),
+                (6, "Maud has no parameter psy."),
+            ]
+        );
+    }
+}
@@ -1,11 +1,34 @@
+mod catalysis;
 mod config;
+mod config_schema;
+mod csv_data;
+mod database;
+mod duplicates;
 mod experiments;
+mod export;
+mod formatting;
+mod hover;
+mod identifiers;
+mod inits;
+mod logging;
 mod looping;
 mod maud_data;
 mod metabolic;
+mod network;
+mod percentiles;
+mod plausibility;
 mod priors;
+mod reorganize;
+mod rules;
+mod sbml;
+mod settings;
 mod state;
 mod symbol_parser;
+mod symbols;
+mod workers;
 
 pub use config::Config;
-pub use looping::main_loop;
+pub use export::{export_project, GraphFormat};
+pub use logging::Session;
+pub use looping::{main_loop, COMMANDS};
+pub use sbml::{export_sbml_project, import_sbml_file};
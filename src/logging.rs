@@ -0,0 +1,410 @@
+//! Levelled logs of the server and timing of its passes.
+//!
+//! Records of the [`log`] macros are written to stderr and to the log file of
+//! the `log` initialization option. Those of the server are also sent to the
+//! client as `window/logMessage`. Every client has its own [`Session`], so that
+//! a server shared over TCP does not mix the logs of its clients.
+//! [`span`]s time parsing, indexing and diagnostics, and are also sent as
+//! `$/logTrace` while the client asks for it with `$/setTrace`.
+use std::cell::RefCell;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crossbeam_channel::Sender;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use lsp_server::{Message, Notification};
+use lsp_types::notification::{LogMessage, LogTrace, Notification as _};
+use lsp_types::{LogMessageParams, LogTraceParams, MessageType, TraceValue};
+use serde_json::Value;
+
+/// Settings of the `log` initialization option.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogOptions {
+    /// Most verbose level that is logged.
+    pub level: LevelFilter,
+    /// File the logs are appended to, relative to the project.
+    pub file: Option<PathBuf>,
+}
+
+impl Default for LogOptions {
+    fn default() -> Self {
+        LogOptions {
+            level: LevelFilter::Info,
+            file: None,
+        }
+    }
+}
+
+impl LogOptions {
+    /// Read `{"level": "debug", "file": "maud-lsp.log"}`, ignoring invalid values.
+    pub fn from_json(value: &Value, root_dir: &Path) -> Self {
+        let mut options = LogOptions::default();
+        if let Some(level) = value.get("level").and_then(Value::as_str) {
+            match level.parse() {
+                Ok(level) => options.level = level,
+                Err(_) => log::warn!("ignoring unknown log level {level}"),
+            }
+        }
+        options.file = value
+            .get("file")
+            .and_then(Value::as_str)
+            .map(|file| root_dir.join(file));
+        options
+    }
+}
+
+/// Where the records of a client session go besides stderr.
+struct Sinks {
+    level: LevelFilter,
+    file: Option<File>,
+    client: Option<Sender<Message>>,
+    trace: TraceValue,
+    /// Records logged before the session is connected, with whether they are
+    /// of the server, so that the warnings about the initialization reach the
+    /// client and the log file too.
+    pending: Option<Vec<(Level, bool, String)>>,
+}
+
+/// Logs of a client session, kept apart from those of the other clients of
+/// the same process.
+///
+/// Records are routed to the session entered by the thread that logs them;
+/// those of no session only go to stderr.
+#[derive(Clone)]
+pub struct Session {
+    sinks: Arc<Mutex<Sinks>>,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Session {
+            sinks: Arc::new(Mutex::new(Sinks {
+                level: LevelFilter::Info,
+                file: None,
+                client: None,
+                trace: TraceValue::Off,
+                pending: Some(Vec::new()),
+            })),
+        }
+    }
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<Session>> = const { RefCell::new(None) };
+}
+
+/// Level of the records of no session, set by [`init`].
+static BASE_LEVEL: Mutex<LevelFilter> = Mutex::new(LevelFilter::Info);
+
+struct Logger;
+
+static LOGGER: Logger = Logger;
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = format!("[{} {}] {}", record.level(), record.target(), record.args());
+        // lsp-server logs the messages it writes, forwarding them would loop
+        let ours = record.target().starts_with("maud_lsp");
+        let Some(session) = Session::current() else {
+            if record.level() <= *BASE_LEVEL.lock().unwrap() {
+                eprintln!("{line}");
+            }
+            return;
+        };
+        let client = {
+            let mut sinks = session.sinks.lock().unwrap();
+            if record.level() > sinks.level {
+                return;
+            }
+            eprintln!("{line}");
+            if let Some(pending) = sinks.pending.as_mut() {
+                pending.push((record.level(), ours, line));
+                return;
+            }
+            if let Some(file) = sinks.file.as_mut() {
+                let _ = writeln!(file, "{} {line}", timestamp());
+            }
+            sinks.client.clone()
+        };
+        if ours {
+            log_message(client, record.level(), line);
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(session) = Session::current() {
+            if let Some(file) = session.sinks.lock().unwrap().file.as_mut() {
+                let _ = file.flush();
+            }
+        }
+    }
+}
+
+/// Seconds since the epoch, with milliseconds.
+fn timestamp() -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    format!("{}.{:03}", now.as_secs(), now.subsec_millis())
+}
+
+/// Notify the client, if it is connected.
+///
+/// Never called with the sinks locked: the stdio connection waits for its
+/// writer thread, which logs the messages it writes.
+fn send(client: Option<Sender<Message>>, method: &str, params: impl serde::Serialize) {
+    if let Some(client) = client {
+        // the client may have exited
+        let _ = client.send(Message::Notification(Notification::new(
+            method.to_string(),
+            params,
+        )));
+    }
+}
+
+/// Send a record of the server as `window/logMessage`.
+fn log_message(client: Option<Sender<Message>>, level: Level, message: String) {
+    let typ = match level {
+        Level::Error => MessageType::ERROR,
+        Level::Warn => MessageType::WARNING,
+        Level::Info => MessageType::INFO,
+        Level::Debug | Level::Trace => MessageType::LOG,
+    };
+    send(
+        client,
+        LogMessage::METHOD,
+        LogMessageParams { typ, message },
+    );
+}
+
+/// Log to stderr at `level`, outside of the client sessions.
+pub fn init(level: LevelFilter) {
+    // the logger is already set by a previous server of the process
+    let _ = log::set_logger(&LOGGER);
+    *BASE_LEVEL.lock().unwrap() = level;
+    log::set_max_level(level.max(log::max_level()));
+}
+
+/// Leaves the session when dropped, entering the previous one again.
+#[must_use = "the session is left when it is dropped"]
+pub struct Entered {
+    previous: Option<Session>,
+}
+
+impl Drop for Entered {
+    fn drop(&mut self) {
+        CURRENT.with(|current| *current.borrow_mut() = self.previous.take());
+    }
+}
+
+/// Stops logging to the client when dropped, so that its connection can close.
+#[must_use = "the client is disconnected when it is dropped"]
+pub struct Connected {
+    session: Session,
+}
+
+impl Drop for Connected {
+    fn drop(&mut self) {
+        let mut sinks = self.session.sinks.lock().unwrap();
+        sinks.client = None;
+        sinks.file = None;
+    }
+}
+
+impl Session {
+    /// Session entered by the current thread.
+    fn current() -> Option<Session> {
+        CURRENT.with(|current| current.borrow().clone())
+    }
+
+    /// Route the records of the current thread to this session.
+    pub fn enter(&self) -> Entered {
+        let previous = CURRENT.with(|current| current.borrow_mut().replace(self.clone()));
+        Entered { previous }
+    }
+
+    /// Also log to the client and to the log file of `options`, starting with
+    /// the records logged since the session was created.
+    pub fn connect(
+        &self,
+        client: Sender<Message>,
+        options: &LogOptions,
+        trace: TraceValue,
+    ) -> Connected {
+        // the server may run without the binary, which sets the logger
+        let base_level = *BASE_LEVEL.lock().unwrap();
+        init(base_level);
+        // the other sessions keep their level
+        log::set_max_level(options.level.max(log::max_level()));
+        let mut file_error = None;
+        let file = options.file.as_ref().and_then(|path| {
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|e| file_error = Some(format!("{}: {e}", path.display())))
+                .ok()
+        });
+        let pending = {
+            let mut sinks = self.sinks.lock().unwrap();
+            sinks.level = options.level;
+            sinks.file = file;
+            sinks.client = Some(client.clone());
+            sinks.trace = trace;
+            let pending = sinks.pending.take().unwrap_or_default();
+            if let Some(file) = sinks.file.as_mut() {
+                for (level, _, line) in pending.iter() {
+                    if *level <= options.level {
+                        let _ = writeln!(file, "{} {line}", timestamp());
+                    }
+                }
+            }
+            pending
+        };
+        for (level, ours, line) in pending {
+            if ours && level <= options.level {
+                log_message(Some(client.clone()), level, line);
+            }
+        }
+        if let Some(e) = file_error {
+            let _entered = self.enter();
+            log::warn!("unable to open the log file {e}");
+        }
+        Connected {
+            session: self.clone(),
+        }
+    }
+
+    /// Follow the `$/setTrace` of the client.
+    pub fn set_trace(&self, trace: TraceValue) {
+        self.sinks.lock().unwrap().trace = trace;
+    }
+}
+
+/// Times a pass of the server until it is dropped.
+#[must_use = "the span ends when it is dropped"]
+pub struct Span {
+    name: String,
+    start: Instant,
+}
+
+/// Start timing a pass, like parsing the kinetic model.
+pub fn span(name: impl Into<String>) -> Span {
+    Span {
+        name: name.into(),
+        start: Instant::now(),
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        let elapsed = self.start.elapsed();
+        log::debug!(target: "maud_lsp::timing", "{} took {elapsed:?}", self.name);
+        let Some(session) = Session::current() else {
+            return;
+        };
+        let (trace, client) = {
+            let sinks = session.sinks.lock().unwrap();
+            (sinks.trace, sinks.client.clone())
+        };
+        let verbose = match trace {
+            TraceValue::Off => return,
+            TraceValue::Messages => None,
+            TraceValue::Verbose => Some(format!(
+                "on thread {}",
+                std::thread::current().name().unwrap_or("unnamed")
+            )),
+        };
+        send(
+            client,
+            LogTrace::METHOD,
+            LogTraceParams {
+                message: format!("{} took {elapsed:?}", self.name),
+                verbose,
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn log_options_are_read_relative_to_the_project() {
+        let root = Path::new("/project");
+        let options = LogOptions::from_json(
+            &serde_json::json!({"level": "DEBUG", "file": "maud-lsp.log"}),
+            root,
+        );
+        assert_eq!(options.level, LevelFilter::Debug);
+        assert_eq!(options.file, Some(root.join("maud-lsp.log")));
+        let options = LogOptions::from_json(&serde_json::json!({"level": "loud"}), root);
+        assert_eq!(options, LogOptions::default());
+    }
+
+    fn notifications<T: serde::de::DeserializeOwned>(
+        receiver: &crossbeam_channel::Receiver<Message>,
+        method: &str,
+    ) -> Vec<T> {
+        receiver
+            .try_iter()
+            .filter_map(|msg| match msg {
+                Message::Notification(not) if not.method == method => {
+                    serde_json::from_value(not.params).ok()
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn spans_are_traced_to_the_client() {
+        let (client, receiver) = crossbeam_channel::unbounded();
+        let session = Session::default();
+        let _entered = session.enter();
+        let _connected = session.connect(client, &LogOptions::default(), TraceValue::Verbose);
+        drop(span("test pass"));
+        session.set_trace(TraceValue::Off);
+        drop(span("untraced pass"));
+        let traces: Vec<LogTraceParams> = notifications(&receiver, LogTrace::METHOD);
+        assert_eq!(traces.len(), 1);
+        assert!(traces[0].message.starts_with("test pass took"));
+        assert!(traces[0]
+            .verbose
+            .as_deref()
+            .is_some_and(|v| v.starts_with("on thread")));
+    }
+
+    #[test]
+    fn warnings_before_connecting_reach_the_client() {
+        init(LevelFilter::Info);
+        let (client, receiver) = crossbeam_channel::unbounded();
+        let session = Session::default();
+        let entered = session.enter();
+        let options = LogOptions::from_json(&serde_json::json!({"level": "loud"}), Path::new("."));
+        drop(entered);
+        // the other session does not get them
+        let (other_client, other_receiver) = crossbeam_channel::unbounded();
+        let other = Session::default();
+        let _other_connected = other.connect(other_client, &options, TraceValue::Off);
+        let _connected = session.connect(client, &options, TraceValue::Off);
+        let messages: Vec<LogMessageParams> = notifications(&receiver, LogMessage::METHOD);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].typ, MessageType::WARNING);
+        assert!(messages[0]
+            .message
+            .ends_with("ignoring unknown log level loud"));
+        assert!(other_receiver.try_iter().next().is_none());
+    }
+}
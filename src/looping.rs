@@ -1,277 +1,752 @@
 use std::error::Error;
-use std::fs::{read_to_string, File};
-use std::io;
-use std::io::BufRead;
-use std::path::Path;
+use std::fs::read_to_string;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crossbeam_channel::Sender;
 
 use lsp_types::{
-    request::{GotoDefinition, HoverRequest},
-    GotoDefinitionResponse, Hover, HoverContents, LanguageString, Location, MarkedString, Position,
-    PublishDiagnosticsParams, Range, TextDocumentIdentifier, Url,
+    notification::{
+        Cancel, DidChangeConfiguration, DidChangeTextDocument, DidCloseTextDocument,
+        DidOpenTextDocument, DidSaveTextDocument, SetTrace,
+    },
+    request::{
+        CallHierarchyIncomingCalls, CallHierarchyOutgoingCalls, CallHierarchyPrepare,
+        CodeActionRequest, Completion, DocumentDiagnosticRequest, DocumentLinkRequest,
+        ExecuteCommand, Formatting, GotoDefinition, HoverRequest, RangeFormatting, Request as _,
+        WorkspaceConfiguration, WorkspaceDiagnosticRefresh, WorkspaceDiagnosticRequest,
+    },
+    CodeActionResponse, CompletionResponse, ConfigurationItem, ConfigurationParams, Diagnostic,
+    DocumentDiagnosticReport, DocumentDiagnosticReportKind, DocumentDiagnosticReportResult,
+    DocumentLink, FullDocumentDiagnosticReport, GotoDefinitionResponse, Hover, HoverContents,
+    Location, MarkupContent, MarkupKind, NumberOrString, Position, PublishDiagnosticsParams, Range,
+    RelatedFullDocumentDiagnosticReport, RelatedUnchangedDocumentDiagnosticReport,
+    TextDocumentIdentifier, TraceValue, UnchangedDocumentDiagnosticReport, Url,
+    WorkspaceDiagnosticReport, WorkspaceDiagnosticReportResult, WorkspaceDocumentDiagnosticReport,
+    WorkspaceFullDocumentDiagnosticReport, WorkspaceUnchangedDocumentDiagnosticReport,
 };
 
+use serde_json::Value;
+
 use lsp_server::{
-    Connection, ExtractError, Message, Notification, Request, RequestId, Response, ResponseError,
+    Connection, ErrorCode, ExtractError, Message, Notification, Request, RequestId, Response,
 };
 
 use crate::config::Config;
-use crate::maud_data::MaudConfig;
-use crate::state::{
-    gather_diagnostics, gather_diagnostics_priors, ExperimentsState, KineticModelState, PriorsState,
-};
-use crate::symbol_parser::extract_symbol;
+use crate::export::{export_network, GraphFormat};
+use crate::hover::hover_markdown;
+use crate::logging::{self, Session};
+use crate::network::{incoming_calls, outgoing_calls, prepare_call_hierarchy};
+use crate::sbml::{export_sbml, import_sbml, import_sbml_file, model_id};
+use crate::state::{same_file, ProjectState};
+use crate::workers::{InFlight, WorkerPool};
 
 pub fn main_loop(
     connection: Connection,
     config: Config,
+    session: Session,
 ) -> Result<(), Box<dyn Error + Sync + Send>> {
-    let maud_config: MaudConfig =
-        toml::from_str(&read_to_string(config.root_dir.join("config.toml"))?)?;
-    let mut kinetic_state =
-        KineticModelState::from_path(config.root_dir.join(&maud_config.kinetic_model_file));
-    let mut priors_state = PriorsState::from_path(config.root_dir.join(&maud_config.priors_file));
-    let mut experiments_state =
-        ExperimentsState::from_path(config.root_dir.join(&maud_config.experiments_file));
-    let mut experiment_ids = experiments_state.experiments();
-    let kinetic_model_uri =
-        Url::from_file_path(config.root_dir.join(&maud_config.kinetic_model_file)).unwrap();
-    let priors_uri = Url::from_file_path(config.root_dir.join(&maud_config.priors_file)).unwrap();
+    let _entered = session.enter();
+    let _logging = session.connect(connection.sender.clone(), &config.log, config.trace);
+    let mut project = Arc::new(ProjectState::load(
+        &config.root_dir,
+        &config.settings.config_file,
+    )?);
+    // the client settings take precedence over the project ones
+    Arc::make_mut(&mut project).set_settings(&config.settings);
+    let workers = WorkerPool::new(WorkerPool::default_size());
+    let in_flight = InFlight::default();
+    let generation = Arc::new(AtomicU64::new(0));
+    let mut refresh_requests = 0;
+    let mut configuration_requests = 0;
+    let mut pending_configuration = None;
+    if config.workspace_configuration() {
+        pending_configuration = Some(request_configuration(
+            &connection,
+            &mut configuration_requests,
+        )?);
+    }
     for msg in &connection.receiver {
-        match match_message(msg, &connection, &kinetic_state, &kinetic_model_uri) {
-            Ok(Some(OkMsg::OkNotFound { id, msg })) => {
-                let no_idea_resp = Response {
-                    id,
-                    result: None,
-                    error: Some(ResponseError {
-                        code: 500,
-                        message: msg,
-                        data: None,
-                    }),
-                };
-                connection.sender.send(Message::Response(no_idea_resp))?;
+        if let Message::Request(req) = msg {
+            if connection.handle_shutdown(&req)? {
+                return Ok(());
+            }
+            dispatch(
+                &workers,
+                &in_flight,
+                &generation,
+                &session,
+                connection.sender.clone(),
+                Arc::clone(&project),
+                req,
+            );
+            continue;
+        }
+        let changed = match match_message(msg) {
+            Ok(Some(OkMsg::Cancel(id))) => {
+                in_flight.cancel(&id);
+                false
+            }
+            Ok(Some(OkMsg::SetTrace(trace))) => {
+                session.set_trace(trace);
+                false
+            }
+            Ok(Some(OkMsg::DidOpen(uri, text))) => {
+                modify(&mut project, &generation, |project| {
+                    project.open_documents.insert(uri.clone(), text.clone());
+                    project.update_document(&uri, text);
+                });
+                // diagnostics are shown on open even if nothing changed
+                true
+            }
+            Ok(Some(OkMsg::DidChange(uri, text))) => modify(&mut project, &generation, |project| {
+                project.open_documents.insert(uri.clone(), text.clone());
+                project.update_document(&uri, text)
+            }),
+            Ok(Some(OkMsg::DidClose(uri))) => {
+                modify(&mut project, &generation, |project| {
+                    // unsaved changes are discarded
+                    project.open_documents.remove(&uri);
+                    read_to_string(uri.path())
+                        .map(|text| project.update_document(&uri, text))
+                        .unwrap_or(false)
+                })
             }
-            Ok(Some(OkMsg::Shutdown)) => return Ok(()),
             Ok(Some(OkMsg::DidSave(text_document))) => {
-                if Some(text_document.uri.path())
-                    == config
-                        .root_dir
-                        .join(&maud_config.kinetic_model_file)
-                        .to_str()
-                {
-                    // the model file may have been saved in an invalid state
-                    // only update the data model if it is valid
-                    if let Ok(state) = KineticModelState::try_from_path(
-                        config.root_dir.join(&maud_config.kinetic_model_file),
-                    ) {
-                        kinetic_state = state;
-                        let diagnostics =
-                            gather_diagnostics(&kinetic_state, &priors_state, &experiment_ids);
-                        let diagnostics = PublishDiagnosticsParams {
-                            uri: kinetic_model_uri.clone(),
-                            diagnostics,
-                            version: None,
-                        };
-                        connection.sender.send(Message::Notification(Notification {
-                            method: "textDocument/publishDiagnostics".to_string(),
-                            params: serde_json::to_value(diagnostics).unwrap(),
-                        }))?;
-                    }
-                } else if Some(text_document.uri.path())
-                    == config.root_dir.join(&maud_config.priors_file).to_str()
-                {
-                    // the priors file may have been saved in an invalid state
-                    // only update the data model if it is valid
-                    if let Ok(state) =
-                        PriorsState::try_from_path(config.root_dir.join(&maud_config.priors_file))
-                    {
-                        priors_state = state;
-
-                        let diagnostics = gather_diagnostics_priors(&priors_state);
-                        let diagnostics = PublishDiagnosticsParams {
-                            uri: priors_uri.clone(),
-                            diagnostics,
-                            version: None,
-                        };
-                        connection.sender.send(Message::Notification(Notification {
-                            method: "textDocument/publishDiagnostics".to_string(),
-                            params: serde_json::to_value(diagnostics).unwrap(),
-                        }))?;
-                        let diagnostics =
-                            gather_diagnostics(&kinetic_state, &priors_state, &experiment_ids);
-                        let diagnostics = PublishDiagnosticsParams {
-                            uri: kinetic_model_uri.clone(),
-                            diagnostics,
-                            version: None,
-                        };
-                        connection.sender.send(Message::Notification(Notification {
-                            method: "textDocument/publishDiagnostics".to_string(),
-                            params: serde_json::to_value(diagnostics).unwrap(),
-                        }))?;
-                    }
-                } else if Some(text_document.uri.path())
-                    == config.root_dir.join(&maud_config.experiments_file).to_str()
-                {
-                    if let Ok(state) = ExperimentsState::try_from_path(
-                        config.root_dir.join(&maud_config.experiments_file),
-                    ) {
-                        experiments_state = state;
-                        experiment_ids = experiments_state.experiments();
-                    }
+                let uri = text_document.uri;
+                // the file may have been saved in an invalid state
+                // only update the data model if it is valid
+                read_to_string(uri.path())
+                    .map(|text| {
+                        modify(&mut project, &generation, |project| {
+                            project.update_document(&uri, text)
+                        })
+                    })
+                    .unwrap_or(false)
+            }
+            Ok(Some(OkMsg::Response(resp))) if Some(&resp.id) == pending_configuration.as_ref() => {
+                pending_configuration = None;
+                // one item is returned for the only requested section
+                let section = resp.result.as_ref().and_then(|result| result.get(0));
+                apply_settings(&mut project, &generation, &config, section);
+                true
+            }
+            Ok(Some(OkMsg::DidChangeConfiguration(settings))) => {
+                if config.workspace_configuration() {
+                    pending_configuration = Some(request_configuration(
+                        &connection,
+                        &mut configuration_requests,
+                    )?);
+                    false
+                } else {
+                    // the settings are pushed, possibly under the section name
+                    let section = settings.get("maud-lsp").unwrap_or(&settings);
+                    apply_settings(&mut project, &generation, &config, Some(section));
+                    true
                 }
             }
             Err(e) => panic!("{:?}", e),
-            _ => (),
+            _ => false,
+        };
+        if !changed {
+            continue;
+        }
+        if config.pull_diagnostics() {
+            // the client will pull the diagnostics when it needs them
+            if config.diagnostic_refresh() {
+                refresh_requests += 1;
+                connection.sender.send(Message::Request(Request::new(
+                    RequestId::from(format!("maud-refresh-{refresh_requests}")),
+                    WorkspaceDiagnosticRefresh::METHOD.to_string(),
+                    (),
+                )))?;
+            }
+        } else {
+            for uri in project.uris() {
+                if let Some(diagnostics) = project.document_diagnostics(uri) {
+                    publish_diagnostics(&connection, uri, diagnostics)?;
+                }
+            }
         }
     }
     Ok(())
 }
 
+/// Apply the `maud-lsp` section of the client settings over its initialization
+/// options, reloading the project if they point to another config file.
+fn apply_settings(
+    project: &mut Arc<ProjectState>,
+    generation: &AtomicU64,
+    config: &Config,
+    section: Option<&Value>,
+) {
+    let mut settings = config.settings.clone();
+    if let Some(section) = section {
+        settings.update(section);
+    }
+    if !project.has_config_file(&settings.config_file) {
+        match ProjectState::load(&config.root_dir, &settings.config_file) {
+            Ok(reloaded) => modify(project, generation, |project| project.reload(reloaded)),
+            // the previous project is kept until the config is fixed
+            Err(e) => log::error!("unable to load the project: {e}"),
+        }
+    }
+    modify(project, generation, |project| {
+        project.set_settings(&settings)
+    });
+}
+
+/// Apply a change to the project. Requests in progress keep their snapshot,
+/// which is only outdated if the change bumped the revision of an input.
+fn modify<T>(
+    project: &mut Arc<ProjectState>,
+    generation: &AtomicU64,
+    change: impl FnOnce(&mut ProjectState) -> T,
+) -> T {
+    let project = Arc::make_mut(project);
+    let revision = project.revision();
+    let result = change(project);
+    if project.revision() != revision {
+        generation.fetch_add(1, Ordering::SeqCst);
+    }
+    result
+}
+
+/// Requests whose results point to positions of the documents, which are
+/// wrong if the documents changed while they were computed.
+const POSITIONAL: [&str; 6] = [
+    GotoDefinition::METHOD,
+    HoverRequest::METHOD,
+    Completion::METHOD,
+    CodeActionRequest::METHOD,
+    Formatting::METHOD,
+    RangeFormatting::METHOD,
+];
+
+/// Handle a request on a worker, responding with an error if the client
+/// cancelled it or if the documents changed in the meantime.
+fn dispatch(
+    workers: &WorkerPool,
+    in_flight: &InFlight,
+    generation: &Arc<AtomicU64>,
+    session: &Session,
+    sender: Sender<Message>,
+    project: Arc<ProjectState>,
+    req: Request,
+) {
+    let id = req.id.clone();
+    in_flight.start(id.clone());
+    let in_flight = in_flight.clone();
+    let generation = Arc::clone(generation);
+    let started = generation.load(Ordering::SeqCst);
+    let session = session.clone();
+    workers.execute(move || {
+        let _entered = session.enter();
+        let positional = POSITIONAL.contains(&req.method.as_str());
+        let _span = logging::span(format!("request {} {}", req.method, id));
+        // a request cancelled while it waited for a worker is not computed
+        let resp = (!in_flight.is_cancelled(&id)).then(|| respond(req, &project));
+        let cancelled = in_flight.finish(&id);
+        let resp = match resp {
+            Some(_) if positional && generation.load(Ordering::SeqCst) != started => {
+                Response::new_err(
+                    id,
+                    ErrorCode::ContentModified as i32,
+                    "The documents changed while handling the request".to_string(),
+                )
+            }
+            Some(resp) if !cancelled => resp,
+            _ => Response::new_err(
+                id,
+                ErrorCode::RequestCanceled as i32,
+                "The request was cancelled".to_string(),
+            ),
+        };
+        // the client may have exited
+        let _ = sender.send(Message::Response(resp));
+    });
+}
+
+/// Response to a request, or the error that prevented it.
+fn respond(req: Request, project: &ProjectState) -> Response {
+    let id = req.id.clone();
+    let (code, message) = match panic::catch_unwind(AssertUnwindSafe(|| {
+        handle_request(req, project)
+    })) {
+        Ok(Ok(resp)) => return resp,
+        Ok(Err(e)) if e.is::<ExtractError<Request>>() => (ErrorCode::InvalidParams, e.to_string()),
+        Ok(Err(e)) => (ErrorCode::InternalError, e.to_string()),
+        Err(_) => (
+            ErrorCode::InternalError,
+            "The request could not be handled".to_string(),
+        ),
+    };
+    Response::new_err(id, code as i32, message)
+}
+
+/// Ask the client for the `maud-lsp` section of its settings.
+fn request_configuration(
+    connection: &Connection,
+    requests: &mut u32,
+) -> Result<RequestId, Box<dyn Error + Sync + Send>> {
+    *requests += 1;
+    let id = RequestId::from(format!("maud-configuration-{requests}"));
+    connection.sender.send(Message::Request(Request::new(
+        id.clone(),
+        WorkspaceConfiguration::METHOD.to_string(),
+        ConfigurationParams {
+            items: vec![ConfigurationItem {
+                scope_uri: None,
+                section: Some("maud-lsp".to_string()),
+            }],
+        },
+    )))?;
+    Ok(id)
+}
+
+fn publish_diagnostics(
+    connection: &Connection,
+    uri: &Url,
+    diagnostics: Vec<Diagnostic>,
+) -> Result<(), Box<dyn Error + Sync + Send>> {
+    let diagnostics = PublishDiagnosticsParams {
+        uri: uri.clone(),
+        diagnostics,
+        version: None,
+    };
+    connection.sender.send(Message::Notification(Notification {
+        method: "textDocument/publishDiagnostics".to_string(),
+        params: serde_json::to_value(diagnostics).unwrap(),
+    }))?;
+    Ok(())
+}
+
+/// Full report of a document, or unchanged if the client already has it.
+fn document_report(
+    project: &ProjectState,
+    uri: &Url,
+    previous_result_id: Option<&str>,
+) -> DocumentDiagnosticReportKind {
+    let result_id = project.result_id(uri);
+    if previous_result_id == Some(result_id.as_str()) {
+        DocumentDiagnosticReportKind::Unchanged(UnchangedDocumentDiagnosticReport { result_id })
+    } else {
+        DocumentDiagnosticReportKind::Full(FullDocumentDiagnosticReport {
+            result_id: Some(result_id),
+            items: project.document_diagnostics(uri).unwrap_or_default(),
+        })
+    }
+}
+
 enum OkMsg {
-    /// Something was not found but the server does not have to crash.
-    OkNotFound { id: RequestId, msg: String },
-    /// Received shutdown, return Ok
-    Shutdown,
-    /// Kinetic Model was saved
+    /// The client cancelled one of its requests
+    Cancel(RequestId),
+    /// A document was opened, with its contents
+    DidOpen(Url, String),
+    /// The contents of a document changed
+    DidChange(Url, String),
+    /// A document was closed
+    DidClose(Url),
+    /// A document was saved
     DidSave(TextDocumentIdentifier),
+    /// The client settings changed
+    DidChangeConfiguration(Value),
+    /// The client changed how much it wants to be traced
+    SetTrace(TraceValue),
+    /// Response to a request of the server
+    Response(Response),
 }
 
-fn match_message(
-    msg: Message,
-    connection: &Connection,
-    kinetic_state: &KineticModelState,
-    kinetic_model_uri: &Url,
-) -> Result<Option<OkMsg>, Box<dyn Error + Sync + Send>> {
-    match msg {
-        Message::Request(req) => {
-            if connection.handle_shutdown(&req)? {
-                return Ok(Some(OkMsg::Shutdown));
+/// Response to a request of the client, computed on a snapshot of the project.
+fn handle_request(
+    req: Request,
+    project: &ProjectState,
+) -> Result<Response, Box<dyn Error + Sync + Send>> {
+    let kinetic_state = &project.kinetic_state;
+    let passed_req = match cast::<GotoDefinition>(req) {
+        Ok((id, params)) => {
+            let position = params.text_document_position_params.position;
+            let uri = &params.text_document_position_params.text_document.uri;
+            if same_file(uri, &project.config_uri) {
+                // the path under the cursor goes to the start of its file
+                let result = project
+                    .config_links()
+                    .into_iter()
+                    .find(|(range, _)| range.start <= position && position <= range.end)
+                    .map(|(_, target)| {
+                        GotoDefinitionResponse::Scalar(Location {
+                            uri: target,
+                            range: Range::default(),
+                        })
+                    });
+                let resp = Response {
+                    id,
+                    result: Some(serde_json::to_value(&result)?),
+                    error: None,
+                };
+                return Ok(resp);
+            }
+            // TOD check the uri is a valid absolute path
+            let symbol = match project.symbol_at(
+                &params.text_document_position_params.text_document.uri,
+                position,
+            )? {
+                Some(s) => s,
+                None => return Ok(Response::new_ok(id, None::<GotoDefinitionResponse>)),
+            };
+            let lines = kinetic_state.find_symbol_lines(&symbol);
+            if lines.is_empty() {
+                return Ok(Response::new_ok(id, None::<GotoDefinitionResponse>));
             }
-            let passed_req = match cast::<GotoDefinition>(req) {
-                Ok((id, params)) => {
-                    let (row, col) = (
-                        params.text_document_position_params.position.line,
-                        params.text_document_position_params.position.character,
-                    );
-                    // TOD check the uri is a valid absolute path
-                    let line_str = read_line(
-                        params
-                            .text_document_position_params
-                            .text_document
-                            .uri
-                            .path(),
-                        row,
-                    )?;
-                    let symbol = match extract_symbol(&line_str, col as usize) {
-                        Some(s) => s,
-                        None => {
-                            return Ok(Some(OkMsg::OkNotFound {
-                                id,
-                                msg: format!("Valid symbol at {},{} Not Found", line_str, col),
-                            }))
+            // the way of finding the symbol on the cursor changes between
+            // maud CSVs and kinetic models.
+            // TODO(carrascomj): we are only handling the kinetic model
+            let mut locations: Vec<Location> = lines
+                .into_iter()
+                .map(|line| {
+                    let position = Position::new(line.saturating_sub(1) as u32, 0);
+                    Location {
+                        uri: project.kinetic_model_uri.clone(),
+                        range: Range::new(position, position),
+                    }
+                })
+                .collect();
+            // an id of more than one kind of entity goes to all of them
+            let result = Some(if locations.len() == 1 {
+                GotoDefinitionResponse::Scalar(locations.remove(0))
+            } else {
+                GotoDefinitionResponse::Array(locations)
+            });
+            let result = serde_json::to_value(&result).unwrap();
+            let resp = Response {
+                id,
+                result: Some(result),
+                error: None,
+            };
+            return Ok(resp);
+        }
+        Err(err @ ExtractError::JsonError { .. }) => return Err(err.into()),
+        Err(ExtractError::MethodMismatch(req)) => req,
+    };
+    let passed_req = match cast::<Completion>(passed_req) {
+        Ok((id, params)) => {
+            let items = project.completions(
+                &params.text_document_position.text_document.uri,
+                params.text_document_position.position,
+            );
+            let result = CompletionResponse::Array(items);
+            let resp = Response {
+                id,
+                result: Some(serde_json::to_value(&result)?),
+                error: None,
+            };
+            return Ok(resp);
+        }
+        Err(err @ ExtractError::JsonError { .. }) => return Err(err.into()),
+        Err(ExtractError::MethodMismatch(req)) => req,
+    };
+    let passed_req = match cast::<CallHierarchyPrepare>(passed_req) {
+        Ok((id, params)) => {
+            let symbol = project.symbol_at(
+                &params.text_document_position_params.text_document.uri,
+                params.text_document_position_params.position,
+            )?;
+            // null if the symbol is not in the network
+            let result = symbol.and_then(|symbol| prepare_call_hierarchy(project, &symbol));
+            let resp = Response {
+                id,
+                result: Some(serde_json::to_value(&result)?),
+                error: None,
+            };
+            return Ok(resp);
+        }
+        Err(err @ ExtractError::JsonError { .. }) => return Err(err.into()),
+        Err(ExtractError::MethodMismatch(req)) => req,
+    };
+    let passed_req = match cast::<CallHierarchyIncomingCalls>(passed_req) {
+        Ok((id, params)) => {
+            let result = incoming_calls(project, &params.item);
+            let resp = Response {
+                id,
+                result: Some(serde_json::to_value(&result)?),
+                error: None,
+            };
+            return Ok(resp);
+        }
+        Err(err @ ExtractError::JsonError { .. }) => return Err(err.into()),
+        Err(ExtractError::MethodMismatch(req)) => req,
+    };
+    let passed_req = match cast::<CallHierarchyOutgoingCalls>(passed_req) {
+        Ok((id, params)) => {
+            let result = outgoing_calls(project, &params.item);
+            let resp = Response {
+                id,
+                result: Some(serde_json::to_value(&result)?),
+                error: None,
+            };
+            return Ok(resp);
+        }
+        Err(err @ ExtractError::JsonError { .. }) => return Err(err.into()),
+        Err(ExtractError::MethodMismatch(req)) => req,
+    };
+    let passed_req = match cast::<ExecuteCommand>(passed_req) {
+        Ok((id, params)) => {
+            let result = match run_command(project, &params.command, &params.arguments) {
+                Ok(result) => result,
+                Err(msg) => return Ok(Response::new_err(id, ErrorCode::InvalidParams as i32, msg)),
+            };
+            let resp = Response {
+                id,
+                result: Some(result),
+                error: None,
+            };
+            return Ok(resp);
+        }
+        Err(err @ ExtractError::JsonError { .. }) => return Err(err.into()),
+        Err(ExtractError::MethodMismatch(req)) => req,
+    };
+    let passed_req = match cast::<Formatting>(passed_req) {
+        Ok((id, params)) => {
+            let edits = project.formatting(&params.text_document.uri, None)?;
+            let resp = Response {
+                id,
+                result: Some(serde_json::to_value(&edits)?),
+                error: None,
+            };
+            return Ok(resp);
+        }
+        Err(err @ ExtractError::JsonError { .. }) => return Err(err.into()),
+        Err(ExtractError::MethodMismatch(req)) => req,
+    };
+    let passed_req = match cast::<RangeFormatting>(passed_req) {
+        Ok((id, params)) => {
+            let edits = project.formatting(&params.text_document.uri, Some(params.range))?;
+            let resp = Response {
+                id,
+                result: Some(serde_json::to_value(&edits)?),
+                error: None,
+            };
+            return Ok(resp);
+        }
+        Err(err @ ExtractError::JsonError { .. }) => return Err(err.into()),
+        Err(ExtractError::MethodMismatch(req)) => req,
+    };
+    let passed_req = match cast::<CodeActionRequest>(passed_req) {
+        Ok((id, params)) => {
+            let result: CodeActionResponse =
+                project.code_actions(&params.text_document.uri, &params.range);
+            let resp = Response {
+                id,
+                result: Some(serde_json::to_value(&result)?),
+                error: None,
+            };
+            return Ok(resp);
+        }
+        Err(err @ ExtractError::JsonError { .. }) => return Err(err.into()),
+        Err(ExtractError::MethodMismatch(req)) => req,
+    };
+    let passed_req = match cast::<DocumentDiagnosticRequest>(passed_req) {
+        Ok((id, params)) => {
+            let report = match document_report(
+                project,
+                &params.text_document.uri,
+                params.previous_result_id.as_deref(),
+            ) {
+                DocumentDiagnosticReportKind::Full(report) => {
+                    DocumentDiagnosticReport::Full(RelatedFullDocumentDiagnosticReport {
+                        related_documents: None,
+                        full_document_diagnostic_report: report,
+                    })
+                }
+                DocumentDiagnosticReportKind::Unchanged(report) => {
+                    DocumentDiagnosticReport::Unchanged(RelatedUnchangedDocumentDiagnosticReport {
+                        related_documents: None,
+                        unchanged_document_diagnostic_report: report,
+                    })
+                }
+            };
+            let result = DocumentDiagnosticReportResult::Report(report);
+            let resp = Response {
+                id,
+                result: Some(serde_json::to_value(&result)?),
+                error: None,
+            };
+            return Ok(resp);
+        }
+        Err(err @ ExtractError::JsonError { .. }) => return Err(err.into()),
+        Err(ExtractError::MethodMismatch(req)) => req,
+    };
+    let passed_req = match cast::<WorkspaceDiagnosticRequest>(passed_req) {
+        Ok((id, params)) => {
+            let items = project
+                .uris()
+                .into_iter()
+                .map(|uri| {
+                    let previous = params
+                        .previous_result_ids
+                        .iter()
+                        .find(|prev| same_file(&prev.uri, uri))
+                        .map(|prev| prev.value.as_str());
+                    match document_report(project, uri, previous) {
+                        DocumentDiagnosticReportKind::Full(report) => {
+                            WorkspaceDocumentDiagnosticReport::Full(
+                                WorkspaceFullDocumentDiagnosticReport {
+                                    uri: uri.clone(),
+                                    version: None,
+                                    full_document_diagnostic_report: report,
+                                },
+                            )
                         }
-                    };
-                    let result_line = match kinetic_state.find_symbol_line(symbol) {
-                        Some(line) => line - 1,
-                        None => {
-                            return Ok(Some(OkMsg::OkNotFound {
-                                id,
-                                msg: format!("Symbol {} Not Found in Kinetic Model", symbol),
-                            }))
+                        DocumentDiagnosticReportKind::Unchanged(report) => {
+                            WorkspaceDocumentDiagnosticReport::Unchanged(
+                                WorkspaceUnchangedDocumentDiagnosticReport {
+                                    uri: uri.clone(),
+                                    version: None,
+                                    unchanged_document_diagnostic_report: report,
+                                },
+                            )
                         }
-                    };
-                    // the way of finding the symbol on the cursor changes between
-                    // maud CSVs and kinetic models.
-                    // TODO(carrascomj): we are only handling the kinetic model
-                    let result = Some(GotoDefinitionResponse::Scalar(Location {
-                        uri: kinetic_model_uri.clone(),
-                        range: Range {
-                            start: Position {
-                                line: result_line as u32,
-                                character: 0,
-                            },
-                            end: Position {
-                                line: result_line as u32,
-                                character: 0,
-                            },
-                        },
-                    }));
-                    let result = serde_json::to_value(&result).unwrap();
-                    let resp = Response {
-                        id,
-                        result: Some(result),
-                        error: None,
-                    };
-                    connection.sender.send(Message::Response(resp))?;
-                    return Ok(None);
+                    }
+                })
+                .collect();
+            let result =
+                WorkspaceDiagnosticReportResult::Report(WorkspaceDiagnosticReport { items });
+            let resp = Response {
+                id,
+                result: Some(serde_json::to_value(&result)?),
+                error: None,
+            };
+            return Ok(resp);
+        }
+        Err(err @ ExtractError::JsonError { .. }) => return Err(err.into()),
+        Err(ExtractError::MethodMismatch(req)) => req,
+    };
+    let passed_req = match cast::<DocumentLinkRequest>(passed_req) {
+        Ok((id, params)) => {
+            let links: Vec<DocumentLink> =
+                if same_file(&params.text_document.uri, &project.config_uri) {
+                    project
+                        .config_links()
+                        .into_iter()
+                        .map(|(range, target)| DocumentLink {
+                            range,
+                            target: Some(target),
+                            tooltip: None,
+                            data: None,
+                        })
+                        .collect()
+                } else {
+                    Vec::new()
+                };
+            let resp = Response {
+                id,
+                result: Some(serde_json::to_value(&links)?),
+                error: None,
+            };
+            return Ok(resp);
+        }
+        Err(err @ ExtractError::JsonError { .. }) => return Err(err.into()),
+        Err(ExtractError::MethodMismatch(req)) => req,
+    };
+    let passed_req = match cast::<HoverRequest>(passed_req) {
+        Ok((id, params)) => {
+            // TODO: check the uri is a valid absolute path
+            let uri = &params.text_document_position_params.text_document.uri;
+            let position = params.text_document_position_params.position;
+            let markdown = if same_file(uri, &project.config_uri) {
+                project.config_hover(position)
+            } else {
+                project
+                    .symbol_at(uri, position)?
+                    .and_then(|symbol| hover_markdown(project, &symbol))
+            };
+            // null if there is nothing to show
+            let result = markdown.map(|value| Hover {
+                contents: HoverContents::Markup(MarkupContent {
+                    kind: MarkupKind::Markdown,
+                    value,
+                }),
+                range: None,
+            });
+            let result = serde_json::to_value(&result)?;
+            let resp = Response {
+                id,
+                result: Some(result),
+                error: None,
+            };
+            return Ok(resp);
+        }
+        Err(err @ ExtractError::JsonError { .. }) => return Err(err.into()),
+        Err(ExtractError::MethodMismatch(req)) => req,
+    };
+    // this should not really happen since we declare our capabilties beforehand
+    Ok(Response::new_err(
+        passed_req.id,
+        ErrorCode::MethodNotFound as i32,
+        format!("Method {} not implemented", passed_req.method),
+    ))
+}
+
+fn match_message(msg: Message) -> Result<Option<OkMsg>, Box<dyn Error + Sync + Send>> {
+    match msg {
+        // requests are handled by the workers
+        Message::Request(_) => Ok(None),
+        Message::Response(resp) => Ok(Some(OkMsg::Response(resp))),
+        Message::Notification(not) => {
+            let not = match cast_not::<DidOpenTextDocument>(not) {
+                Ok(params) => {
+                    return Ok(Some(OkMsg::DidOpen(
+                        params.text_document.uri,
+                        params.text_document.text,
+                    )))
                 }
                 Err(err @ ExtractError::JsonError { .. }) => panic!("{:?}", err),
-                Err(ExtractError::MethodMismatch(req)) => req,
+                Err(ExtractError::MethodMismatch(not)) => not,
             };
-            let req_id = match cast::<HoverRequest>(passed_req) {
-                Ok((id, params)) => {
-                    let (row, col) = (
-                        params.text_document_position_params.position.line,
-                        params.text_document_position_params.position.character,
-                    );
-                    // TODO: check the uri is a valid absolute path
-                    let line_str = read_line(
-                        params
-                            .text_document_position_params
-                            .text_document
-                            .uri
-                            .path(),
-                        row,
-                    )?;
-                    let symbol = match extract_symbol(&line_str, col as usize) {
-                        Some(s) => s,
-                        None => {
-                            return Ok(Some(OkMsg::OkNotFound {
-                                id,
-                                msg: format!("Valid symbol at {},{} Not Found", line_str, col),
-                            }))
-                        }
-                    };
-                    let result_symbol = kinetic_state.find_rendered_symbol(symbol);
-                    // the way of finding the symbol on the cursor changes between
-                    // maud CSVs and kinetic models.
-                    let result = Some(Hover {
-                        contents: HoverContents::Scalar(MarkedString::LanguageString(
-                            LanguageString {
-                                language: "toml".to_string(),
-                                value: result_symbol,
-                            },
-                        )),
-                        range: None,
-                    });
-                    // TODO: handle this unwrap
-                    let result = serde_json::to_value(&result)?;
-                    let resp = Response {
-                        id,
-                        result: Some(result),
-                        error: None,
-                    };
-                    connection.sender.send(Message::Response(resp))?;
-                    return Ok(None);
+            let not = match cast_not::<DidChangeTextDocument>(not) {
+                Ok(mut params) => {
+                    // the whole document is synchronized
+                    return Ok(params
+                        .content_changes
+                        .pop()
+                        .map(|change| OkMsg::DidChange(params.text_document.uri, change.text)));
                 }
                 Err(err @ ExtractError::JsonError { .. }) => panic!("{:?}", err),
-                Err(ExtractError::MethodMismatch(req)) => req.id,
+                Err(ExtractError::MethodMismatch(not)) => not,
             };
-            // this should not really happen since we declare our capabilties beforehand
-            Ok(Some(OkMsg::OkNotFound {
-                id: req_id,
-                msg: "Method not implemented".to_string(),
-            }))
-        }
-        Message::Response(resp) => {
-            eprintln!("got response: {:?}", resp);
-            Ok(None)
-        }
-        Message::Notification(not) => {
-            match cast_not::<lsp_types::notification::DidSaveTextDocument>(not) {
+            let not = match cast_not::<DidCloseTextDocument>(not) {
+                Ok(params) => return Ok(Some(OkMsg::DidClose(params.text_document.uri))),
+                Err(err @ ExtractError::JsonError { .. }) => panic!("{:?}", err),
+                Err(ExtractError::MethodMismatch(not)) => not,
+            };
+            let not = match cast_not::<DidSaveTextDocument>(not) {
                 Ok(params) => return Ok(Some(OkMsg::DidSave(params.text_document))),
-                _ => {
-                    eprintln!("got unhandled notification");
+                Err(err @ ExtractError::JsonError { .. }) => panic!("{:?}", err),
+                Err(ExtractError::MethodMismatch(not)) => not,
+            };
+            let not = match cast_not::<Cancel>(not) {
+                Ok(params) => {
+                    let id = match params.id {
+                        NumberOrString::Number(id) => RequestId::from(id),
+                        NumberOrString::String(id) => RequestId::from(id),
+                    };
+                    return Ok(Some(OkMsg::Cancel(id)));
                 }
+                Err(err @ ExtractError::JsonError { .. }) => panic!("{:?}", err),
+                Err(ExtractError::MethodMismatch(not)) => not,
+            };
+            let not = match cast_not::<DidChangeConfiguration>(not) {
+                Ok(params) => return Ok(Some(OkMsg::DidChangeConfiguration(params.settings))),
+                Err(err @ ExtractError::JsonError { .. }) => panic!("{:?}", err),
+                Err(ExtractError::MethodMismatch(not)) => not,
+            };
+            match cast_not::<SetTrace>(not) {
+                Ok(params) => return Ok(Some(OkMsg::SetTrace(params.value))),
+                Err(ExtractError::MethodMismatch(not)) => {
+                    log::debug!("got unhandled notification {}", not.method);
+                }
+                Err(err) => panic!("{:?}", err),
             }
 
             Ok(None)
@@ -279,6 +754,97 @@ fn match_message(
     }
 }
 
+/// Command that renders the metabolite-reaction network.
+pub const EXPORT_NETWORK: &str = "maud-lsp.exportNetwork";
+/// Command that translates the kinetic model to SBML.
+pub const EXPORT_SBML: &str = "maud-lsp.exportSbml";
+/// Command that translates an SBML file to a kinetic model and stub priors.
+pub const IMPORT_SBML: &str = "maud-lsp.importSbml";
+/// Commands of `workspace/executeCommand`.
+pub const COMMANDS: [&str; 3] = [EXPORT_NETWORK, EXPORT_SBML, IMPORT_SBML];
+
+fn run_command(
+    project: &ProjectState,
+    command: &str,
+    arguments: &[Value],
+) -> Result<Value, String> {
+    match command {
+        EXPORT_NETWORK => run_export_network(project, arguments),
+        EXPORT_SBML => run_export_sbml(project, arguments),
+        IMPORT_SBML => run_import_sbml(project, arguments),
+        _ => Err(format!("Unknown command {command}")),
+    }
+}
+
+/// Write `contents` to `path` (relative to the project) and return the path,
+/// or return the contents if there is no path.
+fn write_or_return(
+    project: &ProjectState,
+    contents: String,
+    path: Option<&str>,
+) -> Result<Value, String> {
+    match path {
+        Some(path) => {
+            let path = project.root_dir().join(path);
+            std::fs::write(&path, contents)
+                .map_err(|e| format!("Could not write {}: {e}", path.display()))?;
+            Ok(Value::String(path.display().to_string()))
+        }
+        None => Ok(Value::String(contents)),
+    }
+}
+
+/// Arguments are the format (`dot` by default) and an optional output path.
+///
+/// The rendered graph is returned if there is no path, otherwise it is written
+/// to the file (relative to the project) and its path is returned.
+fn run_export_network(project: &ProjectState, arguments: &[Value]) -> Result<Value, String> {
+    let format: GraphFormat = arguments
+        .first()
+        .and_then(Value::as_str)
+        .unwrap_or("dot")
+        .parse()?;
+    let graph = export_network(project.kinetic_state.borrow_kinetic_model(), format);
+    write_or_return(project, graph, arguments.get(1).and_then(Value::as_str))
+}
+
+/// The only argument is an optional output path, like for the network.
+fn run_export_sbml(project: &ProjectState, arguments: &[Value]) -> Result<Value, String> {
+    let sbml = export_sbml(
+        project.kinetic_state.borrow_kinetic_model(),
+        &model_id(&project.name),
+    );
+    write_or_return(project, sbml, arguments.first().and_then(Value::as_str))
+}
+
+/// Arguments are the SBML file and an optional output directory (relative to the project).
+///
+/// Without directory, the kinetic model and the priors are returned as
+/// `{"kinetic_model": ..., "priors": ...}`.
+fn run_import_sbml(project: &ProjectState, arguments: &[Value]) -> Result<Value, String> {
+    let sbml_file = arguments
+        .first()
+        .and_then(Value::as_str)
+        .map(|path| project.root_dir().join(path))
+        .ok_or("Missing SBML file argument")?;
+    match arguments.get(1).and_then(Value::as_str) {
+        Some(dir) => {
+            let dir = project.root_dir().join(dir);
+            import_sbml_file(&sbml_file, &dir).map_err(|e| e.to_string())?;
+            Ok(Value::String(dir.display().to_string()))
+        }
+        None => {
+            let xml = read_to_string(&sbml_file)
+                .map_err(|e| format!("Could not read {}: {e}", sbml_file.display()))?;
+            let import = import_sbml(&xml)?;
+            Ok(serde_json::json!({
+                "kinetic_model": import.kinetic_model,
+                "priors": import.priors,
+            }))
+        }
+    }
+}
+
 fn cast<R>(req: Request) -> Result<(RequestId, R::Params), ExtractError<Request>>
 where
     R: lsp_types::request::Request,
@@ -294,12 +860,3 @@ where
 {
     not.extract(N::METHOD)
 }
-
-fn read_line<P: AsRef<Path>>(file_path: P, line: u32) -> io::Result<String> {
-    let input = File::open(file_path)?;
-    let buffered = io::BufReader::new(input);
-    buffered
-        .lines()
-        .nth(line as usize)
-        .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))?
-}
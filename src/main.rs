@@ -1,43 +1,244 @@
 //! Language Server for [Maud](https://github.com/biosustain/Maud).
 //!
 //! Supports Hover and GotoDefinition around the kinetic model.
+//! The `check` subcommand runs the diagnostics without an editor.
 use std::error::Error;
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::thread;
+
+use argh::FromArgs;
 
 use lsp_types::OneOf;
 use lsp_types::{
+    CallHierarchyServerCapability, CodeActionProviderCapability, CompletionOptions,
+    DiagnosticOptions, DiagnosticServerCapabilities, DocumentLinkOptions, ExecuteCommandOptions,
     HoverProviderCapability, InitializeParams, SaveOptions, ServerCapabilities,
     TextDocumentSyncCapability, TextDocumentSyncKind, TextDocumentSyncOptions,
-    TextDocumentSyncSaveOptions,
+    TextDocumentSyncSaveOptions, WorkDoneProgressOptions,
 };
 
 use lsp_server::Connection;
+mod catalysis;
+mod check;
 pub mod config;
+mod config_schema;
+mod csv_data;
+mod database;
+mod duplicates;
 mod experiments;
+mod export;
+mod formatting;
+mod hover;
+mod identifiers;
+mod inits;
+mod logging;
 mod looping;
 mod maud_data;
 mod metabolic;
+mod network;
+mod percentiles;
+mod plausibility;
 mod priors;
+mod reorganize;
+mod rules;
+mod sbml;
+mod settings;
 mod state;
 mod symbol_parser;
+mod symbols;
+mod transport;
+mod workers;
 
+use check::{check, Format};
 use config::Config;
+use export::GraphFormat;
 pub use looping::main_loop;
 
+#[derive(FromArgs)]
+/// Language server for Maud. Speaks LSP over stdio unless a subcommand is given.
+struct Args {
+    /// serve LSP over TCP at an address like 127.0.0.1:9257, to any number of clients
+    #[argh(option)]
+    listen: Option<String>,
+    /// speak LSP over TCP with a client listening at an address
+    #[argh(option)]
+    connect: Option<String>,
+    #[argh(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand)]
+enum Command {
+    Check(CheckArgs),
+    Network(NetworkArgs),
+    ImportSbml(ImportSbmlArgs),
+    ExportSbml(ExportSbmlArgs),
+}
+
+#[derive(FromArgs)]
+/// Run all diagnostics over a Maud input directory and exit non-zero on errors.
+#[argh(subcommand, name = "check")]
+struct CheckArgs {
+    /// maud input directory, containing the config.toml
+    #[argh(positional, default = "PathBuf::from(\".\")")]
+    dir: PathBuf,
+    /// output format: human (default), json or sarif
+    #[argh(option, default = "Format::Human")]
+    format: Format,
+}
+
+#[derive(FromArgs)]
+/// Export the metabolite-reaction network of the kinetic model.
+#[argh(subcommand, name = "network")]
+struct NetworkArgs {
+    /// maud input directory, containing the config.toml
+    #[argh(positional, default = "PathBuf::from(\".\")")]
+    dir: PathBuf,
+    /// graph format: dot (default), graphml or cytoscape
+    #[argh(option, default = "GraphFormat::Dot")]
+    format: GraphFormat,
+    /// output file, the graph is printed to stdout if not given
+    #[argh(option, short = 'o')]
+    output: Option<PathBuf>,
+}
+
+#[derive(FromArgs)]
+/// Translate an SBML model to a Maud kinetic model and stub priors.
+#[argh(subcommand, name = "import-sbml")]
+struct ImportSbmlArgs {
+    /// SBML file
+    #[argh(positional)]
+    sbml: PathBuf,
+    /// directory where kinetic_model.toml and priors.toml are written
+    #[argh(option, short = 'o', default = "PathBuf::from(\".\")")]
+    output_dir: PathBuf,
+}
+
+#[derive(FromArgs)]
+/// Translate the kinetic model of a Maud input directory to SBML.
+#[argh(subcommand, name = "export-sbml")]
+struct ExportSbmlArgs {
+    /// maud input directory, containing the config.toml
+    #[argh(positional, default = "PathBuf::from(\".\")")]
+    dir: PathBuf,
+    /// output file, the SBML is printed to stdout if not given
+    #[argh(option, short = 'o')]
+    output: Option<PathBuf>,
+}
+
 fn main() -> Result<(), Box<dyn Error + Sync + Send>> {
+    let args: Args = argh::from_env();
+    // the subcommands also warn about ignored settings
+    logging::init(log::LevelFilter::Info);
+    match args.command {
+        Some(Command::Check(args)) => {
+            let code = match check(&args.dir, &args.format) {
+                Ok(false) => 0,
+                Ok(true) => 1,
+                Err(e) => {
+                    eprintln!("error: {e}");
+                    2
+                }
+            };
+            std::process::exit(code);
+        }
+        Some(Command::Network(args)) => {
+            exit_on_error(export::export_project(
+                &args.dir,
+                args.format,
+                args.output.as_deref(),
+            ));
+            return Ok(());
+        }
+        Some(Command::ImportSbml(args)) => {
+            exit_on_error(sbml::import_sbml_file(&args.sbml, &args.output_dir));
+            return Ok(());
+        }
+        Some(Command::ExportSbml(args)) => {
+            exit_on_error(sbml::export_sbml_project(&args.dir, args.output.as_deref()));
+            return Ok(());
+        }
+        None => (),
+    }
+
     // Note that  we must have our logging only write out to stderr.
-    eprintln!("starting generic LSP server");
+    log::info!("starting generic LSP server");
 
-    // Create the transport. Includes the stdio (stdin and stdout) versions but this could
-    // also be implemented to use sockets or HTTP.
-    let (connection, io_threads) = Connection::stdio();
+    // Create the transport: stdio (stdin and stdout) by default, or a TCP socket.
+    match (args.listen, args.connect) {
+        (Some(_), Some(_)) => {
+            eprintln!("error: --listen and --connect cannot be used together");
+            std::process::exit(2);
+        }
+        (Some(addr), None) => {
+            // a long-running server, shared by the clients that connect to it
+            let listener = TcpListener::bind(&addr)?;
+            log::info!("waiting for clients at {addr}");
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        log::error!("unable to accept a client: {e}");
+                        continue;
+                    }
+                };
+                let peer = stream
+                    .peer_addr()
+                    .map_or_else(|_| "unknown".to_string(), |peer| peer.to_string());
+                // each client is served on its own thread, in its own session
+                let spawned = thread::Builder::new()
+                    .name(format!("maud-lsp-client-{peer}"))
+                    .spawn(move || {
+                        let result = serve_stream(stream);
+                        if let Err(e) = result {
+                            log::error!("the session of {peer} ended with an error: {e}");
+                        }
+                    });
+                if let Err(e) = spawned {
+                    log::error!("unable to serve a client: {e}");
+                }
+            }
+        }
+        (None, Some(addr)) => {
+            let (connection, io_threads) = Connection::connect(&addr)?;
+            serve(connection)?;
+            io_threads.join()?;
+        }
+        (None, None) => {
+            let (connection, io_threads) = Connection::stdio();
+            serve(connection)?;
+            io_threads.join()?;
+        }
+    }
 
-    // Hover, GotoDefinition and handle saves
+    // Shut down gracefully.
+    log::info!("shutting down server");
+    Ok(())
+}
+
+/// Serve a client of `--listen` and wait for its connection to close.
+fn serve_stream(stream: TcpStream) -> Result<(), Box<dyn Error + Sync + Send>> {
+    let (connection, io_threads) = transport::socket(stream)?;
+    serve(connection)?;
+    io_threads.join()?;
+    Ok(())
+}
+
+/// Initialize a client session and handle its messages until it exits.
+///
+/// The connection is closed when it returns, its threads can then be joined.
+fn serve(connection: Connection) -> Result<(), Box<dyn Error + Sync + Send>> {
+    // Hover, GotoDefinition, CodeActions, Completion, Diagnostics, CallHierarchy and handle
+    // document changes
     let server_capabilities = serde_json::to_value(&ServerCapabilities {
         definition_provider: Some(OneOf::Left(true)),
+        code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
         text_document_sync: Some(TextDocumentSyncCapability::Options(
             TextDocumentSyncOptions {
-                open_close: Some(false),
-                change: Some(TextDocumentSyncKind::NONE),
+                open_close: Some(true),
+                change: Some(TextDocumentSyncKind::FULL),
                 will_save: Some(false),
                 will_save_wait_until: Some(false),
                 save: Some(TextDocumentSyncSaveOptions::SaveOptions(SaveOptions {
@@ -46,18 +247,48 @@ fn main() -> Result<(), Box<dyn Error + Sync + Send>> {
             },
         )),
         hover_provider: Some(HoverProviderCapability::Simple(true)),
+        document_link_provider: Some(DocumentLinkOptions {
+            resolve_provider: Some(false),
+            work_done_progress_options: WorkDoneProgressOptions::default(),
+        }),
+        document_formatting_provider: Some(OneOf::Left(true)),
+        document_range_formatting_provider: Some(OneOf::Left(true)),
+        // metabolite-reaction network
+        call_hierarchy_provider: Some(CallHierarchyServerCapability::Simple(true)),
+        execute_command_provider: Some(ExecuteCommandOptions {
+            commands: looping::COMMANDS.map(String::from).to_vec(),
+            ..Default::default()
+        }),
+        completion_provider: Some(CompletionOptions {
+            // cells of the CSV tables
+            trigger_characters: Some(vec![",".to_string()]),
+            ..Default::default()
+        }),
+        diagnostic_provider: Some(DiagnosticServerCapabilities::Options(DiagnosticOptions {
+            identifier: Some("maud".to_string()),
+            // the kinetic model is checked against the priors and the experiments
+            inter_file_dependencies: true,
+            workspace_diagnostics: true,
+            ..Default::default()
+        })),
         ..Default::default()
     })
     .unwrap();
+    // the warnings about the initialization options are sent once connected
+    let session = logging::Session::default();
+    let entered = session.enter();
     let initialization_params = connection.initialize(server_capabilities)?;
     let params: InitializeParams = serde_json::from_value(initialization_params).unwrap();
     let config = Config::from_init(params)?;
-    // Run the server and wait for the two threads to end (typically by trigger LSP Exit event).
-    main_loop(connection, config)?;
-
-    io_threads.join()?;
+    drop(entered);
+    // Run the server until the client exits (typically by trigger LSP Exit event).
+    main_loop(connection, config, session)
+}
 
-    // Shut down gracefully.
-    eprintln!("shutting down server");
-    Ok(())
+/// Report the error of a subcommand and exit with code 2.
+fn exit_on_error(result: Result<(), Box<dyn Error + Sync + Send>>) {
+    if let Err(e) = result {
+        eprintln!("error: {e}");
+        std::process::exit(2);
+    }
 }
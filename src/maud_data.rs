@@ -1,4 +1,5 @@
 /// Maud data model of the kinetic_model file.
+use crate::plausibility::Bounds;
 use serde::Deserialize;
 use std::collections::HashMap;
 use toml::Spanned;
@@ -57,19 +58,41 @@ pub struct Enzyme<'a> {
 #[derive(Deserialize)]
 pub struct EnzymeReaction<'a> {
     /// identifier, cannot contain underscores
-    pub enzyme_id: &'a str,
-    pub reaction_id: &'a str,
+    #[serde(borrow)]
+    pub enzyme_id: Spanned<&'a str>,
+    #[serde(borrow)]
+    pub reaction_id: Spanned<&'a str>,
 }
 
-/// Table from enzyme to reaction
+/// Compartment where metabolites are.
+#[derive(Deserialize)]
+pub struct Compartment<'a> {
+    /// identifier, cannot contain underscores
+    pub id: Spanned<&'a str>,
+    pub name: &'a str,
+    pub volume: f64,
+}
+
+/// Table from metabolite to compartment
 #[derive(Deserialize)]
 pub struct MetaboliteInCompartment<'a> {
     /// identifier, cannot contain underscores
-    pub metabolite_id: &'a str,
+    #[serde(borrow)]
+    pub metabolite_id: Spanned<&'a str>,
     pub compartment_id: &'a str,
     pub balanced: bool,
 }
 
+/// Allosteric regulation of an enzyme by a metabolite in compartment.
+#[derive(Deserialize)]
+pub struct Allostery<'a> {
+    pub enzyme_id: &'a str,
+    pub metabolite_id: &'a str,
+    pub compartment_id: &'a str,
+    /// either "activation" or "inhibition"
+    pub modification_type: &'a str,
+}
+
 /// Contains the metabolic model structural data.
 #[derive(Deserialize)]
 pub(crate) struct KineticModel<'a> {
@@ -79,17 +102,45 @@ pub(crate) struct KineticModel<'a> {
     pub reactions: Vec<Reaction<'a>>,
     #[serde(rename = "enzyme", borrow)]
     pub enzymes: Vec<Enzyme<'a>>,
+    #[serde(rename = "compartment", borrow, default)]
+    pub compartments: Vec<Compartment<'a>>,
     #[serde(borrow)]
     pub enzyme_reaction: Vec<EnzymeReaction<'a>>,
     #[serde(borrow, default)]
     pub metabolite_in_compartment: Vec<MetaboliteInCompartment<'a>>,
+    #[serde(borrow, default)]
+    pub allostery: Vec<Allostery<'a>>,
 }
 
 #[derive(Deserialize)]
 pub struct MaudConfig {
+    #[serde(default)]
+    pub name: String,
     pub kinetic_model_file: String,
     pub priors_file: String,
     pub experiments_file: String,
+    /// Initial values of the sampler.
+    #[serde(default)]
+    pub user_inits_file: Option<String>,
+    /// Settings of other tools, like `[tool.maud-lsp]`.
+    #[serde(default)]
+    pub tool: ToolConfig,
+}
+
+#[derive(Deserialize, Default)]
+pub struct ToolConfig {
+    #[serde(rename = "maud-lsp", default)]
+    pub maud_lsp: LspToolConfig,
+}
+
+#[derive(Deserialize, Default)]
+pub struct LspToolConfig {
+    /// Severity of each diagnostic rule by code, "off" to turn it off.
+    #[serde(default)]
+    pub diagnostics: HashMap<String, String>,
+    /// Plausibility bounds of the priors by kind, e.g. `km`.
+    #[serde(default)]
+    pub plausibility: HashMap<String, Bounds>,
 }
 
 #[cfg(test)]
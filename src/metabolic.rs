@@ -1,4 +1,6 @@
-use crate::maud_data::{Enzyme, EnzymeReaction, Metabolite, Reaction, ReactionMechanism};
+use crate::maud_data::{
+    Compartment, Enzyme, Metabolite, MetaboliteInCompartment, Reaction, ReactionMechanism,
+};
 use core::fmt::Display;
 use std::collections::HashMap;
 use toml::Spanned;
@@ -42,7 +44,7 @@ impl Display for ReactionMechanism {
     }
 }
 
-fn to_reaction_str(st: &HashMap<&str, f32>) -> String {
+pub(crate) fn to_reaction_str(st: &HashMap<&str, f32>) -> String {
     let reactants = st
         .iter()
         .filter(|(_k, &v)| v < 1.0e-6)
@@ -94,19 +96,6 @@ pub struct MetabolicEnzyme<'a> {
     pub reactions: Vec<&'a str>,
 }
 
-impl<'a> MetabolicEnzyme<'a> {
-    pub fn from_enzyme(enzyme: &'a Enzyme<'a>, enzyme_reactions: &[EnzymeReaction<'a>]) -> Self {
-        Self {
-            enzyme,
-            reactions: enzyme_reactions
-                .iter()
-                .filter(|enz_reac| &enz_reac.enzyme_id == enzyme.id.get_ref())
-                .map(|enz_reac| enz_reac.reaction_id)
-                .collect(),
-        }
-    }
-}
-
 impl Display for MetabolicEnzyme<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -126,10 +115,41 @@ impl Metabolic for MetabolicEnzyme<'_> {
     }
 }
 
+/// Metabolite in a compartment, as referenced in stoichiometries (`g6p_c`).
+pub struct MetabolicMetComp<'a> {
+    pub metabolite: &'a Metabolite<'a>,
+    pub compartment_id: &'a str,
+    pub compartment: Option<&'a Compartment<'a>>,
+    /// Row of `metabolite_in_compartment`, if the model has it.
+    pub row: Option<&'a MetaboliteInCompartment<'a>>,
+}
+
+impl Display for MetabolicMetComp<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "metabolite = {}\ncompartment = {}\nbalanced = {}",
+            self.metabolite.id.get_ref(),
+            self.compartment_id,
+            self.row
+                .map_or("unknown".to_string(), |row| row.balanced.to_string()),
+        )
+    }
+}
+
+impl Metabolic for MetabolicMetComp<'_> {
+    /// The `metabolite_in_compartment` row or, if there is none, the metabolite.
+    fn span(&self) -> &Spanned<&str> {
+        self.row
+            .map_or(&self.metabolite.id, |row| &row.metabolite_id)
+    }
+}
+
 pub enum Entity<'a> {
     Met(&'a Metabolite<'a>),
     Reac(&'a Reaction<'a>),
     Enz(MetabolicEnzyme<'a>),
+    MetComp(MetabolicMetComp<'a>),
 }
 
 impl Display for Entity<'_> {
@@ -138,6 +158,19 @@ impl Display for Entity<'_> {
             Entity::Met(m) => m.fmt(f),
             Entity::Reac(r) => r.fmt(f),
             Entity::Enz(e) => e.fmt(f),
+            Entity::MetComp(mc) => mc.fmt(f),
+        }
+    }
+}
+
+impl Entity<'_> {
+    /// Kind of entity, as written in the kinetic model.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Entity::Met(_) => "metabolite",
+            Entity::Reac(_) => "reaction",
+            Entity::Enz(_) => "enzyme",
+            Entity::MetComp(_) => "metabolite in compartment",
         }
     }
 }
@@ -148,6 +181,7 @@ impl Metabolic for Entity<'_> {
             Entity::Met(m) => m.span(),
             Entity::Reac(r) => r.span(),
             Entity::Enz(e) => e.span(),
+            Entity::MetComp(mc) => mc.span(),
         }
     }
 }
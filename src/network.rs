@@ -0,0 +1,241 @@
+//! Bipartite graph of metabolites and reactions, built from the stoichiometries.
+//!
+//! The graph is exposed through the LSP call hierarchy: a reaction "calls" the
+//! metabolites it produces and is "called" by the metabolites it consumes, so that
+//! the incoming calls of a metabolite are its producing reactions and its outgoing
+//! calls are its consuming reactions.
+use lsp_types::{
+    CallHierarchyIncomingCall, CallHierarchyItem, CallHierarchyOutgoingCall, Range, SymbolKind,
+};
+
+use crate::maud_data::Reaction;
+use crate::metabolic::{Entity, Metabolic};
+use crate::state::{span_to_range, ProjectState};
+use crate::symbol_parser::entry_span;
+
+/// Edge between a reaction and a metabolite in compartment of its stoichiometry.
+pub struct Edge<'a> {
+    pub reaction: &'a Reaction<'a>,
+    pub met_comp: &'a str,
+    /// Negative for substrates, positive for products.
+    pub coefficient: f32,
+}
+
+/// All the edges of the network, reaction by reaction.
+pub fn edges<'a>(reactions: &'a [Reaction<'a>]) -> impl Iterator<Item = Edge<'a>> {
+    reactions.iter().flat_map(|reaction| {
+        reaction
+            .stoichiometry
+            .iter()
+            .map(move |(met_comp, coefficient)| Edge {
+                reaction,
+                met_comp,
+                coefficient: *coefficient,
+            })
+    })
+}
+
+/// Whether a stoichiometry key (`g6p_c`) refers to `id`, either a metabolite
+/// in any compartment or that exact metabolite in compartment.
+fn refers_to(met_comp: &str, id: &str) -> bool {
+    met_comp == id || met_comp.rsplit_once('_').is_some_and(|(met, _)| met == id)
+}
+
+/// Span of the stoichiometry key `met_comp` in the definition of `reaction`.
+fn key_span(text: &str, reaction: &Reaction, met_comp: &str) -> Option<(usize, usize)> {
+    let (start, end) = entry_span(text, reaction.id.span().0);
+    let block = &text[start..end];
+    block
+        .match_indices(met_comp)
+        .map(|(i, _)| (start + i, start + i + met_comp.len()))
+        .find(|&(key_start, key_end)| {
+            let before = text[..key_start].chars().next_back();
+            let after = text[key_end..].trim_start_matches('"').trim_start();
+            !before.is_some_and(|c| c.is_alphanumeric() || c == '_') && after.starts_with('=')
+        })
+}
+
+fn item(
+    project: &ProjectState,
+    entity: &Entity,
+    kind: SymbolKind,
+    detail: &str,
+) -> CallHierarchyItem {
+    let range = span_to_range(
+        project.kinetic_state.borrow_file_str(),
+        entity.span().span(),
+    );
+    CallHierarchyItem {
+        name: entity.identifier().to_string(),
+        kind,
+        tags: None,
+        detail: Some(detail.to_string()),
+        uri: project.kinetic_model_uri.clone(),
+        range,
+        selection_range: range,
+        data: None,
+    }
+}
+
+fn reaction_item(project: &ProjectState, reaction: &Reaction) -> CallHierarchyItem {
+    item(
+        project,
+        &Entity::Reac(reaction),
+        SymbolKind::FUNCTION,
+        reaction.name,
+    )
+}
+
+/// Item of a metabolite in compartment, named after the stoichiometry key.
+fn met_comp_item(project: &ProjectState, met_comp: &str) -> Option<CallHierarchyItem> {
+    let entity = project
+        .kinetic_state
+        .find_symbol(met_comp)
+        .into_iter()
+        .find(|entity| matches!(entity, Entity::MetComp(_)))?;
+    let Entity::MetComp(mc) = &entity else {
+        return None;
+    };
+    let mut item = item(project, &entity, SymbolKind::VARIABLE, mc.metabolite.name);
+    item.name = met_comp.to_string();
+    Some(item)
+}
+
+/// Items for the symbol under the cursor: metabolites (in any or in one
+/// compartment) and reactions, all of them if the id is ambiguous. Enzymes are
+/// not part of the network.
+pub fn prepare_call_hierarchy(
+    project: &ProjectState,
+    symbol: &str,
+) -> Option<Vec<CallHierarchyItem>> {
+    let items: Vec<CallHierarchyItem> = project
+        .kinetic_state
+        .find_symbol(symbol)
+        .iter()
+        .filter_map(|entity| match entity {
+            Entity::Met(met) => Some(item(project, entity, SymbolKind::VARIABLE, met.name)),
+            Entity::MetComp(_) => met_comp_item(project, symbol),
+            Entity::Reac(reac) => Some(reaction_item(project, reac)),
+            Entity::Enz(_) => None,
+        })
+        .collect();
+    (!items.is_empty()).then_some(items)
+}
+
+/// Calls between `item` and its neighbours, with the ranges of the stoichiometry keys.
+///
+/// `into_item` selects the edges pointing to the item: products for metabolites
+/// and substrates for reactions.
+fn neighbours(
+    project: &ProjectState,
+    item: &CallHierarchyItem,
+    into_item: bool,
+) -> Vec<(CallHierarchyItem, Vec<Range>)> {
+    let kinetic_model = project.kinetic_state.borrow_kinetic_model();
+    let text = project.kinetic_state.borrow_file_str();
+    let mut calls: Vec<(CallHierarchyItem, Vec<Range>)> = Vec::new();
+    for edge in edges(&kinetic_model.reactions) {
+        let produced = edge.coefficient > 0.;
+        let neighbour = if item.kind == SymbolKind::FUNCTION {
+            if edge.reaction.id.get_ref() != &item.name || produced == into_item {
+                continue;
+            }
+            match met_comp_item(project, edge.met_comp) {
+                Some(neighbour) => neighbour,
+                None => continue,
+            }
+        } else {
+            if !refers_to(edge.met_comp, &item.name) || produced != into_item {
+                continue;
+            }
+            reaction_item(project, edge.reaction)
+        };
+        let ranges = key_span(text, edge.reaction, edge.met_comp)
+            .map(|span| span_to_range(text, span))
+            .into_iter();
+        // a metabolite may appear in several compartments of the same reaction
+        match calls
+            .iter_mut()
+            .find(|(known, _)| known.name == neighbour.name)
+        {
+            Some((_, known_ranges)) => known_ranges.extend(ranges),
+            None => calls.push((neighbour, ranges.collect())),
+        }
+    }
+    calls
+}
+
+/// Producing reactions of a metabolite, or substrates of a reaction.
+pub fn incoming_calls(
+    project: &ProjectState,
+    item: &CallHierarchyItem,
+) -> Vec<CallHierarchyIncomingCall> {
+    neighbours(project, item, true)
+        .into_iter()
+        .map(|(from, from_ranges)| CallHierarchyIncomingCall { from, from_ranges })
+        .collect()
+}
+
+/// Consuming reactions of a metabolite, or products of a reaction.
+pub fn outgoing_calls(
+    project: &ProjectState,
+    item: &CallHierarchyItem,
+) -> Vec<CallHierarchyOutgoingCall> {
+    neighbours(project, item, false)
+        .into_iter()
+        .map(|(to, from_ranges)| CallHierarchyOutgoingCall { to, from_ranges })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maud_data::MaudConfig;
+
+    fn mock_project() -> ProjectState {
+        let root_dir = std::env::current_dir().unwrap().join("tests/mock");
+        let config: MaudConfig =
+            toml::from_str(&std::fs::read_to_string(root_dir.join("config.toml")).unwrap())
+                .unwrap();
+        ProjectState::try_from_config(&root_dir, &config).unwrap()
+    }
+
+    fn names<T>(calls: &[T], item: impl Fn(&T) -> &CallHierarchyItem) -> Vec<&str> {
+        let mut names: Vec<&str> = calls.iter().map(|call| item(call).name.as_str()).collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn metabolites_are_called_by_producing_reactions() {
+        let project = mock_project();
+        let item = prepare_call_hierarchy(&project, "f6p").unwrap().remove(0);
+        let incoming = incoming_calls(&project, &item);
+        assert_eq!(names(&incoming, |call| &call.from), ["PGI"]);
+        let outgoing = outgoing_calls(&project, &item);
+        assert_eq!(names(&outgoing, |call| &call.to), ["FBP", "PFK"]);
+        // the range points to the stoichiometry key
+        let fbp = outgoing.iter().find(|call| call.to.name == "FBP").unwrap();
+        assert_eq!(fbp.from_ranges[0].start.line, 38);
+    }
+
+    #[test]
+    fn reactions_call_their_products() {
+        let project = mock_project();
+        let item = prepare_call_hierarchy(&project, "PGI").unwrap().remove(0);
+        assert_eq!(item.kind, SymbolKind::FUNCTION);
+        assert_eq!(
+            names(&incoming_calls(&project, &item), |call| &call.from),
+            ["g6p_c"]
+        );
+        assert_eq!(
+            names(&outgoing_calls(&project, &item), |call| &call.to),
+            ["f6p_c"]
+        );
+    }
+
+    #[test]
+    fn enzymes_are_not_in_the_network() {
+        assert!(prepare_call_hierarchy(&mock_project(), "E1").is_none());
+    }
+}
@@ -0,0 +1,268 @@
+//! Conversion of priors between location/scale and the 1st and 99th percentiles.
+//!
+//! Maud priors are lognormal, except for drains and psi, which are normal.
+use lsp_types::{CodeActionOrCommand, Range, TextEdit, Url};
+
+use crate::formatting::{key_value, parse_row, split_comment, Row};
+use crate::priors::Z99;
+use crate::reorganize::{action, line_range, structure};
+
+/// Priors with a normal distribution, parameterised by `location`.
+const NORMAL_PRIORS: [&str; 2] = ["drain", "psi"];
+
+#[derive(Clone, Copy, PartialEq)]
+enum Parameterisation {
+    LocationScale,
+    Percentiles,
+}
+
+use Parameterisation::*;
+
+/// Both parameterisations of a prior.
+struct Conversion {
+    normal: bool,
+}
+
+impl Conversion {
+    fn of(prior: &str) -> Self {
+        Conversion {
+            normal: NORMAL_PRIORS.contains(&prior),
+        }
+    }
+
+    fn location_key(&self) -> &'static str {
+        if self.normal {
+            "location"
+        } else {
+            "exploc"
+        }
+    }
+
+    /// Keys that are read and written going to `target`.
+    fn keys(&self, target: Parameterisation) -> ([&'static str; 2], [&'static str; 2]) {
+        let location_scale = [self.location_key(), "scale"];
+        match target {
+            Percentiles => (location_scale, ["pct1", "pct99"]),
+            LocationScale => (["pct1", "pct99"], location_scale),
+        }
+    }
+
+    fn convert(&self, target: Parameterisation, a: f64, b: f64) -> Option<(f64, f64)> {
+        match (target, self.normal) {
+            (Percentiles, true) => Some((a - Z99 * b, a + Z99 * b)),
+            (Percentiles, false) => Some((a * (-Z99 * b).exp(), a * (Z99 * b).exp())),
+            (LocationScale, true) => (a < b).then(|| ((a + b) / 2., (b - a) / (2. * Z99))),
+            (LocationScale, false) => {
+                (0. < a && a < b).then(|| ((a * b).sqrt(), (b / a).ln() / (2. * Z99)))
+            }
+        }
+    }
+}
+
+/// Six significant digits, always written as a TOML float.
+pub(crate) fn format_number(x: f64) -> String {
+    let digits = 5 - x.abs().log10().floor() as i32;
+    let rounded = match (x == 0., digits >= 0) {
+        (true, _) => 0.,
+        (false, true) => (x * 10f64.powi(digits)).round() / 10f64.powi(digits),
+        (false, false) => (x / 10f64.powi(-digits)).round() * 10f64.powi(-digits),
+    };
+    let mut text = rounded.to_string();
+    if !text.contains(['.', 'e', 'i', 'N']) {
+        text.push_str(".0");
+    }
+    text
+}
+
+fn number(value: &str) -> Option<f64> {
+    value.replace('_', "").parse().ok()
+}
+
+/// Parameterisation in which a prior is fully specified, if it is only one of them.
+fn current(conversion: &Conversion, keys: &[&str]) -> Option<Parameterisation> {
+    let has = |key: &str| keys.contains(&key);
+    let location_scale = has(conversion.location_key()) && has("scale");
+    let percentiles = has("pct1") && has("pct99");
+    match (location_scale, percentiles) {
+        (true, false) => Some(LocationScale),
+        (false, true) => Some(Percentiles),
+        _ => None,
+    }
+}
+
+/// Rewrite an inline prior `{..., exploc = 1.0, scale = 0.2}` on a single line.
+fn convert_row(line: &str, conversion: &Conversion, target: Parameterisation) -> Option<String> {
+    let Some(Row::Table { pairs, comment }) = parse_row(line) else {
+        return None;
+    };
+    let keys: Vec<&str> = pairs.iter().map(|(key, _)| *key).collect();
+    if current(conversion, &keys)? == target {
+        return None;
+    }
+    let (from, to) = conversion.keys(target);
+    let value = |key: &str| {
+        pairs
+            .iter()
+            .find(|(k, _)| *k == key)
+            .and_then(|(_, v)| number(v))
+    };
+    let (a, b) = conversion.convert(target, value(from[0])?, value(from[1])?)?;
+    let mut new_pairs: Vec<String> = pairs
+        .iter()
+        .filter(|(key, _)| !from.contains(key))
+        .map(|(key, value)| format!("{key} = {value}"))
+        .collect();
+    new_pairs.push(format!("{} = {}", to[0], format_number(a)));
+    new_pairs.push(format!("{} = {}", to[1], format_number(b)));
+    let content = split_comment(line).0.trim_end();
+    let indent = &line[..line.len() - line.trim_start().len()];
+    let comma = if content.ends_with(',') { "," } else { "" };
+    let comment = comment.map_or(String::new(), |comment| format!(" {comment}"));
+    Some(format!(
+        "{indent}{{{}}}{comma}{comment}\n",
+        new_pairs.join(", ")
+    ))
+}
+
+/// Rewrite the key lines of a `[[prior]]` table in place.
+fn convert_table(
+    lines: &[&str],
+    start: usize,
+    end: usize,
+    conversion: &Conversion,
+    target: Parameterisation,
+) -> Option<Vec<TextEdit>> {
+    let pairs: Vec<(usize, &str, &str)> = (start + 1..end)
+        .filter_map(|i| {
+            let (key, value) = key_value(split_comment(lines[i]).0)?;
+            Some((i, key, value))
+        })
+        .collect();
+    let keys: Vec<&str> = pairs.iter().map(|(_, key, _)| *key).collect();
+    if current(conversion, &keys)? == target {
+        return None;
+    }
+    let (from, to) = conversion.keys(target);
+    let find = |key: &str| pairs.iter().find(|(_, k, _)| *k == key);
+    let (first, second) = (find(from[0])?, find(from[1])?);
+    let (a, b) = conversion.convert(target, number(first.2)?, number(second.2)?)?;
+    let edits = [(first, to[0], a), (second, to[1], b)]
+        .into_iter()
+        .map(|((i, _, _), key, value)| {
+            let comment = split_comment(lines[*i])
+                .1
+                .map_or(String::new(), |comment| format!(" {comment}"));
+            TextEdit {
+                range: line_range(*i, *i + 1),
+                new_text: format!("{key} = {}{comment}\n", format_number(value)),
+            }
+        })
+        .collect();
+    Some(edits)
+}
+
+/// Actions converting the priors under `range` to the other parameterisation.
+pub fn percentile_actions(text: &str, uri: &Url, range: &Range) -> Vec<CodeActionOrCommand> {
+    let lines: Vec<&str> = text.lines().collect();
+    let (arrays, tables) = structure(&lines);
+    let (first, last) = (range.start.line as usize, range.end.line as usize);
+    let mut actions = Vec::new();
+    for target in [Percentiles, LocationScale] {
+        let mut edits = Vec::new();
+        let mut priors = 0;
+        for array in arrays.iter() {
+            let conversion = Conversion::of(array.name);
+            let rows = first.max(array.start + 1)..last.saturating_add(1).min(array.end);
+            for (i, line) in lines.iter().enumerate().take(rows.end).skip(rows.start) {
+                if let Some(new_text) = convert_row(line, &conversion, target) {
+                    priors += 1;
+                    edits.push(TextEdit {
+                        range: line_range(i, i + 1),
+                        new_text,
+                    });
+                }
+            }
+        }
+        for table in tables
+            .iter()
+            .filter(|table| table.start <= last && first < table.end)
+        {
+            let conversion = Conversion::of(table.name);
+            if let Some(table_edits) =
+                convert_table(&lines, table.start, table.end, &conversion, target)
+            {
+                priors += 1;
+                edits.extend(table_edits);
+            }
+        }
+        if priors == 0 {
+            continue;
+        }
+        let to = match target {
+            Percentiles => "pct1/pct99",
+            LocationScale => "location/scale",
+        };
+        let title = match priors {
+            1 => format!("Convert prior to {to}"),
+            _ => format!("Convert {priors} priors to {to}"),
+        };
+        actions.push(action(title, uri, edits));
+    }
+    actions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsp_types::Position;
+
+    fn converted(text: &str, line: u32) -> Vec<(String, String)> {
+        let position = Position::new(line, 0);
+        percentile_actions(
+            text,
+            &Url::parse("file:///priors.toml").unwrap(),
+            &Range::new(position, position),
+        )
+        .into_iter()
+        .map(|action| {
+            let CodeActionOrCommand::CodeAction(action) = action else {
+                panic!("not an action");
+            };
+            let edits = action
+                .edit
+                .unwrap()
+                .changes
+                .unwrap()
+                .into_values()
+                .next()
+                .unwrap();
+            let new_text = edits.into_iter().map(|edit| edit.new_text).collect();
+            (action.title, new_text)
+        })
+        .collect()
+    }
+
+    #[test]
+    fn lognormal_prior_round_trips() {
+        let text = "kcat = [\n  {enzyme = \"E1\", reaction = \"PGI\", exploc = 126.0, scale = 0.2}, # a\n]\n";
+        let actions = converted(text, 1);
+        assert_eq!(actions.len(), 1);
+        assert_eq!(actions[0].0, "Convert prior to pct1/pct99");
+        assert_eq!(
+            actions[0].1,
+            "  {enzyme = \"E1\", reaction = \"PGI\", pct1 = 79.1237, pct99 = 200.648}, # a\n"
+        );
+        let text = format!("kcat = [\n{}]\n", actions[0].1);
+        let actions = converted(&text, 1);
+        assert_eq!(actions[0].0, "Convert prior to location/scale");
+        assert!(actions[0].1.contains("exploc = 126.0, scale = 0.2}"));
+    }
+
+    #[test]
+    fn drains_are_normal() {
+        let text = "[[drain]]\nreaction = \"d\"\nexperiment = \"A\"\npct1 = -1.0\npct99 = 3.0\n";
+        let actions = converted(text, 2);
+        assert_eq!(actions.len(), 1);
+        assert_eq!(actions[0].1, "location = 1.0\nscale = 0.859717\n");
+    }
+}
@@ -0,0 +1,286 @@
+//! Plausibility of the priors of kinetic parameters.
+//!
+//! The 1st and 99th percentiles of a prior should be within a physiological
+//! range and, for lognormal priors, a few orders of magnitude apart. The bounds
+//! of each kind of prior can be changed in the `[tool.maud-lsp.plausibility]`
+//! table of the Maud `config.toml`, e.g. `km = {max = 1e3, max_orders = 5}`.
+use std::collections::HashMap;
+
+use lsp_types::Diagnostic;
+use serde::Deserialize;
+use toml::Spanned;
+
+use crate::priors::{Prior, Priors};
+use crate::rules::Rule;
+use crate::state::span_to_range;
+
+/// Bounds of the percentiles of a kind of prior, unbounded if `None`.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Bounds {
+    /// Lowest plausible 1st percentile.
+    pub min: Option<f64>,
+    /// Highest plausible 99th percentile.
+    pub max: Option<f64>,
+    /// Most orders of magnitude between the 1st and 99th percentiles.
+    pub max_orders: Option<f64>,
+}
+
+impl Bounds {
+    const fn new(min: f64, max: f64, max_orders: f64) -> Self {
+        Bounds {
+            min: Some(min),
+            max: Some(max),
+            max_orders: Some(max_orders),
+        }
+    }
+
+    /// Fields of `other` take precedence.
+    pub(crate) fn or(self, other: &Bounds) -> Self {
+        Bounds {
+            min: other.min.or(self.min),
+            max: other.max.or(self.max),
+            max_orders: other.max_orders.or(self.max_orders),
+        }
+    }
+}
+
+/// Kinds of priors that are checked, with their unit and default bounds.
+/// Concentrations are in mM and rates in s⁻¹, like in Maud.
+const KINDS: [(&str, &str, Bounds); 6] = [
+    ("kcat", "s⁻¹", Bounds::new(1e-3, 1e4, 4.)),
+    ("km", "mM", Bounds::new(1e-5, 1e2, 4.)),
+    ("ki", "mM", Bounds::new(1e-5, 1e2, 4.)),
+    ("conc_enzyme", "mM", Bounds::new(1e-7, 1., 4.)),
+    ("conc_unbalanced", "mM", Bounds::new(1e-6, 1e2, 4.)),
+    (
+        "drain",
+        "mM s⁻¹",
+        Bounds {
+            min: None,
+            max: None,
+            max_orders: None,
+        },
+    ),
+];
+
+/// Bounds of every kind of prior.
+#[derive(Clone, Debug)]
+pub struct Plausibility {
+    bounds: HashMap<&'static str, Bounds>,
+}
+
+impl Default for Plausibility {
+    fn default() -> Self {
+        Plausibility {
+            bounds: KINDS
+                .iter()
+                .map(|(kind, _, bounds)| (*kind, *bounds))
+                .collect(),
+        }
+    }
+}
+
+impl Plausibility {
+    /// Default bounds overridden by those of the configuration. Unknown kinds are ignored.
+    pub fn from_map(overrides: &HashMap<String, Bounds>) -> Self {
+        let mut plausibility = Plausibility::default();
+        plausibility.extend(overrides);
+        plausibility
+    }
+
+    /// Override some of the bounds, like those sent by the client. Unknown kinds are ignored.
+    pub fn extend(&mut self, overrides: &HashMap<String, Bounds>) {
+        for (kind, bounds) in overrides {
+            match self.bounds.get_mut(kind.as_str()) {
+                Some(known) => *known = known.or(bounds),
+                None => log::warn!("ignoring plausibility bounds of unknown prior {kind}"),
+            }
+        }
+    }
+
+    fn check<P: Prior>(
+        &self,
+        text: &str,
+        kind: &str,
+        priors: &[Spanned<P>],
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        let Some(&(_, unit, _)) = KINDS.iter().find(|(k, _, _)| *k == kind) else {
+            return;
+        };
+        let bounds = self.bounds[kind];
+        for prior in priors {
+            let prior_ref = prior.get_ref();
+            let Some(message) = prior_ref.percentiles().and_then(|percentiles| {
+                let what = format!("{kind} prior of {}", prior_ref.parameter());
+                implausibility(&what, unit, &bounds, percentiles)
+            }) else {
+                continue;
+            };
+            diagnostics.push(Diagnostic {
+                range: span_to_range(text, prior.span()),
+                severity: Some(Rule::ImplausiblePrior.default_severity()),
+                code: Some(Rule::ImplausiblePrior.diagnostic_code()),
+                message,
+                ..Default::default()
+            });
+        }
+    }
+
+    /// Priors whose percentiles are out of the plausible bounds.
+    pub fn diagnostics(&self, text: &str, priors: &Priors) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        self.check(text, "kcat", &priors.kcat, &mut diagnostics);
+        self.check(text, "km", &priors.km, &mut diagnostics);
+        self.check(text, "ki", &priors.ki, &mut diagnostics);
+        self.check(text, "conc_enzyme", &priors.conc_enzyme, &mut diagnostics);
+        self.check(
+            text,
+            "conc_unbalanced",
+            &priors.conc_unbalanced,
+            &mut diagnostics,
+        );
+        self.check(text, "drain", &priors.drain, &mut diagnostics);
+        diagnostics
+    }
+}
+
+/// Number with three significant figures, in scientific notation if it is far from 1,
+/// e.g. `7.92e-10` or `0.0123`.
+fn format_value(x: f64) -> String {
+    let trim = |digits: String| {
+        if digits.contains('.') {
+            digits
+                .trim_end_matches('0')
+                .trim_end_matches('.')
+                .to_string()
+        } else {
+            digits
+        }
+    };
+    if x == 0. || !x.is_finite() {
+        return x.to_string();
+    }
+    if (1e-3..1e4).contains(&x.abs()) {
+        let decimals = (2 - x.abs().log10().floor() as i32).max(0) as usize;
+        trim(format!("{x:.decimals$}"))
+    } else {
+        let scientific = format!("{x:.2e}");
+        let (mantissa, exponent) = scientific.split_once('e').unwrap_or((&scientific, "0"));
+        format!("{}e{exponent}", trim(mantissa.to_string()))
+    }
+}
+
+/// Why the percentiles of a prior, e.g. "km prior of E1 and g6p_c", are implausible.
+/// A distribution that is too wide is reported alone, since it also goes past the bounds.
+fn implausibility(
+    prior: &str,
+    unit: &str,
+    bounds: &Bounds,
+    (pct1, pct99): (f64, f64),
+) -> Option<String> {
+    if let Some(max_orders) = bounds.max_orders.filter(|_| pct1 > 0.) {
+        let orders = (pct99 / pct1).log10();
+        if orders > max_orders {
+            return Some(format!(
+                "The {prior} spans {orders:.1} orders of magnitude between its 1st and 99th percentiles ({} to {} {unit}), more than the plausible {max_orders}.",
+                format_value(pct1),
+                format_value(pct99),
+            ));
+        }
+    }
+    if let Some(min) = bounds.min.filter(|min| pct1 < *min) {
+        return Some(format!(
+            "The 1st percentile of the {prior}, {} {unit}, is below the plausible minimum of {} {unit}.",
+            format_value(pct1),
+            format_value(min),
+        ));
+    }
+    if let Some(max) = bounds.max.filter(|max| pct99 > *max) {
+        return Some(format!(
+            "The 99th percentile of the {prior}, {} {unit}, is above the plausible maximum of {} {unit}.",
+            format_value(pct99),
+            format_value(max),
+        ));
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::PriorsState;
+
+    #[test]
+    fn wide_and_extreme_priors_are_implausible() {
+        let priors = PriorsState::from_path(
+            std::env::current_dir()
+                .unwrap()
+                .join("tests/mock/priors.toml"),
+        );
+        let diagnostics =
+            Plausibility::default().diagnostics(priors.borrow_file_str(), priors.borrow_priors());
+        // the kcat of TPI
+        assert!(diagnostics.iter().any(|diag| diag.range.start.line == 5
+            && diag
+                .message
+                .contains("99th percentile of the kcat prior of E2 and TPI")));
+        // km with a scale of 10.2
+        assert!(diagnostics.iter().any(
+            |diag| diag.range.start.line == 12 && diag.message.contains("orders of magnitude")
+        ));
+        // scale of 1.5
+        assert!(diagnostics.iter().all(|diag| diag.range.start.line != 14));
+    }
+
+    #[test]
+    fn bounds_are_configurable() {
+        let plausibility = Plausibility::from_map(&HashMap::from([(
+            "kcat".to_string(),
+            Bounds {
+                max: Some(1e5),
+                ..Default::default()
+            },
+        )]));
+        assert_eq!(plausibility.bounds["kcat"], Bounds::new(1e-3, 1e5, 4.));
+        assert_eq!(
+            implausibility(
+                "kcat prior",
+                "s⁻¹",
+                &plausibility.bounds["kcat"],
+                (6e3, 1.5e4)
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn messages_show_significant_figures() {
+        assert_eq!(format_value(126.3), "126");
+        assert_eq!(format_value(0.0123456), "0.0123");
+        assert_eq!(format_value(1e-3), "0.001");
+        assert_eq!(format_value(7.92238e-10), "7.92e-10");
+        assert_eq!(format_value(3.23135e11), "3.23e11");
+        assert_eq!(
+            implausibility(
+                "ki prior of E1 and g6p_c",
+                "mM",
+                &Bounds::new(1e-5, 1e2, 4.),
+                (7.92238e-10, 3.23135e11)
+            )
+            .unwrap(),
+            "The ki prior of E1 and g6p_c spans 20.6 orders of magnitude between its 1st and 99th percentiles (7.92e-10 to 3.23e11 mM), more than the plausible 4."
+        );
+        assert_eq!(
+            implausibility(
+                "km prior of E1 and g6p_c",
+                "mM",
+                &Bounds::new(1e-5, 1e2, 4.),
+                (50., 150.)
+            )
+            .unwrap(),
+            "The 99th percentile of the km prior of E1 and g6p_c, 150 mM, is above the plausible maximum of 100 mM."
+        );
+    }
+}
@@ -1,16 +1,80 @@
 use serde::Deserialize;
+use std::collections::HashSet;
 use toml::Spanned;
 
+/// Priors file. Every table is optional, so that an empty file is valid.
 #[derive(Debug, Deserialize, Clone)]
 pub struct Priors {
+    #[serde(default)]
     pub kcat: Vec<Spanned<KcatPrior>>,
+    #[serde(default)]
     pub km: Vec<Spanned<KmPrior>>,
+    #[serde(default)]
+    pub ki: Vec<Spanned<KiPrior>>,
+    #[serde(default)]
     pub conc_enzyme: Vec<Spanned<ConcEnzyme>>,
+    #[serde(default)]
     pub conc_unbalanced: Vec<Spanned<ConcUnbalanced>>,
+    #[serde(default)]
     pub drain: Vec<Spanned<Drain>>,
     pub dgf: Option<Spanned<Dgf>>,
 }
 
+/// Parameters that have a prior, whatever the format of the priors file.
+#[derive(Debug, Default)]
+pub struct PriorCoverage {
+    /// Enzymes and the reactions with a kcat of theirs.
+    pub kcat: HashSet<(String, String)>,
+    /// Enzymes and metabolites in compartments (`g6p_c`) with a km.
+    pub km: HashSet<(String, String)>,
+    /// Enzymes and experiments with a concentration.
+    pub conc_enzyme: HashSet<(String, String)>,
+    /// Drains and experiments with a flux.
+    pub drain: HashSet<(String, String)>,
+}
+
+impl From<&Priors> for PriorCoverage {
+    fn from(priors: &Priors) -> Self {
+        PriorCoverage {
+            kcat: priors
+                .kcat
+                .iter()
+                .map(|kcat| {
+                    let kcat = kcat.get_ref();
+                    (kcat.enzyme.clone(), kcat.reaction.clone())
+                })
+                .collect(),
+            km: priors
+                .km
+                .iter()
+                .map(|km| {
+                    let km = km.get_ref();
+                    (
+                        km.enzyme.clone(),
+                        format!("{}_{}", km.metabolite, km.compartment),
+                    )
+                })
+                .collect(),
+            conc_enzyme: priors
+                .conc_enzyme
+                .iter()
+                .map(|conc| {
+                    let conc = conc.get_ref();
+                    (conc.enzyme.clone(), conc.experiment.clone())
+                })
+                .collect(),
+            drain: priors
+                .drain
+                .iter()
+                .map(|drain| {
+                    let drain = drain.get_ref();
+                    (drain.reaction.clone(), drain.experiment.clone())
+                })
+                .collect(),
+        }
+    }
+}
+
 pub trait Prior {
     /// Incomplete priors generate an diagnostic error.
     fn incomplete(&self) -> Option<&'static str> {
@@ -20,11 +84,71 @@ pub trait Prior {
     fn inconsistent(&self) -> Option<&'static str> {
         None
     }
+    /// Short description of the distribution, e.g. for hovers.
+    fn distribution(&self) -> String {
+        String::new()
+    }
+    /// 1st and 99th percentiles of the distribution, if it is complete.
+    fn percentiles(&self) -> Option<(f64, f64)> {
+        None
+    }
+    /// Identifiers of the parameter, e.g. `E1 and g6p_c` for a km.
+    fn parameter(&self) -> String {
+        String::new()
+    }
+}
+
+/// 99th percentile of the standard normal distribution.
+pub(crate) const Z99: f64 = 2.326_347_874_040_841;
+
+/// Percentiles of a lognormal prior, `exploc` being the exponential of its location.
+fn lognormal_percentiles(
+    exploc: Option<f64>,
+    scale: Option<f64>,
+    pct1: Option<f64>,
+    pct99: Option<f64>,
+) -> Option<(f64, f64)> {
+    match (exploc, scale, pct1, pct99) {
+        (Some(exploc), Some(scale), _, _) => {
+            Some((exploc * (-Z99 * scale).exp(), exploc * (Z99 * scale).exp()))
+        }
+        (_, _, Some(lower), Some(upper)) => Some((lower, upper)),
+        _ => None,
+    }
+}
+
+fn normal_percentiles(
+    location: Option<f64>,
+    scale: Option<f64>,
+    pct1: Option<f64>,
+    pct99: Option<f64>,
+) -> Option<(f64, f64)> {
+    match (location, scale, pct1, pct99) {
+        (Some(location), Some(scale), _, _) => {
+            Some((location - Z99 * scale, location + Z99 * scale))
+        }
+        (_, _, Some(lower), Some(upper)) => Some((lower, upper)),
+        _ => None,
+    }
+}
+
+/// Location and scale take precedence over percentiles, like in Maud.
+pub(crate) fn describe_distribution(
+    location: Option<f64>,
+    scale: Option<f64>,
+    pct1: Option<f64>,
+    pct99: Option<f64>,
+) -> String {
+    match (location, scale, pct1, pct99) {
+        (Some(location), Some(scale), _, _) => format!("location {location}, scale {scale}"),
+        (_, _, Some(pct1), Some(pct99)) => format!("pct1 {pct1}, pct99 {pct99}"),
+        _ => String::from("incomplete"),
+    }
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct KcatPrior {
-    enzyme: String,
+    pub enzyme: String,
     pub reaction: String,
     #[serde(default)]
     pub exploc: Option<f64>,
@@ -51,6 +175,21 @@ pub struct KmPrior {
     pub pct99: Option<f64>,
 }
 
+#[derive(Debug, Deserialize, Clone)]
+pub struct KiPrior {
+    pub enzyme: String,
+    pub metabolite: String,
+    pub compartment: String,
+    #[serde(default)]
+    pub exploc: Option<f64>,
+    #[serde(default)]
+    pub scale: Option<f64>,
+    #[serde(default)]
+    pub pct1: Option<f64>,
+    #[serde(default)]
+    pub pct99: Option<f64>,
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct ConcEnzyme {
     pub enzyme: String,
@@ -69,7 +208,7 @@ pub struct ConcEnzyme {
 pub struct ConcUnbalanced {
     pub metabolite: String,
     pub compartment: String,
-    experiment: String,
+    pub experiment: String,
     #[serde(default)]
     pub exploc: Option<f64>,
     #[serde(default)]
@@ -148,6 +287,54 @@ fn is_square(matrix: &Vec<Vec<f64>>) -> bool {
 }
 
 impl Prior for KmPrior {
+    fn parameter(&self) -> String {
+        format!(
+            "{} and {}_{}",
+            self.enzyme, self.metabolite, self.compartment
+        )
+    }
+    fn distribution(&self) -> String {
+        describe_distribution(self.exploc, self.scale, self.pct1, self.pct99)
+    }
+    fn percentiles(&self) -> Option<(f64, f64)> {
+        lognormal_percentiles(self.exploc, self.scale, self.pct1, self.pct99)
+    }
+    fn incomplete(&self) -> Option<&'static str> {
+        if !((self.exploc.is_some() & self.scale.is_some())
+            || (self.pct1.is_some() & self.pct99.is_some()))
+        {
+            Some("Incomplete prior spec. Either exploc AND scale or pct1 AND pct99 must be specified.")
+        } else {
+            None
+        }
+    }
+    fn inconsistent(&self) -> Option<&'static str> {
+        if (self.exploc.is_some() as usize
+            + self.scale.is_some() as usize
+            + self.pct1.is_some() as usize
+            + self.pct99.is_some() as usize)
+            > 2
+        {
+            Some("There are extra parameters specified. Exploc and scale take precedence over percentiles!")
+        } else {
+            None
+        }
+    }
+}
+
+impl Prior for KiPrior {
+    fn parameter(&self) -> String {
+        format!(
+            "{} and {}_{}",
+            self.enzyme, self.metabolite, self.compartment
+        )
+    }
+    fn distribution(&self) -> String {
+        describe_distribution(self.exploc, self.scale, self.pct1, self.pct99)
+    }
+    fn percentiles(&self) -> Option<(f64, f64)> {
+        lognormal_percentiles(self.exploc, self.scale, self.pct1, self.pct99)
+    }
     fn incomplete(&self) -> Option<&'static str> {
         if !((self.exploc.is_some() & self.scale.is_some())
             || (self.pct1.is_some() & self.pct99.is_some()))
@@ -172,6 +359,15 @@ impl Prior for KmPrior {
 }
 
 impl Prior for KcatPrior {
+    fn parameter(&self) -> String {
+        format!("{} and {}", self.enzyme, self.reaction)
+    }
+    fn distribution(&self) -> String {
+        describe_distribution(self.exploc, self.scale, self.pct1, self.pct99)
+    }
+    fn percentiles(&self) -> Option<(f64, f64)> {
+        lognormal_percentiles(self.exploc, self.scale, self.pct1, self.pct99)
+    }
     fn incomplete(&self) -> Option<&'static str> {
         if !((self.exploc.is_some() & self.scale.is_some())
             || (self.pct1.is_some() & self.pct99.is_some()))
@@ -196,6 +392,15 @@ impl Prior for KcatPrior {
 }
 
 impl Prior for ConcEnzyme {
+    fn parameter(&self) -> String {
+        format!("{} and {}", self.enzyme, self.experiment)
+    }
+    fn distribution(&self) -> String {
+        describe_distribution(self.exploc, self.scale, self.pct1, self.pct99)
+    }
+    fn percentiles(&self) -> Option<(f64, f64)> {
+        lognormal_percentiles(self.exploc, self.scale, self.pct1, self.pct99)
+    }
     fn incomplete(&self) -> Option<&'static str> {
         if !((self.exploc.is_some() & self.scale.is_some())
             || (self.pct1.is_some() & self.pct99.is_some()))
@@ -221,6 +426,15 @@ impl Prior for ConcEnzyme {
 }
 
 impl Prior for Drain {
+    fn parameter(&self) -> String {
+        format!("{} and {}", self.reaction, self.experiment)
+    }
+    fn distribution(&self) -> String {
+        describe_distribution(self.location, self.scale, self.pct1, self.pct99)
+    }
+    fn percentiles(&self) -> Option<(f64, f64)> {
+        normal_percentiles(self.location, self.scale, self.pct1, self.pct99)
+    }
     fn incomplete(&self) -> Option<&'static str> {
         if !((self.location.is_some() & self.scale.is_some())
             || (self.pct1.is_some() & self.pct99.is_some()))
@@ -246,6 +460,18 @@ impl Prior for Drain {
 }
 
 impl Prior for ConcUnbalanced {
+    fn parameter(&self) -> String {
+        format!(
+            "{}_{} and {}",
+            self.metabolite, self.compartment, self.experiment
+        )
+    }
+    fn distribution(&self) -> String {
+        describe_distribution(self.exploc, self.scale, self.pct1, self.pct99)
+    }
+    fn percentiles(&self) -> Option<(f64, f64)> {
+        lognormal_percentiles(self.exploc, self.scale, self.pct1, self.pct99)
+    }
     fn incomplete(&self) -> Option<&'static str> {
         if !((self.exploc.is_some() & self.scale.is_some())
             || (self.pct1.is_some() & self.pct99.is_some()))
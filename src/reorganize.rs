@@ -0,0 +1,462 @@
+//! Code actions that reorganize the arrays of priors and the tables of the model.
+//!
+//! Rows are moved verbatim, with the comments above them, so that the edits only
+//! change the structure of the file.
+use std::collections::HashMap;
+
+use lsp_types::{
+    CodeAction, CodeActionKind, CodeActionOrCommand, Position, Range, TextEdit, Url, WorkspaceEdit,
+};
+
+use crate::formatting::{
+    format_rows, key_value, parse_row, split_comment, FormattingStyle, Row, Scan,
+};
+
+/// Keys that rows are sorted by, in order of priority.
+const SORT_KEYS: [&str; 6] = [
+    "enzyme",
+    "reaction",
+    "metabolite",
+    "compartment",
+    "experiment",
+    "id",
+];
+
+/// Row of an array of inline tables, with the comments above it.
+struct ArrayRow<'a> {
+    lines: Vec<&'a str>,
+    values: HashMap<String, String>,
+}
+
+impl ArrayRow<'_> {
+    /// Value of an identifier, either as `enzyme` or `enzyme_id`.
+    fn value(&self, key: &str) -> &str {
+        self.values
+            .get(key)
+            .or_else(|| self.values.get(&format!("{key}_id")))
+            .map_or("", String::as_str)
+    }
+
+    fn sort_key(&self) -> Vec<&str> {
+        SORT_KEYS.iter().map(|key| self.value(key)).collect()
+    }
+}
+
+/// `name = [` ... `]` with one inline table per line.
+pub(crate) struct InlineArray<'a> {
+    pub(crate) name: &'a str,
+    /// Line of `name = [`.
+    pub(crate) start: usize,
+    /// Line of `]`.
+    pub(crate) end: usize,
+    rows: Vec<ArrayRow<'a>>,
+}
+
+/// `[[name]]` table, from its header to the next one.
+pub(crate) struct TableBlock<'a> {
+    pub(crate) name: &'a str,
+    pub(crate) start: usize,
+    pub(crate) end: usize,
+}
+
+fn unquote(value: &str) -> String {
+    value.trim_matches(|c| c == '"' || c == '\'').to_string()
+}
+
+fn parse_array<'a>(lines: &[&'a str], start: usize, end: usize) -> Option<InlineArray<'a>> {
+    let (content, _) = split_comment(lines[start]);
+    let (name, value) = key_value(content)?;
+    if value != "[" || !lines[end].trim().starts_with(']') {
+        return None;
+    }
+    let mut rows = Vec::new();
+    let mut pending = Vec::new();
+    for line in lines[start + 1..end].iter() {
+        match parse_row(line)? {
+            Row::Table { pairs, .. } => {
+                pending.push(*line);
+                rows.push(ArrayRow {
+                    lines: std::mem::take(&mut pending),
+                    values: pairs
+                        .into_iter()
+                        .map(|(key, value)| (key.to_string(), unquote(value)))
+                        .collect(),
+                });
+            }
+            Row::Comment(_) => pending.push(*line),
+            Row::Blank => (),
+        }
+    }
+    // trailing comments stay at the end
+    if !pending.is_empty() || rows.is_empty() {
+        return None;
+    }
+    Some(InlineArray {
+        name,
+        start,
+        end,
+        rows,
+    })
+}
+
+/// Arrays of inline tables and `[[tables]]` of a TOML document.
+pub(crate) fn structure<'a>(lines: &[&'a str]) -> (Vec<InlineArray<'a>>, Vec<TableBlock<'a>>) {
+    let mut arrays = Vec::new();
+    let mut tables: Vec<TableBlock> = Vec::new();
+    let mut in_table = false;
+    let mut i = 0;
+    while i < lines.len() {
+        let trimmed = lines[i].trim();
+        if trimmed.starts_with('[') {
+            if let Some(table) = tables.last_mut().filter(|table| table.end == 0) {
+                table.end = i;
+            }
+            let header = split_comment(trimmed).0;
+            if let Some(name) = header.strip_prefix("[[").and_then(|h| h.strip_suffix("]]")) {
+                tables.push(TableBlock {
+                    name: name.trim(),
+                    start: i,
+                    end: 0,
+                });
+            }
+            in_table = true;
+            i += 1;
+            continue;
+        }
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        let mut scan = Scan::default();
+        scan.line(lines[i]);
+        i += 1;
+        while scan.open() && i < lines.len() {
+            scan.line(lines[i]);
+            i += 1;
+        }
+        // keys of tables cannot be moved out of them
+        if !in_table && i - start > 2 {
+            arrays.extend(parse_array(lines, start, i - 1));
+        }
+    }
+    if let Some(table) = tables.last_mut().filter(|table| table.end == 0) {
+        table.end = lines.len();
+    }
+    (arrays, tables)
+}
+
+pub(crate) fn line_range(start: usize, end: usize) -> Range {
+    Range::new(Position::new(start as u32, 0), Position::new(end as u32, 0))
+}
+
+pub(crate) fn action(title: String, uri: &Url, edits: Vec<TextEdit>) -> CodeActionOrCommand {
+    CodeActionOrCommand::CodeAction(CodeAction {
+        title,
+        kind: Some(CodeActionKind::REFACTOR_REWRITE),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(uri.clone(), edits)])),
+            ..Default::default()
+        }),
+        ..Default::default()
+    })
+}
+
+/// Replace the rows of an array, in a new order.
+fn reorder_rows(array: &InlineArray, order: Vec<&ArrayRow>, group_by: Option<&str>) -> TextEdit {
+    let mut new_text = String::new();
+    let mut previous: Option<&str> = None;
+    for row in order {
+        if let Some(key) = group_by {
+            if previous.is_some_and(|previous| previous != row.value(key)) {
+                new_text.push('\n');
+            }
+            previous = Some(row.value(key));
+        }
+        for line in row.lines.iter() {
+            new_text.push_str(line);
+            new_text.push('\n');
+        }
+    }
+    TextEdit {
+        range: line_range(array.start + 1, array.end),
+        new_text,
+    }
+}
+
+fn sort_action(array: &InlineArray, uri: &Url) -> Option<CodeActionOrCommand> {
+    let keys: Vec<&str> = SORT_KEYS
+        .into_iter()
+        .filter(|key| array.rows.iter().any(|row| !row.value(key).is_empty()))
+        .collect();
+    if keys.is_empty() {
+        return None;
+    }
+    let mut sorted: Vec<&ArrayRow> = array.rows.iter().collect();
+    sorted.sort_by(|a, b| a.sort_key().cmp(&b.sort_key()));
+    if sorted
+        .iter()
+        .zip(array.rows.iter())
+        .all(|(a, b)| std::ptr::eq(*a, b))
+    {
+        return None;
+    }
+    Some(action(
+        format!("Sort {} by {}", array.name, keys.join(", ")),
+        uri,
+        vec![reorder_rows(array, sorted, None)],
+    ))
+}
+
+fn group_action(array: &InlineArray, uri: &Url) -> Option<CodeActionOrCommand> {
+    let mut experiments: Vec<&str> = array
+        .rows
+        .iter()
+        .map(|row| row.value("experiment"))
+        .collect();
+    experiments.dedup();
+    let mut distinct = experiments.clone();
+    distinct.sort();
+    distinct.dedup();
+    // already grouped or nothing to group
+    if distinct.len() < 2 || experiments.len() == distinct.len() || distinct.contains(&"") {
+        return None;
+    }
+    let mut grouped: Vec<&ArrayRow> = array.rows.iter().collect();
+    grouped.sort_by_key(|row| row.value("experiment"));
+    Some(action(
+        format!("Group {} by experiment", array.name),
+        uri,
+        vec![reorder_rows(array, grouped, Some("experiment"))],
+    ))
+}
+
+/// `name = [{...}]` into `[[name]]` tables, which go at the end of the document
+/// so that they do not capture the keys after them.
+fn to_tables_action(array: &InlineArray, lines: &[&str], uri: &Url) -> CodeActionOrCommand {
+    let mut tables = String::new();
+    for row in array.rows.iter() {
+        tables.push('\n');
+        let (table, comments) = row.lines.split_last().unwrap();
+        for comment in comments {
+            tables.push_str(comment.trim());
+            tables.push('\n');
+        }
+        let (content, comment) = split_comment(table);
+        match comment {
+            Some(comment) => tables.push_str(&format!("[[{}]] {comment}\n", array.name)),
+            None => tables.push_str(&format!("[[{}]]\n", array.name)),
+        }
+        if let Some(Row::Table { pairs, .. }) = parse_row(content) {
+            for (key, value) in pairs {
+                tables.push_str(&format!("{key} = {value}\n"));
+            }
+        }
+    }
+    // the array and the blank lines after it are removed
+    let mut end = array.end + 1;
+    while end < lines.len() && lines[end].trim().is_empty() {
+        end += 1;
+    }
+    let insert_at = Position::new(lines.len() as u32, 0);
+    action(
+        format!("Convert {} to [[{}]] tables", array.name, array.name),
+        uri,
+        vec![
+            TextEdit {
+                range: line_range(array.start, end),
+                new_text: String::new(),
+            },
+            TextEdit {
+                range: Range::new(insert_at, insert_at),
+                new_text: tables,
+            },
+        ],
+    )
+}
+
+/// `[[name]]` tables into `name = [{...}]`, which goes before the first table.
+fn to_inline_action(
+    name: &str,
+    tables: &[&TableBlock],
+    first_table: usize,
+    lines: &[&str],
+    uri: &Url,
+    style: &FormattingStyle,
+) -> Option<CodeActionOrCommand> {
+    let mut rows: Vec<String> = Vec::new();
+    // comments after the last key of a table are above the next one
+    let mut comments: Vec<String> = Vec::new();
+    for table in tables {
+        rows.append(&mut comments);
+        let mut pairs = Vec::new();
+        for line in lines[table.start + 1..table.end].iter() {
+            let (content, comment) = split_comment(line);
+            comments.extend(comment.map(|comment| format!("  {comment}")));
+            if content.trim().is_empty() {
+                continue;
+            }
+            rows.append(&mut comments);
+            let mut scan = Scan::default();
+            scan.line(line);
+            // inline tables must fit in one line
+            if scan.open() {
+                return None;
+            }
+            let (key, value) = key_value(content)?;
+            pairs.push(format!("{key} = {value}"));
+        }
+        rows.extend(
+            split_comment(lines[table.start])
+                .1
+                .map(|c| format!("  {c}")),
+        );
+        rows.push(format!("  {{{}}},", pairs.join(", ")));
+    }
+    rows.append(&mut comments);
+    let rows: Vec<&str> = rows.iter().map(String::as_str).collect();
+    let mut array = format!("{name} = [\n");
+    for row in format_rows(&rows, style)? {
+        array.push_str(&row);
+        array.push('\n');
+    }
+    array.push_str("]\n\n");
+    let mut edits: Vec<TextEdit> = tables
+        .iter()
+        .map(|table| TextEdit {
+            range: line_range(table.start, table.end),
+            new_text: String::new(),
+        })
+        .collect();
+    // an insertion at the start of a removed table is merged with its removal
+    match edits
+        .iter_mut()
+        .find(|edit| edit.range.start.line == first_table as u32)
+    {
+        Some(edit) => edit.new_text = array,
+        None => edits.push(TextEdit {
+            range: line_range(first_table, first_table),
+            new_text: array,
+        }),
+    }
+    Some(action(
+        format!("Convert [[{name}]] tables to an inline array"),
+        uri,
+        edits,
+    ))
+}
+
+/// Sorting, grouping and conversion actions of the structure under `range`.
+pub fn reorganize_actions(
+    text: &str,
+    uri: &Url,
+    range: &Range,
+    style: &FormattingStyle,
+) -> Vec<CodeActionOrCommand> {
+    let lines: Vec<&str> = text.lines().collect();
+    let (arrays, tables) = structure(&lines);
+    let line = range.start.line as usize;
+    let mut actions = Vec::new();
+    if let Some(array) = arrays
+        .iter()
+        .find(|array| array.start <= line && line <= array.end)
+    {
+        actions.extend(sort_action(array, uri));
+        actions.extend(group_action(array, uri));
+        actions.push(to_tables_action(array, &lines, uri));
+    }
+    if let Some(table) = tables
+        .iter()
+        .find(|table| table.start <= line && line < table.end)
+    {
+        let same_name: Vec<&TableBlock> = tables.iter().filter(|t| t.name == table.name).collect();
+        let first_table = lines
+            .iter()
+            .position(|line| line.trim().starts_with('['))
+            .unwrap_or(0);
+        actions.extend(to_inline_action(
+            table.name,
+            &same_name,
+            first_table,
+            &lines,
+            uri,
+            style,
+        ));
+    }
+    actions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Apply non-overlapping edits, from the last one.
+    fn apply(text: &str, action: &CodeActionOrCommand) -> String {
+        let CodeActionOrCommand::CodeAction(action) = action else {
+            panic!("not an action");
+        };
+        let mut edits = action.edit.as_ref().unwrap().changes.as_ref().unwrap()
+            [&Url::parse("file:///priors.toml").unwrap()]
+            .clone();
+        edits.sort_by_key(|edit| std::cmp::Reverse(edit.range.start.line));
+        let mut lines: Vec<String> = text.lines().map(|line| format!("{line}\n")).collect();
+        for edit in edits {
+            let (start, end) = (edit.range.start.line as usize, edit.range.end.line as usize);
+            lines.splice(start..end, [edit.new_text]);
+        }
+        lines.concat()
+    }
+
+    fn actions_at(text: &str, line: u32) -> Vec<CodeActionOrCommand> {
+        let position = Position::new(line, 0);
+        reorganize_actions(
+            text,
+            &Url::parse("file:///priors.toml").unwrap(),
+            &Range::new(position, position),
+            &FormattingStyle::default(),
+        )
+    }
+
+    fn titled<'a>(actions: &'a [CodeActionOrCommand], prefix: &str) -> &'a CodeActionOrCommand {
+        actions
+            .iter()
+            .find(|action| matches!(action, CodeActionOrCommand::CodeAction(a) if a.title.starts_with(prefix)))
+            .unwrap()
+    }
+
+    const PRIORS: &str = "kcat = [\n  {enzyme = \"E2\", reaction = \"TPI\", exploc = 1.0, scale = 0.2},\n  # first\n  {enzyme = \"E1\", reaction = \"PGI\", exploc = 1.0, scale = 0.2},\n]\nconc_enzyme = [\n  {enzyme = \"E1\", experiment = \"A\", exploc = 1.0, scale = 0.2},\n  {enzyme = \"E1\", experiment = \"B\", exploc = 1.0, scale = 0.2},\n  {enzyme = \"E2\", experiment = \"A\", exploc = 1.0, scale = 0.2},\n]\n";
+
+    #[test]
+    fn rows_are_sorted_with_their_comments() {
+        let actions = actions_at(PRIORS, 1);
+        let sorted = apply(PRIORS, titled(&actions, "Sort kcat by enzyme, reaction"));
+        assert!(sorted.starts_with("kcat = [\n  # first\n  {enzyme = \"E1\""));
+    }
+
+    #[test]
+    fn conc_priors_are_grouped_by_experiment() {
+        let actions = actions_at(PRIORS, 6);
+        let grouped = apply(PRIORS, titled(&actions, "Group conc_enzyme by experiment"));
+        assert!(grouped.contains(
+            "\"A\", exploc = 1.0, scale = 0.2},\n\n  {enzyme = \"E1\", experiment = \"B\""
+        ));
+    }
+
+    #[test]
+    fn conversions_round_trip() {
+        let actions = actions_at(PRIORS, 0);
+        let tables = apply(PRIORS, titled(&actions, "Convert kcat to [[kcat]] tables"));
+        assert!(tables.starts_with("conc_enzyme = ["));
+        assert!(tables.contains("\n# first\n[[kcat]]\nenzyme = \"E1\"\nreaction = \"PGI\"\n"));
+        let priors: crate::priors::Priors = toml::from_str(&tables).unwrap();
+        assert_eq!(priors.kcat.len(), 2);
+
+        let line = tables.lines().position(|line| line == "[[kcat]]").unwrap();
+        let actions = actions_at(&tables, line as u32);
+        let inline = apply(&tables, titled(&actions, "Convert [[kcat]] tables"));
+        assert!(inline.contains("kcat = [\n  {enzyme = \"E2\", reaction = \"TPI\", exploc = 1.0, scale = 0.2},\n  # first\n"));
+        let priors: crate::priors::Priors = toml::from_str(&inline).unwrap();
+        assert_eq!(priors.kcat.len(), 2);
+        assert_eq!(priors.conc_enzyme.len(), 3);
+    }
+}
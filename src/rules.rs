@@ -0,0 +1,402 @@
+//! Named diagnostic rules, their severities and inline suppressions.
+//!
+//! Severities can be overridden with a map from rule code to severity, e.g.
+//! `{"missing-drain-prior": "off", "missing-kcat": "warning"}`, which is read
+//! from the `[tool.maud-lsp.diagnostics]` table of the Maud `config.toml`, the
+//! `diagnostics` key of the `initializationOptions` and the `maud-lsp` section
+//! of the client configuration, in increasing order of precedence.
+//!
+//! A `# maud-lsp: ignore[rule-a, rule-b]` comment silences the rules on its
+//! line. Placed on its own line, it silences the next entry, or the whole
+//! table if the next line is a table header. A bare `# maud-lsp: ignore`
+//! silences every rule.
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+
+use lsp_types::{Diagnostic, DiagnosticSeverity, NumberOrString};
+
+use crate::formatting::split_comment;
+
+const SUPPRESSION: &str = "maud-lsp: ignore";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Rule {
+    /// A reaction that is not catalyzed by any enzyme.
+    MissingEnzyme,
+    /// A drain without a prior for one of the experiments.
+    MissingDrainPrior,
+    /// An enzyme without a concentration prior for one of the experiments.
+    MissingEnzymeConcentration,
+    /// An enzyme that does not catalyze any reaction.
+    EnzymeWithoutReaction,
+    /// A reaction without kcat prior for one of its enzymes.
+    MissingKcat,
+    /// An enzyme without km prior for some of the metabolites of its reaction.
+    MissingKm,
+    /// An `enzyme_reaction` row, kcat or km prior of an enzyme and a reaction
+    /// that do not go together.
+    EnzymeReactionMismatch,
+    /// A prior that lacks some of its required fields.
+    IncompletePrior,
+    /// A prior whose fields contradict each other.
+    InconsistentPrior,
+    /// A km prior above the concentration of its metabolite.
+    KmAboveConcentration,
+    /// A prior too wide or out of the physiological range.
+    ImplausiblePrior,
+    /// An identifier defined more than once in the kinetic model.
+    DuplicateId,
+    /// A prior defined more than once.
+    DuplicatePrior,
+    /// An identifier that breaks the Maud naming rules.
+    InvalidId,
+    /// Identifiers that only differ in case.
+    IdCaseClash,
+    /// A CSV cell that refers to an identifier not defined in the project.
+    UnknownId,
+    /// A malformed CSV row or value.
+    InvalidCsv,
+    /// A key of the Maud config that Maud does not read.
+    UnknownConfigKey,
+    /// A value of the Maud config of the wrong type or out of range.
+    InvalidConfig,
+    /// A file referred to by the Maud config that does not exist.
+    MissingFile,
+    /// An initial value that does not match a parameter of the model.
+    InvalidInit,
+}
+
+impl Rule {
+    pub const ALL: [Rule; 21] = [
+        Rule::MissingEnzyme,
+        Rule::MissingDrainPrior,
+        Rule::MissingEnzymeConcentration,
+        Rule::EnzymeWithoutReaction,
+        Rule::MissingKcat,
+        Rule::MissingKm,
+        Rule::EnzymeReactionMismatch,
+        Rule::IncompletePrior,
+        Rule::InconsistentPrior,
+        Rule::KmAboveConcentration,
+        Rule::ImplausiblePrior,
+        Rule::DuplicateId,
+        Rule::DuplicatePrior,
+        Rule::InvalidId,
+        Rule::IdCaseClash,
+        Rule::UnknownId,
+        Rule::InvalidCsv,
+        Rule::UnknownConfigKey,
+        Rule::InvalidConfig,
+        Rule::MissingFile,
+        Rule::InvalidInit,
+    ];
+
+    /// Stable name of the rule, used as diagnostic code.
+    pub fn code(self) -> &'static str {
+        match self {
+            Rule::MissingEnzyme => "missing-enzyme",
+            Rule::MissingDrainPrior => "missing-drain-prior",
+            Rule::MissingEnzymeConcentration => "missing-enzyme-concentration",
+            Rule::EnzymeWithoutReaction => "enzyme-without-reaction",
+            Rule::MissingKcat => "missing-kcat",
+            Rule::MissingKm => "missing-km",
+            Rule::EnzymeReactionMismatch => "enzyme-reaction-mismatch",
+            Rule::IncompletePrior => "incomplete-prior",
+            Rule::InconsistentPrior => "inconsistent-prior",
+            Rule::KmAboveConcentration => "km-above-concentration",
+            Rule::ImplausiblePrior => "implausible-prior",
+            Rule::DuplicateId => "duplicate-id",
+            Rule::DuplicatePrior => "duplicate-prior",
+            Rule::InvalidId => "invalid-id",
+            Rule::IdCaseClash => "id-case-clash",
+            Rule::UnknownId => "unknown-id",
+            Rule::InvalidCsv => "invalid-csv",
+            Rule::UnknownConfigKey => "unknown-config-key",
+            Rule::InvalidConfig => "invalid-config",
+            Rule::MissingFile => "missing-file",
+            Rule::InvalidInit => "invalid-init",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Rule> {
+        Rule::ALL.into_iter().find(|rule| rule.code() == code)
+    }
+
+    pub fn default_severity(self) -> DiagnosticSeverity {
+        match self {
+            Rule::MissingDrainPrior
+            | Rule::MissingEnzymeConcentration
+            | Rule::InconsistentPrior
+            | Rule::KmAboveConcentration
+            | Rule::ImplausiblePrior
+            | Rule::UnknownConfigKey
+            | Rule::IdCaseClash => DiagnosticSeverity::WARNING,
+            _ => DiagnosticSeverity::ERROR,
+        }
+    }
+
+    pub fn diagnostic_code(self) -> NumberOrString {
+        NumberOrString::String(self.code().to_string())
+    }
+}
+
+/// Parse a severity, `Some(None)` meaning that the rule is turned off.
+fn parse_severity(severity: &str) -> Option<Option<DiagnosticSeverity>> {
+    match severity.to_lowercase().as_str() {
+        "error" => Some(Some(DiagnosticSeverity::ERROR)),
+        "warning" | "warn" => Some(Some(DiagnosticSeverity::WARNING)),
+        "information" | "info" => Some(Some(DiagnosticSeverity::INFORMATION)),
+        "hint" => Some(Some(DiagnosticSeverity::HINT)),
+        "off" | "allow" | "none" => Some(None),
+        _ => None,
+    }
+}
+
+/// Severities chosen by the user for some of the rules.
+#[derive(Clone, Debug, Default)]
+pub struct RuleSettings {
+    overrides: HashMap<Rule, Option<DiagnosticSeverity>>,
+}
+
+impl RuleSettings {
+    /// Read a map of rule codes to severities. Unknown rules and severities are ignored.
+    pub fn from_map<'a>(map: impl IntoIterator<Item = (&'a str, &'a str)>) -> Self {
+        RuleSettings {
+            overrides: map
+                .into_iter()
+                .filter_map(|(code, value)| {
+                    let rule = Rule::from_code(code);
+                    let severity = parse_severity(value);
+                    if rule.is_none() || severity.is_none() {
+                        log::warn!("ignoring diagnostic setting {code} = {value}");
+                    }
+                    Some((rule?, severity?))
+                })
+                .collect(),
+        }
+    }
+
+    /// Read the JSON object sent by the client, if it is one.
+    pub fn from_json(value: &serde_json::Value) -> Self {
+        value
+            .as_object()
+            .map(|map| {
+                RuleSettings::from_map(
+                    map.iter()
+                        .filter_map(|(code, sev)| Some((code.as_str(), sev.as_str()?))),
+                )
+            })
+            .unwrap_or_default()
+    }
+
+    /// Override these settings with `other`.
+    pub fn extend(&mut self, other: &RuleSettings) {
+        self.overrides
+            .extend(other.overrides.iter().map(|(k, v)| (*k, *v)));
+    }
+
+    /// Severity of a rule, `None` if it is turned off.
+    pub fn severity(&self, rule: Rule) -> Option<DiagnosticSeverity> {
+        self.overrides
+            .get(&rule)
+            .copied()
+            .unwrap_or(Some(rule.default_severity()))
+    }
+
+    /// Apply the configured severities and the suppression comments of `text`.
+    pub fn apply(&self, text: &str, diagnostics: Vec<Diagnostic>) -> Vec<Diagnostic> {
+        let suppressions = suppressions(text);
+        diagnostics
+            .into_iter()
+            .filter_map(|mut diag| {
+                let rule = match &diag.code {
+                    Some(NumberOrString::String(code)) => Rule::from_code(code),
+                    _ => None,
+                };
+                if let Some(rule) = rule {
+                    let line = diag.range.start.line as usize;
+                    if suppressions.iter().any(|s| s.covers(line, rule)) {
+                        return None;
+                    }
+                    diag.severity = Some(self.severity(rule)?);
+                }
+                Some(diag)
+            })
+            .collect()
+    }
+}
+
+/// Lines silenced by a suppression comment.
+struct Suppression {
+    lines: RangeInclusive<usize>,
+    /// Silenced rules, all of them if `None`.
+    rules: Option<Vec<Rule>>,
+}
+
+impl Suppression {
+    fn covers(&self, line: usize, rule: Rule) -> bool {
+        self.lines.contains(&line)
+            && self
+                .rules
+                .as_ref()
+                .is_none_or(|rules| rules.contains(&rule))
+    }
+}
+
+/// Parse the suppression comment of a line, returning where it starts and its rules.
+fn parse_suppression(line: &str) -> Option<(usize, Option<Vec<Rule>>)> {
+    // a '#' inside a string, like a name, does not start a comment
+    let comment = split_comment(line).1?;
+    // the comment runs until the end of the line, without the trailing spaces
+    let hash = line.trim_end().len() - comment.len();
+    let rest = comment[1..].trim_start().strip_prefix(SUPPRESSION)?;
+    let rules = rest.trim_start().strip_prefix('[').and_then(|rest| {
+        let (inner, _) = rest.split_once(']')?;
+        Some(
+            inner
+                .split(',')
+                .filter_map(|code| Rule::from_code(code.trim()))
+                .collect(),
+        )
+    });
+    Some((hash, rules))
+}
+
+/// Lines until the brackets opened at `start` are closed.
+fn entry_end(lines: &[&str], start: usize) -> usize {
+    let mut depth = 0i32;
+    for (i, line) in lines.iter().enumerate().skip(start) {
+        let code = split_comment(line).0;
+        depth += code.matches(['{', '[']).count() as i32;
+        depth -= code.matches(['}', ']']).count() as i32;
+        if depth <= 0 {
+            return i;
+        }
+    }
+    lines.len().saturating_sub(1)
+}
+
+fn suppressions(text: &str) -> Vec<Suppression> {
+    let lines: Vec<&str> = text.lines().collect();
+    lines
+        .iter()
+        .enumerate()
+        .filter_map(|(i, line)| {
+            let (hash, rules) = parse_suppression(line)?;
+            if !line[..hash].trim().is_empty() {
+                return Some(Suppression {
+                    lines: i..=i,
+                    rules,
+                });
+            }
+            // on its own line, it applies to what comes next
+            let next = (i + 1..lines.len()).find(|&j| {
+                let next = lines[j].trim();
+                !next.is_empty() && !next.starts_with('#')
+            })?;
+            let end = if lines[next].trim_start().starts_with('[') && !lines[next].contains('=') {
+                (next + 1..lines.len())
+                    .find(|&j| {
+                        let l = lines[j].trim_start();
+                        l.starts_with('[') && !l.contains('=')
+                    })
+                    .map_or(lines.len() - 1, |j| j - 1)
+            } else {
+                entry_end(&lines, next)
+            };
+            Some(Suppression {
+                lines: next..=end,
+                rules,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsp_types::{Position, Range};
+
+    fn diagnostic(line: u32, rule: Rule) -> Diagnostic {
+        Diagnostic {
+            range: Range::new(Position::new(line, 0), Position::new(line, 1)),
+            severity: Some(rule.default_severity()),
+            code: Some(rule.diagnostic_code()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn codes_are_unique() {
+        for rule in Rule::ALL {
+            assert_eq!(Rule::from_code(rule.code()), Some(rule));
+        }
+    }
+
+    #[test]
+    fn severities_are_overridden() {
+        let settings = RuleSettings::from_map([("missing-kcat", "warning"), ("missing-km", "off")]);
+        let diagnostics = settings.apply(
+            "",
+            vec![
+                diagnostic(0, Rule::MissingKcat),
+                diagnostic(0, Rule::MissingKm),
+                diagnostic(0, Rule::MissingEnzyme),
+            ],
+        );
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::WARNING));
+        assert_eq!(diagnostics[1].severity, Some(DiagnosticSeverity::ERROR));
+    }
+
+    #[test]
+    fn comments_suppress_lines_and_blocks() {
+        let text = r#"[[reactions]]
+id = "PGI" # maud-lsp: ignore[missing-kcat]
+name = "pgi"
+
+# maud-lsp: ignore[missing-km, missing-kcat]
+[[reactions]]
+id = "PFK"
+
+[[reactions]]
+# maud-lsp: ignore
+km = [
+  {metabolite = "g6p"},
+]
+id = "FBA"
+"#;
+        let settings = RuleSettings::default();
+        let kept = |line, rule| {
+            !settings
+                .apply(text, vec![diagnostic(line, rule)])
+                .is_empty()
+        };
+        assert!(!kept(1, Rule::MissingKcat));
+        assert!(kept(1, Rule::MissingKm));
+        assert!(kept(2, Rule::MissingKcat));
+        assert!(!kept(5, Rule::MissingKm));
+        assert!(!kept(6, Rule::MissingKcat));
+        assert!(kept(6, Rule::MissingEnzyme));
+        assert!(kept(8, Rule::MissingKcat));
+        assert!(!kept(11, Rule::InconsistentPrior));
+        assert!(kept(13, Rule::MissingKcat));
+    }
+
+    #[test]
+    fn hashes_in_strings_are_not_comments() {
+        let text = r##"name = "#1 # maud-lsp: ignore" # maud-lsp: ignore[missing-km]
+id = "PGI # maud-lsp: ignore"
+"##;
+        let settings = RuleSettings::default();
+        let kept = |line, rule| {
+            !settings
+                .apply(text, vec![diagnostic(line, rule)])
+                .is_empty()
+        };
+        assert!(!kept(0, Rule::MissingKm));
+        assert!(kept(0, Rule::MissingKcat));
+        assert!(kept(1, Rule::MissingKcat));
+        assert!(kept(2, Rule::MissingKcat));
+    }
+}
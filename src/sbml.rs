@@ -0,0 +1,654 @@
+//! Conversion between SBML and Maud kinetic models.
+//!
+//! Importing produces a skeleton of the kinetic model and stub priors, which still
+//! need to be reviewed: SBML does not carry the information that Maud needs for
+//! the priors. Exporting writes SBML Level 3 with the `fbc` package for the enzymes.
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::error::Error;
+use std::fs::read_to_string;
+use std::io::{self, Write};
+use std::path::Path;
+
+use roxmltree::{Document, Node};
+
+use crate::identifiers::propose_id;
+use crate::maud_data::{KineticModel, ReactionMechanism};
+use crate::settings::DEFAULT_CONFIG_FILE;
+use crate::state::ProjectState;
+
+const FBC_NS: &str = "http://www.sbml.org/sbml/level3/version1/fbc/version2";
+const RDF_NS: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#";
+const BQBIOL_NS: &str = "http://biomodels.net/biology-qualifiers/";
+
+/// Kinetic model and priors files translated from SBML.
+#[derive(Debug)]
+pub struct SbmlImport {
+    pub kinetic_model: String,
+    pub priors: String,
+}
+
+struct Species {
+    metabolite: String,
+    compartment: String,
+    balanced: bool,
+}
+
+struct ImportedReaction {
+    id: String,
+    name: String,
+    stoichiometry: Vec<(String, f64)>,
+    mechanism: &'static str,
+    enzymes: Vec<String>,
+}
+
+/// Escape a string for a basic TOML string.
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Escape a string for an XML attribute.
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Children of `node` in the list `list` (e.g. `listOfSpecies`), of any namespace.
+fn list<'a, 'input>(node: Node<'a, 'input>, list: &str) -> Vec<Node<'a, 'input>> {
+    node.children()
+        .find(|child| child.tag_name().name() == list)
+        .map(|list| list.children().filter(Node::is_element).collect())
+        .unwrap_or_default()
+}
+
+/// Attribute of the fbc package, which may be written with or without namespace.
+fn fbc_attribute<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.attribute((FBC_NS, name))
+        .or_else(|| node.attribute(name))
+}
+
+/// Remove the BiGG-like prefix of SBML identifiers (`M_`, `R_`, `G_`).
+fn strip_prefix<'a>(id: &'a str, prefix: &str) -> &'a str {
+    id.strip_prefix(prefix)
+        .filter(|s| !s.is_empty())
+        .unwrap_or(id)
+}
+
+/// Maud-compliant identifiers, keeping the same one for repeated SBML identifiers.
+#[derive(Default)]
+struct IdMap {
+    ids: HashMap<String, String>,
+    taken: HashSet<String>,
+}
+
+impl IdMap {
+    fn get(&mut self, sbml_id: &str, fallback: &str) -> String {
+        if let Some(id) = self.ids.get(sbml_id) {
+            return id.clone();
+        }
+        let id = propose_id(sbml_id, fallback, &self.taken);
+        self.taken.insert(id.to_lowercase());
+        self.ids.insert(sbml_id.to_string(), id.clone());
+        id
+    }
+}
+
+/// InChIKey of the MIRIAM annotation of a species, if any.
+fn inchi_key(species: Node) -> String {
+    species
+        .descendants()
+        .filter_map(|node| node.attributes().find(|attr| attr.name() == "resource"))
+        .find_map(|attr| {
+            let value = attr.value();
+            value
+                .find("inchikey")
+                .map(|i| value[i..].trim_start_matches(|c| c != '/' && c != ':'))
+                .map(|key| key.trim_start_matches(['/', ':']).to_string())
+        })
+        .unwrap_or_default()
+}
+
+/// Enzymes of the gene product association of a reaction.
+fn gene_products(reaction: Node, genes: &mut IdMap) -> Vec<String> {
+    // a gene product may be in several complexes, e.g. `(A and B) or (A and C)`
+    let mut seen = HashSet::new();
+    reaction
+        .descendants()
+        .filter(|node| node.tag_name().name() == "geneProductRef")
+        .filter_map(|node| fbc_attribute(node, "geneProduct"))
+        .map(|gene| genes.get(strip_prefix(gene, "G_"), "enzyme"))
+        .filter(|enzyme| seen.insert(enzyme.clone()))
+        .collect()
+}
+
+/// Translate an SBML document into a Maud kinetic model and stub priors.
+pub fn import_sbml(xml: &str) -> Result<SbmlImport, String> {
+    let doc = Document::parse(xml).map_err(|e| format!("Invalid SBML: {e}"))?;
+    let model = doc
+        .root_element()
+        .children()
+        .find(|node| node.tag_name().name() == "model")
+        .ok_or("SBML document without model")?;
+    let mut comp_ids = IdMap::default();
+    let mut met_ids = IdMap::default();
+    let mut reac_ids = IdMap::default();
+    let mut gene_ids = IdMap::default();
+
+    let mut out = String::new();
+    let mut metabolites: Vec<(String, String, String)> = Vec::new();
+    let mut species: HashMap<&str, Species> = HashMap::new();
+    for node in list(model, "listOfSpecies") {
+        let (Some(sbml_id), Some(comp)) = (node.attribute("id"), node.attribute("compartment"))
+        else {
+            continue;
+        };
+        let compartment = comp_ids.get(comp, "compartment");
+        // M_g6p_c is the metabolite g6p in the compartment c
+        let sbml_met = strip_prefix(sbml_id, "M_");
+        let sbml_met = sbml_met
+            .strip_suffix(comp)
+            .and_then(|met| met.strip_suffix('_'))
+            .unwrap_or(sbml_met);
+        let known = met_ids.ids.contains_key(sbml_met);
+        let metabolite = met_ids.get(sbml_met, "metabolite");
+        if !known {
+            metabolites.push((
+                metabolite.clone(),
+                node.attribute("name").unwrap_or(sbml_met).to_string(),
+                inchi_key(node),
+            ));
+        }
+        let external = node.attribute("boundaryCondition") == Some("true")
+            || node.attribute("constant") == Some("true");
+        species.insert(
+            sbml_id,
+            Species {
+                metabolite,
+                compartment,
+                balanced: !external,
+            },
+        );
+    }
+    out.push_str("metabolite = [\n");
+    for (id, name, inchi_key) in metabolites.iter() {
+        out.push_str(&format!(
+            "  {{id = {}, name = {}, inchi_key = {}}},\n",
+            quote(id),
+            quote(name),
+            quote(inchi_key)
+        ));
+    }
+    out.push_str("]\n\nallostery = []\n");
+    // top-level keys must precede the tables
+    let header_end = out.len();
+
+    for node in list(model, "listOfCompartments") {
+        let Some(sbml_id) = node.attribute("id") else {
+            continue;
+        };
+        out.push_str(&format!(
+            "\n[[compartment]]\nid = {}\nname = {}\nvolume = {}\n",
+            quote(&comp_ids.get(sbml_id, "compartment")),
+            quote(node.attribute("name").unwrap_or(sbml_id)),
+            node.attribute("size")
+                .and_then(|size| size.parse::<f64>().ok())
+                .unwrap_or(1.),
+        ));
+    }
+
+    let mut reactions = Vec::new();
+    for node in list(model, "listOfReactions") {
+        let Some(sbml_id) = node.attribute("id") else {
+            continue;
+        };
+        let mut stoichiometry = Vec::new();
+        for (side, sign) in [("listOfReactants", -1.), ("listOfProducts", 1.)] {
+            for reference in list(node, side) {
+                let Some(sp) = reference.attribute("species").and_then(|s| species.get(s)) else {
+                    continue;
+                };
+                let coefficient = reference
+                    .attribute("stoichiometry")
+                    .and_then(|s| s.parse::<f64>().ok())
+                    .unwrap_or(1.);
+                stoichiometry.push((
+                    format!("{}_{}", sp.metabolite, sp.compartment),
+                    sign * coefficient,
+                ));
+            }
+        }
+        let one_sided = stoichiometry.iter().all(|(_, c)| *c < 0.)
+            || stoichiometry.iter().all(|(_, c)| *c > 0.);
+        let mechanism = if one_sided {
+            "drain"
+        } else if node.attribute("reversible") == Some("false") {
+            "irreversible_michaelis_menten"
+        } else {
+            "reversible_michaelis_menten"
+        };
+        let enzymes = if one_sided {
+            Vec::new()
+        } else {
+            gene_products(node, &mut gene_ids)
+        };
+        reactions.push(ImportedReaction {
+            id: reac_ids.get(strip_prefix(sbml_id, "R_"), "reaction"),
+            name: node.attribute("name").unwrap_or(sbml_id).to_string(),
+            stoichiometry,
+            mechanism,
+            enzymes,
+        });
+    }
+    for reac in reactions.iter() {
+        let stoichiometry = reac
+            .stoichiometry
+            .iter()
+            .map(|(met_comp, coefficient)| format!("{met_comp} = {coefficient}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        out.push_str(&format!(
+            "\n[[reaction]]\nid = {}\nname = {}\nstoichiometry = {{{stoichiometry}}}\nmechanism = {}\n",
+            quote(&reac.id),
+            quote(&reac.name),
+            quote(reac.mechanism),
+        ));
+    }
+
+    let gene_names: HashMap<String, String> = list(model, "listOfGeneProducts")
+        .into_iter()
+        .filter_map(|node| {
+            let id = fbc_attribute(node, "id")?;
+            let name = fbc_attribute(node, "name").or(fbc_attribute(node, "label"))?;
+            Some((
+                gene_ids.get(strip_prefix(id, "G_"), "enzyme"),
+                name.to_string(),
+            ))
+        })
+        .collect();
+    let mut enzymes: Vec<&String> = reactions.iter().flat_map(|reac| &reac.enzymes).collect();
+    enzymes.sort();
+    enzymes.dedup();
+    for enzyme in enzymes.iter() {
+        out.push_str(&format!(
+            "\n[[enzyme]]\nid = {}\nname = {}\nsubunits = 1\n",
+            quote(enzyme),
+            quote(gene_names.get(*enzyme).unwrap_or(enzyme)),
+        ));
+    }
+    // the tables are required by Maud, even if empty
+    if enzymes.is_empty() {
+        out.insert_str(header_end, "enzyme = []\nenzyme_reaction = []\n");
+    }
+    for reac in reactions.iter() {
+        for enzyme in reac.enzymes.iter() {
+            out.push_str(&format!(
+                "\n[[enzyme_reaction]]\nenzyme_id = {}\nreaction_id = {}\n",
+                quote(enzyme),
+                quote(&reac.id)
+            ));
+        }
+    }
+
+    let mut rows: Vec<&Species> = species.values().collect();
+    rows.sort_by(|a, b| (&a.metabolite, &a.compartment).cmp(&(&b.metabolite, &b.compartment)));
+    for sp in rows {
+        out.push_str(&format!(
+            "\n[[metabolite_in_compartment]]\nmetabolite_id = {}\ncompartment_id = {}\nbalanced = {}\n",
+            quote(&sp.metabolite),
+            quote(&sp.compartment),
+            sp.balanced
+        ));
+    }
+    Ok(SbmlImport {
+        kinetic_model: out,
+        priors: stub_priors(&reactions),
+    })
+}
+
+/// Placeholder kcat and km priors of every enzyme-reaction pair.
+fn stub_priors(reactions: &[ImportedReaction]) -> String {
+    let mut kcats = String::new();
+    let mut kms: BTreeMap<(String, String), String> = BTreeMap::new();
+    for reac in reactions.iter() {
+        for enzyme in reac.enzymes.iter() {
+            kcats.push_str(&format!(
+                "  {{enzyme = {}, reaction = {}, exploc = 1.0, scale = 1.0}},\n",
+                quote(enzyme),
+                quote(&reac.id)
+            ));
+            for (met_comp, coefficient) in reac.stoichiometry.iter() {
+                // products only bind reversible enzymes
+                if *coefficient > 0. && reac.mechanism != "reversible_michaelis_menten" {
+                    continue;
+                }
+                let (metabolite, compartment) = met_comp.rsplit_once('_').unwrap_or((met_comp, ""));
+                kms.insert(
+                    (enzyme.clone(), met_comp.clone()),
+                    format!(
+                        "  {{metabolite = {}, compartment = {}, enzyme = {}, exploc = 1.0, scale = 1.0}},\n",
+                        quote(metabolite),
+                        quote(compartment),
+                        quote(enzyme)
+                    ),
+                );
+            }
+        }
+    }
+    format!(
+        "# Stub priors imported from SBML: every value is a placeholder.\nkcat = [\n{kcats}]\nkm = [\n{}]\n",
+        kms.into_values().collect::<String>()
+    )
+}
+
+/// Translate a Maud kinetic model into SBML Level 3 Version 2, with enzymes as gene products.
+pub fn export_sbml(kinetic_model: &KineticModel, model_id: &str) -> String {
+    let mut out = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <sbml xmlns=\"http://www.sbml.org/sbml/level3/version2/core\" xmlns:fbc=\"{FBC_NS}\" \
+         level=\"3\" version=\"2\" fbc:required=\"false\">\n  \
+         <model id=\"{}\" fbc:strict=\"false\">\n",
+        escape(model_id)
+    );
+    out.push_str("    <listOfCompartments>\n");
+    for comp in kinetic_model.compartments.iter() {
+        out.push_str(&format!(
+            "      <compartment id=\"{}\" name=\"{}\" size=\"{}\" constant=\"true\"/>\n",
+            escape(comp.id.get_ref()),
+            escape(comp.name),
+            comp.volume
+        ));
+    }
+    out.push_str("    </listOfCompartments>\n    <listOfSpecies>\n");
+    // every metabolite in compartment, whether it has a row or only appears in reactions
+    let mut met_comps: BTreeMap<String, (&str, &str, Option<bool>)> = BTreeMap::new();
+    for row in kinetic_model.metabolite_in_compartment.iter() {
+        met_comps.insert(
+            format!("{}_{}", row.metabolite_id.get_ref(), row.compartment_id),
+            (
+                row.metabolite_id.get_ref(),
+                row.compartment_id,
+                Some(row.balanced),
+            ),
+        );
+    }
+    for reac in kinetic_model.reactions.iter() {
+        for met_comp in reac.stoichiometry.keys() {
+            if let Some((met, comp)) = met_comp.rsplit_once('_') {
+                met_comps
+                    .entry(met_comp.to_string())
+                    .or_insert((met, comp, None));
+            }
+        }
+    }
+    for (met_comp, (met, comp, balanced)) in met_comps.iter() {
+        let metabolite = kinetic_model
+            .metabolites
+            .iter()
+            .find(|m| m.id.get_ref() == met);
+        let id = format!("M_{}", escape(met_comp));
+        out.push_str(&format!(
+            "      <species id=\"{id}\" metaid=\"{id}\" name=\"{}\" compartment=\"{}\" \
+             hasOnlySubstanceUnits=\"false\" boundaryCondition=\"{}\" constant=\"false\"",
+            escape(metabolite.map_or(*met, |m| m.name)),
+            escape(comp),
+            !balanced.unwrap_or(true)
+        ));
+        match metabolite.map(|m| m.inchi_key).filter(|key| !key.is_empty()) {
+            // MIRIAM annotation, read back on import
+            Some(inchi_key) => out.push_str(&format!(
+                ">\n        <annotation><rdf:RDF xmlns:rdf=\"{RDF_NS}\" xmlns:bqbiol=\"{BQBIOL_NS}\">\
+                 <rdf:Description rdf:about=\"#{id}\"><bqbiol:is><rdf:Bag>\
+                 <rdf:li rdf:resource=\"https://identifiers.org/inchikey/{}\"/>\
+                 </rdf:Bag></bqbiol:is></rdf:Description></rdf:RDF></annotation>\n      </species>\n",
+                escape(inchi_key)
+            )),
+            None => out.push_str("/>\n"),
+        }
+    }
+    out.push_str("    </listOfSpecies>\n    <listOfReactions>\n");
+    for reac in kinetic_model.reactions.iter() {
+        let id = reac.id.get_ref();
+        let reversible = !matches!(
+            reac.mechanism,
+            ReactionMechanism::IrreversibleMichaelisMenten
+        );
+        out.push_str(&format!(
+            "      <reaction id=\"R_{}\" name=\"{}\" reversible=\"{reversible}\" fast=\"false\">\n",
+            escape(id),
+            escape(reac.name),
+        ));
+        let mut stoichiometry: Vec<(&&str, &f32)> = reac.stoichiometry.iter().collect();
+        stoichiometry.sort_by(|a, b| a.0.cmp(b.0));
+        for (side, products) in [("listOfReactants", false), ("listOfProducts", true)] {
+            let refs: Vec<String> = stoichiometry
+                .iter()
+                .filter(|(_, coefficient)| (**coefficient > 0.) == products)
+                .map(|(met_comp, coefficient)| {
+                    format!(
+                        "          <speciesReference species=\"M_{}\" stoichiometry=\"{}\" constant=\"true\"/>\n",
+                        escape(met_comp),
+                        coefficient.abs()
+                    )
+                })
+                .collect();
+            if !refs.is_empty() {
+                out.push_str(&format!(
+                    "        <{side}>\n{}        </{side}>\n",
+                    refs.concat()
+                ));
+            }
+        }
+        let enzymes: Vec<&str> = kinetic_model
+            .enzyme_reaction
+            .iter()
+            .filter(|er| er.reaction_id.get_ref() == id)
+            .map(|er| *er.enzyme_id.get_ref())
+            .collect();
+        let refs: String = enzymes
+            .iter()
+            .map(|enz| {
+                format!(
+                    "<fbc:geneProductRef fbc:geneProduct=\"G_{}\"/>",
+                    escape(enz)
+                )
+            })
+            .collect();
+        match enzymes.len() {
+            0 => (),
+            1 => out.push_str(&format!(
+                "        <fbc:geneProductAssociation>{refs}</fbc:geneProductAssociation>\n"
+            )),
+            // isoenzymes
+            _ => out.push_str(&format!(
+                "        <fbc:geneProductAssociation><fbc:or>{refs}</fbc:or></fbc:geneProductAssociation>\n"
+            )),
+        }
+        out.push_str("      </reaction>\n");
+    }
+    out.push_str("    </listOfReactions>\n    <fbc:listOfGeneProducts>\n");
+    for enz in kinetic_model.enzymes.iter() {
+        out.push_str(&format!(
+            "      <fbc:geneProduct fbc:id=\"G_{}\" fbc:label=\"{}\" fbc:name=\"{}\"/>\n",
+            escape(enz.id.get_ref()),
+            escape(enz.id.get_ref()),
+            escape(enz.name)
+        ));
+    }
+    out.push_str("    </fbc:listOfGeneProducts>\n  </model>\n</sbml>\n");
+    out
+}
+
+/// SBML identifier of the model, after the name of the Maud project.
+pub fn model_id(name: &str) -> String {
+    let id: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    match id.chars().next() {
+        Some(c) if c.is_ascii_alphabetic() => id,
+        _ => format!("maud_{id}"),
+    }
+}
+
+/// Import `sbml_file` as `kinetic_model.toml` and `priors.toml` in `output_dir`.
+///
+/// Existing files are not overwritten.
+pub fn import_sbml_file(
+    sbml_file: &Path,
+    output_dir: &Path,
+) -> Result<(), Box<dyn Error + Sync + Send>> {
+    let import = import_sbml(&read_to_string(sbml_file)?)?;
+    std::fs::create_dir_all(output_dir)?;
+    for (name, contents) in [
+        ("kinetic_model.toml", import.kinetic_model),
+        ("priors.toml", import.priors),
+    ] {
+        let path = output_dir.join(name);
+        if path.exists() {
+            return Err(format!("{} already exists", path.display()).into());
+        }
+        std::fs::write(path, contents)?;
+    }
+    Ok(())
+}
+
+/// Export the kinetic model of the project at `root_dir` to `output`, or to stdout.
+pub fn export_sbml_project(
+    root_dir: &Path,
+    output: Option<&Path>,
+) -> Result<(), Box<dyn Error + Sync + Send>> {
+    // the files of the project are located by URI, from an absolute path
+    let project = ProjectState::load(&std::path::absolute(root_dir)?, DEFAULT_CONFIG_FILE)?;
+    let sbml = export_sbml(
+        project.kinetic_state.borrow_kinetic_model(),
+        &model_id(&project.name),
+    );
+    match output {
+        Some(path) => std::fs::write(path, sbml)?,
+        None => match std::io::stdout().lock().write_all(sbml.as_bytes()) {
+            // the output was piped to a command that exited, like `head`
+            Err(e) if e.kind() != io::ErrorKind::BrokenPipe => return Err(e.into()),
+            _ => (),
+        },
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::KineticModelState;
+
+    const SBML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<sbml xmlns="http://www.sbml.org/sbml/level3/version1/core" xmlns:fbc="http://www.sbml.org/sbml/level3/version1/fbc/version2" level="3" version="1">
+  <model id="toy">
+    <listOfCompartments>
+      <compartment id="c" name="cytosol" size="1" constant="true"/>
+    </listOfCompartments>
+    <listOfSpecies>
+      <species id="M_g6p_c" name="D-Glucose 6-phosphate" compartment="c" boundaryCondition="false" constant="false"/>
+      <species id="M_f6p_c" name="D-Fructose 6-phosphate" compartment="c" boundaryCondition="false" constant="false"/>
+      <species id="M_glc__D_c" name="D-Glucose" compartment="c" boundaryCondition="true" constant="false"/>
+    </listOfSpecies>
+    <listOfReactions>
+      <reaction id="R_PGI" name="Glucose-6-phosphate isomerase" reversible="true">
+        <listOfReactants><speciesReference species="M_g6p_c" stoichiometry="1"/></listOfReactants>
+        <listOfProducts><speciesReference species="M_f6p_c" stoichiometry="1"/></listOfProducts>
+        <fbc:geneProductAssociation><fbc:or>
+          <fbc:geneProductRef fbc:geneProduct="G_b4025"/>
+          <fbc:geneProductRef fbc:geneProduct="G_b0001"/>
+        </fbc:or></fbc:geneProductAssociation>
+      </reaction>
+      <reaction id="R_EX_f6p" reversible="true">
+        <listOfReactants><speciesReference species="M_f6p_c" stoichiometry="1"/></listOfReactants>
+      </reaction>
+    </listOfReactions>
+    <fbc:listOfGeneProducts>
+      <fbc:geneProduct fbc:id="G_b4025" fbc:label="b4025" fbc:name="pgi"/>
+    </fbc:listOfGeneProducts>
+  </model>
+</sbml>"#;
+
+    #[test]
+    fn imported_model_is_valid_maud() {
+        let import = import_sbml(SBML).unwrap();
+        let state = KineticModelState::try_from_str(import.kinetic_model.clone()).unwrap();
+        let model = state.borrow_kinetic_model();
+        let ids: Vec<&str> = model.metabolites.iter().map(|m| *m.id.get_ref()).collect();
+        // underscores are removed from identifiers
+        assert_eq!(ids, ["g6p", "f6p", "glcD"]);
+        let pgi = &model.reactions[0];
+        assert_eq!(pgi.id.get_ref(), &"PGI");
+        assert_eq!(pgi.stoichiometry.get("g6p_c"), Some(&-1.));
+        assert!(matches!(
+            model.reactions[1].mechanism,
+            ReactionMechanism::Drain
+        ));
+        // isoenzymes
+        assert_eq!(model.enzyme_reaction.len(), 2);
+        assert_eq!(model.enzymes[1].name, "pgi");
+        assert!(
+            !model
+                .metabolite_in_compartment
+                .iter()
+                .find(|row| row.metabolite_id.get_ref() == &"glcD")
+                .unwrap()
+                .balanced
+        );
+        assert!(import
+            .priors
+            .contains("{enzyme = \"b4025\", reaction = \"PGI\", exploc = 1.0, scale = 1.0}"));
+        assert!(toml::from_str::<crate::priors::Priors>(&import.priors).is_ok());
+    }
+
+    #[test]
+    fn gene_products_of_several_complexes_are_enzymes_once() {
+        let sbml = SBML.replace(
+            r#"<fbc:or>
+          <fbc:geneProductRef fbc:geneProduct="G_b4025"/>
+          <fbc:geneProductRef fbc:geneProduct="G_b0001"/>
+        </fbc:or>"#,
+            r#"<fbc:or>
+          <fbc:and><fbc:geneProductRef fbc:geneProduct="G_A"/><fbc:geneProductRef fbc:geneProduct="G_B"/></fbc:and>
+          <fbc:and><fbc:geneProductRef fbc:geneProduct="G_A"/><fbc:geneProductRef fbc:geneProduct="G_C"/></fbc:and>
+        </fbc:or>"#,
+        );
+        let import = import_sbml(&sbml).unwrap();
+        let state = KineticModelState::try_from_str(import.kinetic_model).unwrap();
+        let model = state.borrow_kinetic_model();
+        let enzymes: Vec<&str> = model
+            .enzyme_reaction
+            .iter()
+            .map(|row| *row.enzyme_id.get_ref())
+            .collect();
+        assert_eq!(enzymes, ["A", "B", "C"]);
+    }
+
+    #[test]
+    fn exported_model_round_trips() {
+        let state = KineticModelState::from_path(
+            std::env::current_dir()
+                .unwrap()
+                .join("tests/mock/ecoli_kinetic_model.toml"),
+        );
+        let sbml = export_sbml(state.borrow_kinetic_model(), "ecoli_small");
+        assert!(sbml.contains("name=\"D-Glucose 6-phosphate\""));
+        let import = import_sbml(&sbml).unwrap();
+        let imported = KineticModelState::try_from_str(import.kinetic_model).unwrap();
+        let model = imported.borrow_kinetic_model();
+        assert_eq!(model.reactions.len(), 6);
+        let pfk = model
+            .reactions
+            .iter()
+            .find(|reac| reac.id.get_ref() == &"PFK")
+            .unwrap();
+        assert!(matches!(
+            pfk.mechanism,
+            ReactionMechanism::IrreversibleMichaelisMenten
+        ));
+        assert_eq!(pfk.stoichiometry.get("fdp_c"), Some(&1.));
+        assert_eq!(model.enzyme_reaction.len(), 5);
+        assert_eq!(model.metabolites[0].inchi_key, "D INCHI");
+    }
+}
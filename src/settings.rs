@@ -0,0 +1,142 @@
+//! Settings of the client, from the `initializationOptions` and the `maud-lsp`
+//! section of its configuration.
+//!
+//! Both are objects like
+//! `{"config_file": "config.toml", "diagnostics": {"missing-kcat": "off"}, "hover": "brief"}`.
+//! The section is read on `workspace/didChangeConfiguration` and overrides the
+//! keys of the initialization options it has, which override the
+//! `[tool.maud-lsp]` table of the Maud config. Invalid values are ignored.
+use std::collections::HashMap;
+
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::formatting::FormattingStyle;
+use crate::hover::HoverVerbosity;
+use crate::plausibility::Bounds;
+use crate::rules::RuleSettings;
+
+/// Name of the Maud config in the project directory, unless the client says otherwise.
+pub const DEFAULT_CONFIG_FILE: &str = "config.toml";
+
+#[derive(Clone, Debug)]
+pub struct Settings {
+    /// Maud config of the project, relative to the root directory.
+    pub config_file: String,
+    /// Severities of the diagnostic rules.
+    pub diagnostics: RuleSettings,
+    /// Plausibility bounds of the priors by kind, e.g. `km`.
+    pub plausibility: HashMap<String, Bounds>,
+    pub hover: HoverVerbosity,
+    pub formatting: FormattingStyle,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            config_file: DEFAULT_CONFIG_FILE.to_string(),
+            diagnostics: RuleSettings::default(),
+            plausibility: HashMap::new(),
+            hover: HoverVerbosity::default(),
+            formatting: FormattingStyle::default(),
+        }
+    }
+}
+
+/// Value of a setting, `None` if it is invalid.
+fn parse<T: DeserializeOwned>(key: &str, value: &Value) -> Option<T> {
+    serde_json::from_value(value.clone())
+        .map_err(|e| log::warn!("ignoring setting {key} = {value}: {e}"))
+        .ok()
+}
+
+impl Settings {
+    /// Override the settings with the keys of a JSON object sent by the client.
+    pub fn update(&mut self, value: &Value) {
+        if let Some(file) = value
+            .get("config_file")
+            .and_then(|v| parse("config_file", v))
+        {
+            self.config_file = file;
+        }
+        if let Some(diagnostics) = value.get("diagnostics") {
+            self.diagnostics
+                .extend(&RuleSettings::from_json(diagnostics));
+        }
+        let plausibility = value.get("plausibility").and_then(Value::as_object);
+        for (kind, bounds) in plausibility.into_iter().flatten() {
+            if let Some(bounds) = parse::<Bounds>(kind, bounds) {
+                self.plausibility
+                    .entry(kind.clone())
+                    .and_modify(|known| *known = known.or(&bounds))
+                    .or_insert(bounds);
+            }
+        }
+        if let Some(hover) = value.get("hover").and_then(|v| parse("hover", v)) {
+            self.hover = hover;
+        }
+        // each field on its own, so that they can be set in different places
+        let formatting = value.get("formatting");
+        if let Some(sort_keys) = formatting
+            .and_then(|f| f.get("sort_keys"))
+            .and_then(|v| parse("sort_keys", v))
+        {
+            self.formatting.sort_keys = sort_keys;
+        }
+        if let Some(align_columns) = formatting
+            .and_then(|f| f.get("align_columns"))
+            .and_then(|v| parse("align_columns", v))
+        {
+            self.formatting.align_columns = align_columns;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::Rule;
+    use serde_json::json;
+
+    #[test]
+    fn later_settings_override_only_their_keys() {
+        let mut settings = Settings::default();
+        settings.update(&json!({
+            "config_file": "maud.toml",
+            "diagnostics": {"missing-kcat": "off"},
+            "plausibility": {"km": {"max": 1e3}},
+            "formatting": {"align_columns": false},
+        }));
+        settings.update(&json!({
+            "diagnostics": {"missing-enzyme": "hint"},
+            "plausibility": {"km": {"min": 1e-6}, "kcat": {"max": "high"}},
+            "hover": "brief",
+            "formatting": {"sort_keys": false},
+        }));
+        assert_eq!(settings.config_file, "maud.toml");
+        assert_eq!(settings.diagnostics.severity(Rule::MissingKcat), None);
+        assert_eq!(
+            settings.plausibility,
+            HashMap::from([(
+                "km".to_string(),
+                Bounds {
+                    min: Some(1e-6),
+                    max: Some(1e3),
+                    max_orders: None
+                }
+            )])
+        );
+        assert_eq!(settings.hover, HoverVerbosity::Brief);
+        assert_eq!(
+            settings.formatting,
+            FormattingStyle {
+                sort_keys: false,
+                align_columns: false
+            }
+        );
+        // invalid values are ignored
+        settings.update(&json!({"hover": "loud", "config_file": 3}));
+        assert_eq!(settings.hover, HoverVerbosity::Brief);
+        assert_eq!(settings.config_file, "maud.toml");
+    }
+}
@@ -1,13 +1,32 @@
+use crate::catalysis::{enzyme_reaction_diagnostics, prior_pair_diagnostics};
+use crate::config_schema::{config_diagnostics, config_file_links, config_hover};
+use crate::csv_data::{CsvTable, KnownIds};
+use crate::database::{Input, Memo, Revisions};
+use crate::duplicates::{kinetic_model_duplicates, priors_duplicates};
 use crate::experiments::ExperimentData;
-use crate::maud_data::{KineticModel, ReactionMechanism};
+use crate::formatting::{formatting_edits, FormattingStyle};
+use crate::hover::HoverVerbosity;
+use crate::identifiers::{invalid_identifiers, Document};
+use crate::inits::{csv_inits_diagnostics, inits_diagnostics};
+use crate::logging::span;
+use crate::maud_data::{KineticModel, MaudConfig, ReactionMechanism};
 use crate::metabolic::{Entity, Metabolic, MetabolicEnzyme};
-use crate::priors::{Prior, Priors};
-use lsp_types::{Diagnostic, Position};
+use crate::percentiles::percentile_actions;
+use crate::plausibility::Plausibility;
+use crate::priors::{Prior, PriorCoverage, Priors};
+use crate::reorganize::reorganize_actions;
+use crate::rules::{Rule, RuleSettings};
+use crate::settings::Settings;
+use crate::symbol_parser::{extract_met_comp, extract_symbol};
+use crate::symbols::{PriorIndex, SymbolIndex};
+use lsp_types::{CodeActionOrCommand, CompletionItem, Diagnostic, Position, TextEdit, Url};
 
 use ouroboros::self_referencing;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
-use std::io::Read;
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use toml::Spanned;
 
 const OFF: u32 = 5;
@@ -15,35 +34,29 @@ const OFF: u32 = 5;
 /// Both the data model and string representing the file.
 #[self_referencing]
 pub struct KineticModelState {
-    file_str: String,
+    pub(crate) file_str: String,
     #[borrows(file_str)]
     #[covariant]
-    kinetic_model: KineticModel<'this>,
+    pub(crate) kinetic_model: KineticModel<'this>,
+    #[borrows(kinetic_model)]
+    #[covariant]
+    pub(crate) symbols: SymbolIndex<'this>,
 }
 
 impl KineticModelState {
-    /// Can panic. Used first time the server reads the document
+    /// Can panic. Used in tests to read the mock documents.
+    #[cfg(test)]
     pub fn from_path<P: AsRef<std::path::Path>>(path: P) -> Self {
-        let mut file = std::fs::File::open(path).expect("Unable to open the file");
-        let mut contents = String::new();
-        file.read_to_string(&mut contents)
-            .expect("Unable to read the file");
-        KineticModelStateBuilder {
-            file_str: contents,
-            kinetic_model_builder: |file_str| toml::from_str(file_str.as_str()).unwrap(),
-        }
-        .build()
+        let contents = std::fs::read_to_string(path).expect("Unable to read the file");
+        Self::try_from_str(contents).expect("Invalid document")
     }
 
-    /// Do not panic.
-    pub fn try_from_path<P: AsRef<std::path::Path>>(path: P) -> Result<Self, std::io::Error> {
-        let mut file = File::open(path).expect("Unable to open the file");
-        let mut contents = String::new();
-        file.read_to_string(&mut contents)
-            .expect("Unable to read the file");
+    /// Do not panic. The file may be in an invalid state while it is edited.
+    pub fn try_from_str(contents: String) -> Result<Self, std::io::Error> {
         KineticModelStateTryBuilder {
             file_str: contents,
             kinetic_model_builder: |file_str| {
+                let _span = span("parse kinetic model");
                 toml::from_str(file_str.as_str())
                     // the error is changed because of lifetime bounds of toml::from_str in
                     // conjuction with ouroboros
@@ -54,101 +67,158 @@ impl KineticModelState {
                         )
                     })
             },
+            symbols_builder: |kinetic_model| {
+                let _span = span("index kinetic model");
+                Ok(SymbolIndex::new(kinetic_model))
+            },
         }
         .try_build()
     }
 
-    /// Return the absolute spanned value of the symbol in the data model.
-    /// TODO: return the actual symbol to make it more useful
-    pub fn find_symbol<'a>(&'a self, symbol: &str) -> Option<Entity<'a>> {
-        let some_met = self
-            .borrow_kinetic_model()
-            .metabolites
-            .iter()
-            // TODO: handle this unwrap
-            .find(|&met| met.identifier() == symbol);
-        if some_met.is_some() {
-            return some_met.map(Entity::Met);
-        }
+    /// Entities of the data model with an id, one for every kind it is the id of.
+    ///
+    /// Ids are only unique within a kind, e.g. an enzyme may be named like its
+    /// reaction, so ambiguous ids return more than one entity.
+    pub fn find_symbol<'a>(&'a self, symbol: &str) -> Vec<Entity<'a>> {
+        let symbols = self.borrow_symbols();
+        let mut entities = Vec::new();
+        entities.extend(symbols.metabolite(symbol).map(Entity::Met));
+        entities.extend(symbols.reaction(symbol).map(Entity::Reac));
+        entities.extend(symbols.enzyme(symbol).map(|enzyme| {
+            Entity::Enz(MetabolicEnzyme {
+                enzyme,
+                reactions: symbols.reactions_of_enzyme(symbol).to_vec(),
+            })
+        }));
+        // metabolite in compartment, e.g. g6p_c
+        entities.extend(symbols.met_comp(symbol).map(Entity::MetComp));
+        entities
+    }
 
-        let some_reac = self
-            .borrow_kinetic_model()
-            .reactions
-            .iter()
-            // TODO: handle this unwrap
-            .find(|&reac| reac.identifier() == symbol)
-            .map(Entity::Reac);
-        if some_reac.is_some() {
-            return some_reac;
-        }
-        self.borrow_kinetic_model()
-            .enzymes
+    /// Find the lines where a symbol is defined (for GotoDefinition).
+    pub fn find_symbol_lines(&self, symbol: &str) -> Vec<usize> {
+        self.find_symbol(symbol)
             .iter()
-            // TODO: handle this unwrap
-            .find(|enz| enz.id.get_ref() == &symbol)
-            .map(|enz| {
-                Entity::Enz(MetabolicEnzyme::from_enzyme(
-                    enz,
-                    self.borrow_kinetic_model().enzyme_reaction.as_slice(),
-                ))
-            })
+            .map(|entity| span_to_line_number(self.borrow_file_str(), entity.span()))
+            .collect()
     }
+}
 
-    /// Render a symbol str.
-    pub fn find_rendered_symbol(&self, symbol: &str) -> String {
-        let metabolic_entity = self.find_symbol(symbol);
-        if let Some(met) = metabolic_entity {
-            met.to_string()
-        } else {
-            String::from("")
+fn is_csv(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("csv"))
+}
+
+/// Clients may encode the same path differently, compare only the paths.
+pub(crate) fn same_file(uri: &Url, other: &Url) -> bool {
+    uri.path() == other.path()
+}
+
+fn read_line<P: AsRef<Path>>(file_path: P, line: u32) -> std::io::Result<String> {
+    let input = File::open(file_path)?;
+    let buffered = std::io::BufReader::new(input);
+    buffered
+        .lines()
+        .nth(line as usize)
+        .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::NotFound))?
+}
+
+fn span_to_line_number<T>(file_string: &str, span: &Spanned<T>) -> usize {
+    file_string.get(0..span.start()).unwrap().lines().count()
+}
+
+/// Start of every line of a file, to find the lines of many spans without
+/// scanning the file from its start each time.
+pub(crate) struct LineIndex {
+    starts: Vec<usize>,
+}
+
+impl LineIndex {
+    pub(crate) fn new(file_string: &str) -> Self {
+        LineIndex {
+            starts: std::iter::once(0)
+                .chain(file_string.match_indices('\n').map(|(i, _)| i + 1))
+                .collect(),
         }
     }
 
-    /// Find the line where a symbol is defined (for GotoDefinition).
-    pub fn find_symbol_line(&self, symbol: &str) -> Option<usize> {
-        let met_metabolite = self.find_symbol(symbol)?;
-        Some(span_to_line_number(
-            self.borrow_file_str(),
-            met_metabolite.span(),
-        ))
+    /// Same as [`span_to_line_number`], in logarithmic time.
+    fn line_number<T>(&self, span: &Spanned<T>) -> usize {
+        let offset = span.start();
+        let newlines = self.starts.partition_point(|start| *start <= offset) - 1;
+        newlines + usize::from(self.starts[newlines] < offset)
     }
 }
 
-fn span_to_line_number<T>(file_string: &str, span: &Spanned<T>) -> usize {
-    file_string.get(0..span.start()).unwrap().lines().count()
+/// Convert a byte offset into an LSP position (UTF-16 based character).
+pub(crate) fn offset_to_position(file_string: &str, offset: usize) -> Position {
+    let before = &file_string[..offset];
+    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    Position {
+        line: before.matches('\n').count() as u32,
+        character: before[line_start..].encode_utf16().count() as u32,
+    }
+}
+
+/// Convert an LSP position into a byte offset, clamped to the end of the line.
+pub(crate) fn position_to_offset(file_string: &str, position: Position) -> usize {
+    let line_start: usize = file_string
+        .split_inclusive('\n')
+        .take(position.line as usize)
+        .map(str::len)
+        .sum();
+    let line = file_string[line_start..]
+        .split('\n')
+        .next()
+        .unwrap_or_default();
+    let mut units = 0;
+    for (i, c) in line.char_indices() {
+        if units >= position.character as usize {
+            return line_start + i;
+        }
+        units += c.len_utf16();
+    }
+    line_start + line.len()
+}
+
+/// Convert a byte span into an LSP range.
+pub(crate) fn span_to_range(file_string: &str, span: (usize, usize)) -> lsp_types::Range {
+    lsp_types::Range {
+        start: offset_to_position(file_string, span.0),
+        end: offset_to_position(file_string, span.1),
+    }
 }
 
 #[self_referencing]
 pub struct PriorsState {
-    file_str: String,
+    pub(crate) file_str: String,
     #[borrows(file_str)]
     pub priors: Priors,
+    #[borrows(priors)]
+    #[covariant]
+    pub(crate) prior_index: PriorIndex<'this>,
 }
 
 impl PriorsState {
+    #[cfg(test)]
     pub fn from_path<P: AsRef<std::path::Path>>(path: P) -> Self {
-        let mut file = File::open(path).expect("Unable to open the file");
-        let mut contents = String::new();
-        file.read_to_string(&mut contents)
-            .expect("Unable to read the file");
-        PriorsStateBuilder {
-            file_str: contents,
-            priors_builder: |file_str| toml::from_str(file_str.as_str()).unwrap(),
-        }
-        .build()
+        let contents = std::fs::read_to_string(path).expect("Unable to read the file");
+        Self::try_from_str(contents).expect("Invalid document")
     }
 
-    pub fn try_from_path<P: AsRef<std::path::Path>>(path: P) -> Result<Self, std::io::Error> {
-        let mut file = File::open(path)?;
-        let mut contents = String::new();
-        file.read_to_string(&mut contents)?;
+    pub fn try_from_str(contents: String) -> Result<Self, std::io::Error> {
         PriorsStateTryBuilder {
             file_str: contents,
             priors_builder: |file_str| {
+                let _span = span("parse priors");
                 toml::from_str(file_str.as_str()).map_err(|_| {
                     std::io::Error::new(std::io::ErrorKind::InvalidData, "Invalid priors.")
                 })
             },
+            prior_index_builder: |priors| {
+                let _span = span("index priors");
+                Ok(PriorIndex::new(priors))
+            },
         }
         .try_build()
     }
@@ -156,32 +226,18 @@ impl PriorsState {
 
 #[self_referencing]
 pub struct ExperimentsState {
-    file_str: String,
+    pub(crate) file_str: String,
     #[borrows(file_str)]
     #[covariant]
     experiments: ExperimentData<'this>,
 }
 
 impl ExperimentsState {
-    pub fn from_path<P: AsRef<std::path::Path>>(path: P) -> Self {
-        let mut file = File::open(path).expect("Unable to open the file");
-        let mut contents = String::new();
-        file.read_to_string(&mut contents)
-            .expect("Unable to read the file");
-        ExperimentsStateBuilder {
-            file_str: contents,
-            experiments_builder: |file_str| toml::from_str(file_str.as_str()).unwrap(),
-        }
-        .build()
-    }
-
-    pub fn try_from_path<P: AsRef<std::path::Path>>(path: P) -> Result<Self, std::io::Error> {
-        let mut file = File::open(path)?;
-        let mut contents = String::new();
-        file.read_to_string(&mut contents)?;
+    pub fn try_from_str(contents: String) -> Result<Self, std::io::Error> {
         ExperimentsStateTryBuilder {
             file_str: contents,
             experiments_builder: |file_str| {
+                let _span = span("parse experiments");
                 toml::from_str(file_str.as_str()).map_err(|_| {
                     std::io::Error::new(
                         std::io::ErrorKind::InvalidData,
@@ -197,28 +253,763 @@ impl ExperimentsState {
     }
 }
 
+/// Parsed files of a Maud project along with their URIs.
+///
+/// Cloning is cheap, the parsed files are shared, so that requests can be
+/// handled on a snapshot while the documents change.
+#[derive(Clone)]
+pub struct ProjectState {
+    pub kinetic_state: Arc<KineticModelState>,
+    pub kinetic_model_uri: Url,
+    pub priors_state: Arc<PriorsState>,
+    pub priors_uri: Url,
+    pub experiments_state: Arc<ExperimentsState>,
+    pub experiments_uri: Url,
+    pub experiment_ids: Vec<String>,
+    pub config_uri: Url,
+    /// Contents of the config.toml, validated against the schema of Maud.
+    config_text: String,
+    /// File of initial values of the sampler, if the config has one. Its text
+    /// is empty if it is a CSV table, which is in `csv_documents`.
+    pub inits_uri: Option<Url>,
+    inits_text: String,
+    /// CSV tables of the project, e.g. the priors and measurements of older Maud projects.
+    pub csv_documents: HashMap<Url, Arc<CsvTable>>,
+    /// Name of the project, from the config.toml.
+    pub name: String,
+    root_dir: PathBuf,
+    /// Contents of the documents opened by the client, which may not be saved.
+    pub open_documents: HashMap<Url, String>,
+    /// Severities of the diagnostic rules of the `[tool.maud-lsp]` table of the config.
+    project_rules: RuleSettings,
+    /// Plausibility bounds of the `[tool.maud-lsp]` table of the config.
+    project_plausibility: Plausibility,
+    /// Severities of the diagnostic rules, with those of the client.
+    rules: RuleSettings,
+    /// Bounds of the plausible priors, with those of the client.
+    plausibility: Plausibility,
+    hover: HoverVerbosity,
+    formatting: FormattingStyle,
+    /// Revisions of the files and settings the queries are computed from.
+    revisions: Revisions,
+    /// Shared by the snapshots, results are keyed by the revisions of their inputs.
+    queries: Arc<Queries>,
+}
+
+/// Data derived from the project files, recomputed only when its inputs change.
+#[derive(Default)]
+struct Queries {
+    known_ids: Memo<(), KnownIds>,
+    prior_coverage: Memo<(), PriorCoverage>,
+    /// Diagnostics of the documents, by path.
+    diagnostics: Memo<String, Vec<Diagnostic>>,
+}
+
+impl ProjectState {
+    /// Read the Maud config of the project, `config_file` in `root_dir`, and its files.
+    pub fn load(
+        root_dir: &Path,
+        config_file: &str,
+    ) -> Result<Self, Box<dyn std::error::Error + Sync + Send>> {
+        let config_path = root_dir.join(config_file);
+        let with_path = |e: &dyn std::fmt::Display| format!("{}: {e}", config_path.display());
+        let text = std::fs::read_to_string(&config_path).map_err(|e| with_path(&e))?;
+        let maud_config: MaudConfig = toml::from_str(&text).map_err(|e| with_path(&e))?;
+        Ok(Self::try_from_config_file(
+            root_dir,
+            config_file,
+            &maud_config,
+        )?)
+    }
+
+    /// Do not panic. The error points to the file that could not be read.
+    #[cfg(test)]
+    pub fn try_from_config(
+        root_dir: &Path,
+        maud_config: &MaudConfig,
+    ) -> Result<Self, std::io::Error> {
+        Self::try_from_config_file(root_dir, crate::settings::DEFAULT_CONFIG_FILE, maud_config)
+    }
+
+    /// Project whose Maud config, `maud_config`, is `config_file` in `root_dir`.
+    fn try_from_config_file(
+        root_dir: &Path,
+        config_file: &str,
+        maud_config: &MaudConfig,
+    ) -> Result<Self, std::io::Error> {
+        // read, parse and locate one of the files, pointing to it on error
+        fn load<T>(
+            path: std::path::PathBuf,
+            parse: impl FnOnce(String) -> Result<T, std::io::Error>,
+        ) -> Result<(T, Url), std::io::Error> {
+            let with_file = |e: std::io::Error| {
+                std::io::Error::new(e.kind(), format!("{}: {e}", path.display()))
+            };
+            let uri = Url::from_file_path(&path)
+                .map_err(|_| with_file(std::io::ErrorKind::InvalidInput.into()))?;
+            let state = std::fs::read_to_string(&path)
+                .and_then(parse)
+                .map_err(with_file)?;
+            Ok((state, uri))
+        }
+        let (kinetic_state, kinetic_model_uri) = load(
+            root_dir.join(&maud_config.kinetic_model_file),
+            KineticModelState::try_from_str,
+        )?;
+        let mut csv_documents = HashMap::new();
+        let priors_path = root_dir.join(&maud_config.priors_file);
+        let (priors_state, priors_uri) = if is_csv(&priors_path) {
+            let (table, uri) = load(priors_path, |text| Ok(CsvTable::parse(text)))?;
+            csv_documents.insert(uri.clone(), Arc::new(table));
+            (PriorsState::try_from_str(String::new())?, uri)
+        } else {
+            load(priors_path, PriorsState::try_from_str)?
+        };
+        let experiments_path = root_dir.join(&maud_config.experiments_file);
+        let (experiments_state, experiments_uri, experiment_ids) = if is_csv(&experiments_path) {
+            let (table, uri) = load(experiments_path, |text| Ok(CsvTable::parse(text)))?;
+            let experiment_ids = table.experiments();
+            csv_documents.insert(uri.clone(), Arc::new(table));
+            (
+                ExperimentsState::try_from_str(String::new())?,
+                uri,
+                experiment_ids,
+            )
+        } else {
+            let (state, uri) = load(experiments_path, ExperimentsState::try_from_str)?;
+            let experiment_ids = state.experiments();
+            (state, uri, experiment_ids)
+        };
+        let config_path = root_dir.join(config_file);
+        let config_text = std::fs::read_to_string(&config_path).unwrap_or_default();
+        let config_uri = Url::from_file_path(&config_path)
+            .map_err(|_| std::io::Error::from(std::io::ErrorKind::InvalidInput))?;
+        // a missing inits file is reported in the config
+        let (inits_uri, inits_text) = match &maud_config.user_inits_file {
+            Some(file) if is_csv(&root_dir.join(file)) => {
+                match load(root_dir.join(file), |text| Ok(CsvTable::parse(text))) {
+                    Ok((table, uri)) => {
+                        csv_documents.insert(uri.clone(), Arc::new(table));
+                        (Some(uri), String::new())
+                    }
+                    Err(_) => (None, String::new()),
+                }
+            }
+            Some(file) => (
+                Url::from_file_path(root_dir.join(file)).ok(),
+                std::fs::read_to_string(root_dir.join(file)).unwrap_or_default(),
+            ),
+            None => (None, String::new()),
+        };
+        // other tables, like measurements, are next to the config
+        for entry in std::fs::read_dir(root_dir)?.flatten() {
+            let path = entry.path();
+            if !is_csv(&path) {
+                continue;
+            }
+            if let Ok((table, uri)) = load(path, |text| Ok(CsvTable::parse(text))) {
+                csv_documents.entry(uri).or_insert(Arc::new(table));
+            }
+        }
+        let rules = RuleSettings::from_map(
+            maud_config
+                .tool
+                .maud_lsp
+                .diagnostics
+                .iter()
+                .map(|(code, severity)| (code.as_str(), severity.as_str())),
+        );
+        let plausibility = Plausibility::from_map(&maud_config.tool.maud_lsp.plausibility);
+        Ok(ProjectState {
+            kinetic_state: Arc::new(kinetic_state),
+            kinetic_model_uri,
+            priors_state: Arc::new(priors_state),
+            priors_uri,
+            experiment_ids,
+            experiments_state: Arc::new(experiments_state),
+            experiments_uri,
+            config_uri,
+            config_text,
+            inits_uri,
+            inits_text,
+            csv_documents,
+            name: maud_config.name.clone(),
+            root_dir: root_dir.to_path_buf(),
+            open_documents: HashMap::new(),
+            project_rules: rules.clone(),
+            project_plausibility: plausibility.clone(),
+            rules,
+            plausibility,
+            hover: HoverVerbosity::default(),
+            formatting: FormattingStyle::default(),
+            revisions: Revisions::default(),
+            queries: Arc::default(),
+        })
+    }
+
+    /// URIs of the files that make up the project.
+    pub fn uris(&self) -> Vec<&Url> {
+        let mut uris = vec![
+            &self.kinetic_model_uri,
+            &self.priors_uri,
+            &self.experiments_uri,
+            &self.config_uri,
+        ];
+        uris.extend(self.inits_uri.as_ref());
+        for uri in self.csv_documents.keys() {
+            if !uris.iter().any(|known| same_file(known, uri)) {
+                uris.push(uri);
+            }
+        }
+        uris
+    }
+
+    /// Whether a document is the file of initial values.
+    fn is_inits(&self, uri: &Url) -> bool {
+        self.inits_uri
+            .as_ref()
+            .is_some_and(|inits| same_file(uri, inits))
+    }
+
+    /// CSV table of a document, if it is one.
+    pub fn csv_document(&self, uri: &Url) -> Option<&CsvTable> {
+        self.csv_documents
+            .iter()
+            .find(|(known, _)| same_file(known, uri))
+            .map(|(_, table)| table.as_ref())
+    }
+
+    /// Identifies the current version of the diagnostics of a file.
+    ///
+    /// It only changes when one of the files the diagnostics depend on does.
+    pub fn result_id(&self, uri: &Url) -> String {
+        let inputs = self.diagnostic_inputs(uri).unwrap_or_default();
+        self.revisions.latest(&inputs).to_string()
+    }
+
+    /// Current contents of one of the project files.
+    fn current_text(&self, uri: &Url) -> Option<&str> {
+        if let Some(table) = self.csv_document(uri) {
+            Some(&table.text)
+        } else if same_file(uri, &self.kinetic_model_uri) {
+            Some(self.kinetic_state.borrow_file_str())
+        } else if same_file(uri, &self.priors_uri) {
+            Some(self.priors_state.borrow_file_str())
+        } else if same_file(uri, &self.experiments_uri) {
+            Some(self.experiments_state.borrow_file_str())
+        } else if same_file(uri, &self.config_uri) {
+            Some(&self.config_text)
+        } else if self.is_inits(uri) {
+            Some(&self.inits_text)
+        } else {
+            None
+        }
+    }
+
+    /// Replace the contents of one of the project files.
+    ///
+    /// The file may be in an invalid state while it is being edited, so the
+    /// data model is only updated if it is valid. Returns whether it was
+    /// updated, which it is not if the text is unchanged or invalid.
+    pub fn update_document(&mut self, uri: &Url, text: String) -> bool {
+        // saving or reopening a file does not change anything
+        if self.current_text(uri) == Some(text.as_str()) {
+            return false;
+        }
+        let in_project = uri
+            .to_file_path()
+            .is_ok_and(|path| path.starts_with(&self.root_dir));
+        if is_csv(Path::new(uri.path())) && in_project {
+            let table = CsvTable::parse(text);
+            if same_file(uri, &self.experiments_uri) {
+                self.experiment_ids = table.experiments();
+                self.revisions.bump(Input::Experiments);
+            }
+            if same_file(uri, &self.priors_uri) {
+                self.revisions.bump(Input::Priors);
+            }
+            self.csv_documents.retain(|known, _| !same_file(known, uri));
+            self.csv_documents.insert(uri.clone(), Arc::new(table));
+            self.revisions.bump(Input::Csv(uri.path().to_string()));
+            true
+        } else if same_file(uri, &self.kinetic_model_uri) {
+            KineticModelState::try_from_str(text)
+                .map(|state| {
+                    self.kinetic_state = Arc::new(state);
+                    self.revisions.bump(Input::KineticModel);
+                })
+                .is_ok()
+        } else if same_file(uri, &self.priors_uri) {
+            PriorsState::try_from_str(text)
+                .map(|state| {
+                    self.priors_state = Arc::new(state);
+                    self.revisions.bump(Input::Priors);
+                })
+                .is_ok()
+        } else if same_file(uri, &self.experiments_uri) {
+            ExperimentsState::try_from_str(text)
+                .map(|state| {
+                    self.experiment_ids = state.experiments();
+                    self.experiments_state = Arc::new(state);
+                    self.revisions.bump(Input::Experiments);
+                })
+                .is_ok()
+        } else if same_file(uri, &self.config_uri) {
+            self.config_text = text;
+            self.revisions.bump(Input::Config);
+            true
+        } else if self.is_inits(uri) {
+            self.inits_text = text;
+            self.revisions.bump(Input::Inits);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Documentation of the key of the config.toml at a position.
+    pub fn config_hover(&self, position: Position) -> Option<String> {
+        config_hover(&self.config_text, position)
+    }
+
+    /// Files referred to by the config.toml that exist, with the range of their paths.
+    pub fn config_links(&self) -> Vec<(lsp_types::Range, Url)> {
+        config_file_links(&self.config_text, &self.root_dir)
+            .into_iter()
+            .filter(|(_, path)| path.exists())
+            .filter_map(|(range, path)| Some((range, Url::from_file_path(path).ok()?)))
+            .collect()
+    }
+
+    /// Directory of the project, where the config.toml is.
+    pub fn root_dir(&self) -> &Path {
+        &self.root_dir
+    }
+
+    /// Apply the settings of the client on top of those of the project, which
+    /// changes the diagnostics. The config file is not reloaded, see [`Self::reload`].
+    pub fn set_settings(&mut self, settings: &Settings) {
+        self.rules = self.project_rules.clone();
+        self.rules.extend(&settings.diagnostics);
+        self.plausibility = self.project_plausibility.clone();
+        self.plausibility.extend(&settings.plausibility);
+        self.hover = settings.hover;
+        self.formatting = settings.formatting;
+        self.revisions.bump(Input::Settings);
+    }
+
+    /// Revision of the project, which changes with any of its inputs.
+    pub fn revision(&self) -> u64 {
+        self.revisions.current()
+    }
+
+    pub fn hover_verbosity(&self) -> HoverVerbosity {
+        self.hover
+    }
+
+    /// Whether `config_file` is the Maud config of the project.
+    pub fn has_config_file(&self, config_file: &str) -> bool {
+        self.config_uri.to_file_path().ok() == Some(self.root_dir.join(config_file))
+    }
+
+    /// Replace the project by another one, e.g. read from another config file,
+    /// keeping the documents opened by the client.
+    ///
+    /// Revisions keep increasing, so that no result id of the old project is reused.
+    pub fn reload(&mut self, mut project: ProjectState) {
+        project.revisions = std::mem::take(&mut self.revisions);
+        for input in [
+            Input::KineticModel,
+            Input::Priors,
+            Input::Experiments,
+            Input::Config,
+            Input::Inits,
+            Input::Settings,
+        ] {
+            project.revisions.bump(input);
+        }
+        for uri in project.csv_documents.keys() {
+            project.revisions.bump(Input::Csv(uri.path().to_string()));
+        }
+        for (uri, text) in std::mem::take(&mut self.open_documents) {
+            project.open_documents.insert(uri.clone(), text.clone());
+            project.update_document(&uri, text);
+        }
+        *self = project;
+    }
+
+    /// Contents of a document, from the client if it is open.
+    pub fn document_text(&self, uri: &Url) -> std::io::Result<String> {
+        match self.open_documents.get(uri) {
+            Some(text) => Ok(text.clone()),
+            None => std::fs::read_to_string(uri.path()),
+        }
+    }
+
+    /// Edits that format a TOML document, or only the entries that touch `range`.
+    pub fn formatting(
+        &self,
+        uri: &Url,
+        range: Option<lsp_types::Range>,
+    ) -> std::io::Result<Vec<TextEdit>> {
+        if is_csv(Path::new(uri.path())) {
+            return Ok(Vec::new());
+        }
+        Ok(formatting_edits(
+            &self.document_text(uri)?,
+            range,
+            &self.formatting,
+        ))
+    }
+
+    /// Read a line of a document, preferring the contents sent by the client.
+    pub fn read_line(&self, uri: &Url, line: u32) -> std::io::Result<String> {
+        match self.open_documents.get(uri) {
+            Some(text) => text
+                .lines()
+                .nth(line as usize)
+                .map(String::from)
+                .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::NotFound)),
+            None => read_line(uri.path(), line),
+        }
+    }
+
+    /// Identifiers of the kinetic model and the experiments.
+    pub fn known_ids(&self) -> Arc<KnownIds> {
+        let inputs = self
+            .revisions
+            .of(&[Input::KineticModel, Input::Experiments]);
+        self.queries
+            .known_ids
+            .get((), inputs, || self.compute_known_ids())
+    }
+
+    fn compute_known_ids(&self) -> KnownIds {
+        let _span = span("index known ids");
+        let kinetic_model = self.kinetic_state.borrow_kinetic_model();
+        let mut known = KnownIds {
+            metabolites: kinetic_model
+                .metabolites
+                .iter()
+                .map(|met| met.id.get_ref().to_string())
+                .collect(),
+            reactions: kinetic_model
+                .reactions
+                .iter()
+                .map(|reac| reac.id.get_ref().to_string())
+                .collect(),
+            enzymes: kinetic_model
+                .enzymes
+                .iter()
+                .map(|enz| enz.id.get_ref().to_string())
+                .collect(),
+            experiments: self.experiment_ids.iter().cloned().collect(),
+            ..Default::default()
+        };
+        let met_comps = kinetic_model
+            .reactions
+            .iter()
+            .flat_map(|reac| reac.stoichiometry.keys().map(|key| key.to_string()))
+            .chain(
+                kinetic_model
+                    .metabolite_in_compartment
+                    .iter()
+                    .map(|mic| format!("{}_{}", mic.metabolite_id.get_ref(), mic.compartment_id)),
+            );
+        known.compartments.extend(
+            kinetic_model
+                .compartments
+                .iter()
+                .map(|comp| comp.id.get_ref().to_string()),
+        );
+        for met_comp in met_comps {
+            if let Some((_, compartment)) = met_comp.rsplit_once('_') {
+                known.compartments.insert(compartment.to_string());
+            }
+            known.met_comps.insert(met_comp);
+        }
+        known
+    }
+
+    /// Parameters that have a prior, from the TOML or the CSV priors.
+    pub fn prior_coverage(&self) -> Arc<PriorCoverage> {
+        let inputs = self.revisions.of(&[Input::Priors]);
+        self.queries
+            .prior_coverage
+            .get((), inputs, || self.compute_prior_coverage())
+    }
+
+    fn compute_prior_coverage(&self) -> PriorCoverage {
+        let _span = span("index prior coverage");
+        match self.csv_document(&self.priors_uri) {
+            Some(table) if table.is_priors() => table.prior_coverage(),
+            _ => PriorCoverage::from(self.priors_state.borrow_priors()),
+        }
+    }
+
+    /// Identifier of the kinetic model under the cursor.
+    pub fn symbol_at(&self, uri: &Url, position: Position) -> std::io::Result<Option<String>> {
+        if let Some(table) = self.csv_document(uri) {
+            return Ok(table
+                .symbol_at(position)
+                .map(|(_, value)| value.to_string()));
+        }
+        let line_str = self.read_line(uri, position.line)?;
+        let column = position.character as usize;
+        if let Some(met_comp) = extract_met_comp(&line_str, column) {
+            if !self.kinetic_state.find_symbol(met_comp).is_empty() {
+                return Ok(Some(met_comp.to_string()));
+            }
+        }
+        Ok(extract_symbol(&line_str, column).map(String::from))
+    }
+
+    /// Completion items at a position of a document.
+    pub fn completions(&self, uri: &Url, position: Position) -> Vec<CompletionItem> {
+        match self.csv_document(uri) {
+            Some(table) => table.completions(position, &self.known_ids()),
+            None => Vec::new(),
+        }
+    }
+
+    /// Inputs that the diagnostics of a file depend on, `None` if it is not
+    /// part of the project.
+    fn diagnostic_inputs(&self, uri: &Url) -> Option<Vec<Input>> {
+        use Input::*;
+        let inputs = if self.csv_document(uri).is_some() {
+            vec![
+                Csv(uri.path().to_string()),
+                KineticModel,
+                Experiments,
+                Settings,
+            ]
+        } else if same_file(uri, &self.kinetic_model_uri) {
+            vec![KineticModel, Priors, Experiments, Settings]
+        } else if same_file(uri, &self.priors_uri) {
+            vec![Priors, KineticModel, Settings]
+        } else if same_file(uri, &self.experiments_uri) {
+            vec![Experiments]
+        } else if same_file(uri, &self.config_uri) {
+            vec![Config, Settings]
+        } else if self.is_inits(uri) {
+            vec![Inits, KineticModel, Experiments, Settings]
+        } else {
+            return None;
+        };
+        Some(inputs)
+    }
+
+    /// Diagnostics of a file, `None` if it is not part of the project.
+    ///
+    /// They are only recomputed if one of the files they depend on changed.
+    pub fn document_diagnostics(&self, uri: &Url) -> Option<Vec<Diagnostic>> {
+        let inputs = self.diagnostic_inputs(uri)?;
+        if same_file(uri, &self.config_uri) {
+            // the config refers to files that may have been created since
+            return self.compute_diagnostics(uri);
+        }
+        let diagnostics = self.queries.diagnostics.get(
+            uri.path().to_string(),
+            self.revisions.of(&inputs),
+            || self.compute_diagnostics(uri).unwrap_or_default(),
+        );
+        Some(diagnostics.to_vec())
+    }
+
+    fn compute_diagnostics(&self, uri: &Url) -> Option<Vec<Diagnostic>> {
+        let _span = span(format!("diagnostics of {}", uri.path()));
+        if let Some(table) = self.csv_document(uri) {
+            let mut diagnostics = table.diagnostics(&self.known_ids());
+            if same_file(uri, &self.priors_uri) && table.is_priors() {
+                diagnostics
+                    .extend(table.prior_pair_diagnostics(self.kinetic_state.borrow_symbols()));
+            }
+            if self.is_inits(uri) {
+                diagnostics.extend(csv_inits_diagnostics(
+                    table,
+                    self.kinetic_state.borrow_symbols(),
+                    &self.known_ids(),
+                ));
+            }
+            return Some(self.rules.apply(&table.text, diagnostics));
+        }
+        if same_file(uri, &self.kinetic_model_uri) {
+            Some(self.rules.apply(
+                self.kinetic_state.borrow_file_str(),
+                self.kinetic_model_diagnostics(),
+            ))
+        } else if same_file(uri, &self.priors_uri) {
+            Some(self.rules.apply(
+                self.priors_state.borrow_file_str(),
+                self.priors_diagnostics(),
+            ))
+        } else if same_file(uri, &self.experiments_uri) {
+            Some(Vec::new())
+        } else if same_file(uri, &self.config_uri) {
+            Some(self.rules.apply(
+                &self.config_text,
+                config_diagnostics(&self.config_text, &self.root_dir),
+            ))
+        } else if self.is_inits(uri) {
+            Some(self.rules.apply(
+                &self.inits_text,
+                inits_diagnostics(
+                    &self.inits_text,
+                    self.kinetic_state.borrow_symbols(),
+                    &self.known_ids(),
+                ),
+            ))
+        } else {
+            None
+        }
+    }
+
+    /// All diagnostics of the kinetic model file.
+    pub fn kinetic_model_diagnostics(&self) -> Vec<Diagnostic> {
+        let text = self.kinetic_state.borrow_file_str();
+        let mut diagnostics = gather_diagnostics(
+            &self.kinetic_state,
+            &self.prior_coverage(),
+            &self.experiment_ids,
+        );
+        diagnostics.extend(
+            kinetic_model_duplicates(&self.kinetic_state)
+                .iter()
+                .map(|dup| dup.to_diagnostic(text, &self.kinetic_model_uri)),
+        );
+        diagnostics.extend(enzyme_reaction_diagnostics(&self.kinetic_state));
+        diagnostics.extend(
+            invalid_identifiers(&self.kinetic_state)
+                .iter()
+                .map(|inv| inv.to_diagnostic(text, &self.kinetic_model_uri)),
+        );
+        diagnostics
+    }
+
+    /// All diagnostics of the priors file.
+    pub fn priors_diagnostics(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = gather_diagnostics_priors(&self.priors_state);
+        diagnostics.extend(self.plausibility.diagnostics(
+            self.priors_state.borrow_file_str(),
+            self.priors_state.borrow_priors(),
+        ));
+        diagnostics.extend(prior_pair_diagnostics(
+            self.priors_state.borrow_file_str(),
+            self.priors_state.borrow_priors(),
+            self.kinetic_state.borrow_symbols(),
+        ));
+        diagnostics.extend(
+            priors_duplicates(&self.priors_state).iter().map(|dup| {
+                dup.to_diagnostic(self.priors_state.borrow_file_str(), &self.priors_uri)
+            }),
+        );
+        diagnostics
+    }
+
+    /// Quick fixes available for a range of a document.
+    pub fn code_actions(&self, uri: &Url, range: &lsp_types::Range) -> Vec<CodeActionOrCommand> {
+        let mut actions = self.fix_actions(uri, range);
+        let reorganizable = [
+            &self.kinetic_model_uri,
+            &self.priors_uri,
+            &self.experiments_uri,
+        ]
+        .into_iter()
+        .any(|file| same_file(uri, file));
+        if reorganizable && !is_csv(Path::new(uri.path())) {
+            if let Ok(text) = self.document_text(uri) {
+                if same_file(uri, &self.priors_uri) {
+                    actions.extend(percentile_actions(&text, uri, range));
+                }
+                actions.extend(reorganize_actions(&text, uri, range, &self.formatting));
+            }
+        }
+        actions
+    }
+
+    /// Quick fixes of duplicated and invalid identifiers.
+    fn fix_actions(&self, uri: &Url, range: &lsp_types::Range) -> Vec<CodeActionOrCommand> {
+        if same_file(uri, &self.kinetic_model_uri) {
+            let text = self.kinetic_state.borrow_file_str();
+            // every document that may refer to the entities of the kinetic model
+            let mut documents = vec![Document::Toml(&self.kinetic_model_uri, text)];
+            // the priors or experiments may be CSV tables, which are listed below
+            for (doc_uri, doc_text) in [
+                (&self.priors_uri, self.priors_state.borrow_file_str()),
+                (
+                    &self.experiments_uri,
+                    self.experiments_state.borrow_file_str(),
+                ),
+            ] {
+                if self.csv_document(doc_uri).is_none() {
+                    documents.push(Document::Toml(doc_uri, doc_text));
+                }
+            }
+            documents.extend(
+                self.inits_uri
+                    .iter()
+                    .filter(|inits_uri| self.csv_document(inits_uri).is_none())
+                    .map(|inits_uri| Document::Toml(inits_uri, &self.inits_text)),
+            );
+            documents.extend(
+                self.csv_documents
+                    .iter()
+                    .map(|(csv_uri, table)| Document::Csv(csv_uri, table)),
+            );
+            let symbols = self.kinetic_state.borrow_symbols();
+            kinetic_model_duplicates(&self.kinetic_state)
+                .iter()
+                .filter(|dup| dup.intersects(text, range))
+                .map(|dup| dup.to_code_action(text, uri))
+                .chain(
+                    invalid_identifiers(&self.kinetic_state)
+                        .iter()
+                        .filter(|inv| inv.intersects(text, range))
+                        .map(|inv| {
+                            inv.to_code_action(&documents, symbols.kinds_named(&inv.id) > 1)
+                        }),
+                )
+                .collect()
+        } else if same_file(uri, &self.priors_uri) {
+            let text = self.priors_state.borrow_file_str();
+            priors_duplicates(&self.priors_state)
+                .iter()
+                .filter(|dup| dup.intersects(text, range))
+                .map(|dup| dup.to_code_action(text, uri))
+                .collect()
+        } else {
+            Vec::new()
+        }
+    }
+}
+
 pub fn gather_diagnostics(
     kinetic_state: &KineticModelState,
-    priors: &PriorsState,
+    priors: &PriorCoverage,
     experiments: &[String],
 ) -> Vec<Diagnostic> {
     let kinetic_model = kinetic_state.borrow_kinetic_model();
-    let priors = priors.borrow_priors();
-    // let compartments = kinetic_model.metabolites
+    let lines = LineIndex::new(kinetic_state.borrow_file_str());
+    // links between enzymes and reactions are indexed once, not searched for
+    // every entity, so that genome-scale models stay fast
+    // a reaction may be catalyzed by several isoenzymes
+    let symbols = kinetic_state.borrow_symbols();
+    let mut kms_of_enzyme: HashMap<&str, HashSet<&str>> = HashMap::new();
+    for (enzyme, met_comp) in priors.km.iter() {
+        kms_of_enzyme
+            .entry(enzyme.as_str())
+            .or_default()
+            .insert(met_comp.as_str());
+    }
     // offset to apply to the diagnostic range ("id = ")
     // check that all reactions have a corresponding enzyme
     kinetic_model
         .reactions
         .iter()
         .filter(|reac| !matches!(reac.mechanism, ReactionMechanism::Drain))
-        .filter(|reac| {
-            kinetic_model
-                .enzyme_reaction
-                .iter()
-                .all(|er| er.reaction_id != reac.id.clone().into_inner())
-        })
+        .filter(|reac| symbols.enzymes_of_reaction(reac.id.get_ref()).is_empty())
         .map(|reac| {
-            let result_line = span_to_line_number(kinetic_state.borrow_file_str(), reac.span()) - 1;
+            let result_line = lines.line_number(reac.span()) - 1;
             let span = reac.id.span();
             let end = (span.1 - span.0) as u32;
             Diagnostic {
@@ -233,7 +1024,7 @@ pub fn gather_diagnostics(
                     },
                 },
                 severity: Some(lsp_types::DiagnosticSeverity::ERROR),
-                code: Some(lsp_types::NumberOrString::Number(0)),
+                code: Some(Rule::MissingEnzyme.diagnostic_code()),
                 message: "Missing enzyme for reaction.".to_string(),
                 ..Default::default()
             }
@@ -247,14 +1038,12 @@ pub fn gather_diagnostics(
                 // cartesian product with experiments
                 .flat_map(|x| experiments.iter().map(move |y| (x, y)))
                 .filter(|(reac, exp)| {
-                    !priors.drain.iter().any(|drain| {
-                        drain.get_ref().reaction.as_str() == *reac.id.get_ref()
-                            && **exp == drain.get_ref().experiment
-                    })
+                    !priors
+                        .drain
+                        .contains(&(reac.id.get_ref().to_string(), exp.to_string()))
                 })
                 .map(|(reac, experiment)| {
-                    let result_line =
-                        span_to_line_number(kinetic_state.borrow_file_str(), reac.span()) - 1;
+                    let result_line = lines.line_number(reac.span()) - 1;
                     let span = reac.id.span();
                     let end = (span.1 - span.0) as u32;
                     Diagnostic {
@@ -269,7 +1058,7 @@ pub fn gather_diagnostics(
                             },
                         },
                         severity: Some(lsp_types::DiagnosticSeverity::WARNING),
-                        code: Some(lsp_types::NumberOrString::Number(0)),
+                        code: Some(Rule::MissingDrainPrior.diagnostic_code()),
                         message: format!("Missing prior for experiment '{experiment}'"),
                         ..Default::default()
                     }
@@ -282,14 +1071,12 @@ pub fn gather_diagnostics(
                 .iter()
                 .flat_map(|x| experiments.iter().map(move |y| (x, y)))
                 .filter(|(enz, exp)| {
-                    !priors.conc_enzyme.iter().any(|conc| {
-                        &conc.get_ref().enzyme.as_str() == enz.id.get_ref()
-                            && &conc.get_ref().experiment.as_str() == exp
-                    })
+                    !priors
+                        .conc_enzyme
+                        .contains(&(enz.id.get_ref().to_string(), exp.to_string()))
                 })
                 .map(|(enz, exp)| {
-                    let result_line =
-                        span_to_line_number(kinetic_state.borrow_file_str(), &enz.id) - 1;
+                    let result_line = lines.line_number(&enz.id) - 1;
                     let span = enz.id.span();
                     let end = (span.1 - span.0) as u32;
                     Diagnostic {
@@ -304,7 +1091,7 @@ pub fn gather_diagnostics(
                             },
                         },
                         severity: Some(lsp_types::DiagnosticSeverity::WARNING),
-                        code: Some(lsp_types::NumberOrString::Number(0)),
+                        code: Some(Rule::MissingEnzymeConcentration.diagnostic_code()),
                         message: format!("Missing concentration prior for experiment {exp}."),
                         ..Default::default()
                     }
@@ -315,15 +1102,9 @@ pub fn gather_diagnostics(
             kinetic_model
                 .enzymes
                 .iter()
-                .filter(|reac| {
-                    kinetic_model
-                        .enzyme_reaction
-                        .iter()
-                        .all(|er| er.enzyme_id != reac.id.clone().into_inner())
-                })
+                .filter(|enz| symbols.reactions_of_enzyme(enz.id.get_ref()).is_empty())
                 .map(|enz: &crate::maud_data::Enzyme| {
-                    let result_line =
-                        span_to_line_number(kinetic_state.borrow_file_str(), &enz.id) - 1;
+                    let result_line = lines.line_number(&enz.id) - 1;
                     let span = enz.id.span();
                     let end = (span.1 - span.0) as u32;
                     Diagnostic {
@@ -338,27 +1119,39 @@ pub fn gather_diagnostics(
                             },
                         },
                         severity: Some(lsp_types::DiagnosticSeverity::ERROR),
-                        code: Some(lsp_types::NumberOrString::Number(0)),
-                        message: "Missing kcat for reaction.".to_string(),
+                        code: Some(Rule::EnzymeWithoutReaction.diagnostic_code()),
+                        message:
+                            "Enzyme does not catalyze any reaction: add it to enzyme_reaction."
+                                .to_string(),
                         ..Default::default()
                     }
                 }),
         )
         .chain(
-            // check that all reactions have a corresponding kcat
+            // check that every enzyme of a reaction has a kcat for it
             kinetic_model
                 .reactions
                 .iter()
                 .filter(|reac| !matches!(reac.mechanism, ReactionMechanism::Drain))
-                .filter(|reac| {
-                    priors
-                        .kcat
+                .filter_map(|reac| {
+                    let enzymes = symbols.enzymes_of_reaction(reac.id.get_ref());
+                    let missing_kcat = enzymes
                         .iter()
-                        .all(|kc| &kc.get_ref().reaction.as_str() != reac.id.get_ref())
+                        .filter(|enzyme| {
+                            !priors
+                                .kcat
+                                .contains(&(enzyme.to_string(), reac.id.get_ref().to_string()))
+                        })
+                        .copied()
+                        .collect::<Vec<_>>();
+                    if missing_kcat.is_empty() {
+                        None
+                    } else {
+                        Some((reac, missing_kcat))
+                    }
                 })
-                .map(|reac| {
-                    let result_line =
-                        span_to_line_number(kinetic_state.borrow_file_str(), reac.span()) - 1;
+                .map(|(reac, missing_kcat)| {
+                    let result_line = lines.line_number(reac.span()) - 1;
                     let span = reac.id.span();
                     let end = (span.1 - span.0) as u32;
                     Diagnostic {
@@ -373,8 +1166,12 @@ pub fn gather_diagnostics(
                             },
                         },
                         severity: Some(lsp_types::DiagnosticSeverity::ERROR),
-                        code: Some(lsp_types::NumberOrString::Number(0)),
-                        message: "Missing kcat for reaction!".to_string(),
+                        code: Some(Rule::MissingKcat.diagnostic_code()),
+                        message: format!(
+                            "Missing kcat of reaction {} for enzymes {:?}.",
+                            reac.id.get_ref(),
+                            missing_kcat
+                        ),
                         ..Default::default()
                     }
                 }),
@@ -385,44 +1182,28 @@ pub fn gather_diagnostics(
                 .reactions
                 .iter()
                 .filter(|reac| !matches!(reac.mechanism, ReactionMechanism::Drain))
-                .map(|reac| {
-                    (
-                        reac,
-                        kinetic_model
-                            .enzyme_reaction
-                            .iter()
-                            .find(|er| er.reaction_id == reac.id.clone().into_inner()),
-                        reac.stoichiometry.keys().copied().collect::<HashSet<_>>(),
-                    )
+                .flat_map(|reac| {
+                    let enzymes = symbols.enzymes_of_reaction(reac.id.get_ref());
+                    enzymes.iter().map(move |enzyme| (reac, enzyme))
                 })
-                .filter(|(_, er, _)| er.is_some())
-                .filter_map(|(reac, er, st)| {
-                    let er = er.as_ref().unwrap();
-                    let defined_km = priors
-                        .km
-                        .iter()
-                        .filter(|km| km.get_ref().enzyme == er.enzyme_id)
-                        .map(|km| {
-                            format!("{}_{}", km.get_ref().metabolite, km.get_ref().compartment)
-                        })
-                        .collect::<HashSet<_>>();
-                    let def_km = defined_km
-                        .iter()
-                        .map(|x| x.as_str())
-                        .collect::<HashSet<_>>();
-                    let missing_km = st
-                        .difference(&def_km)
+                .filter_map(|(reac, enzyme)| {
+                    let def_km = kms_of_enzyme.get(enzyme);
+                    let mut missing_km = reac
+                        .stoichiometry
+                        .keys()
+                        .copied()
+                        .filter(|met_comp| def_km.is_none_or(|kms| !kms.contains(met_comp)))
                         .map(|x| x.to_string())
                         .collect::<Vec<_>>();
+                    missing_km.sort();
                     if missing_km.is_empty() {
                         None
                     } else {
-                        Some((reac, missing_km))
+                        Some((reac, enzyme, missing_km))
                     }
                 })
-                .map(|(reac, missing_km)| {
-                    let result_line =
-                        span_to_line_number(kinetic_state.borrow_file_str(), reac.span()) - 1;
+                .map(|(reac, enzyme, missing_km)| {
+                    let result_line = lines.line_number(reac.span()) - 1;
                     let span = reac.id.span();
                     let end = (span.1 - span.0) as u32;
                     Diagnostic {
@@ -437,10 +1218,10 @@ pub fn gather_diagnostics(
                             },
                         },
                         severity: Some(lsp_types::DiagnosticSeverity::ERROR),
-                        code: Some(lsp_types::NumberOrString::Number(0)),
+                        code: Some(Rule::MissingKm.diagnostic_code()),
                         message: format!(
-                            "Missing kms for reaction {}: {:?}.",
-                            reac.id.clone().into_inner(),
+                            "Missing kms of enzyme {enzyme} for reaction {}: {:?}.",
+                            reac.id.get_ref(),
                             missing_km
                         ),
                         ..Default::default()
@@ -450,27 +1231,40 @@ pub fn gather_diagnostics(
         .collect()
 }
 
+/// Line, span, error and warning (with its rule) of a prior.
+type PriorInfo<'a> = (
+    usize,
+    (usize, usize),
+    Option<&'a str>,
+    Option<(Rule, &'a str)>,
+);
+
 fn get_prior_info<'a, P: Prior>(
-    priors_state: &'a PriorsState,
+    lines: &'a LineIndex,
     priors: &'a [Spanned<P>],
-) -> impl Iterator<Item = (usize, (usize, usize), Option<&'a str>, Option<&'a str>)> {
+) -> impl Iterator<Item = PriorInfo<'a>> {
     priors.iter().map(|prior| {
-        let result_line = span_to_line_number(priors_state.borrow_file_str(), prior) - 1;
+        let result_line = lines.line_number(prior).saturating_sub(1);
         let span = prior.span();
         (
             result_line,
             span,
             prior.get_ref().incomplete(),
-            prior.get_ref().inconsistent(),
+            prior
+                .get_ref()
+                .inconsistent()
+                .map(|warn| (Rule::InconsistentPrior, warn)),
         )
     })
 }
 
 pub fn gather_diagnostics_priors(priors_state: &PriorsState) -> Vec<Diagnostic> {
-    let km_info = get_prior_info(priors_state, &priors_state.borrow_priors().km);
-    let kcat_info = get_prior_info(priors_state, &priors_state.borrow_priors().kcat);
-    let enzyme_info = get_prior_info(priors_state, &priors_state.borrow_priors().conc_enzyme);
-    let drain_info = get_prior_info(priors_state, &priors_state.borrow_priors().drain);
+    let lines = LineIndex::new(priors_state.borrow_file_str());
+    let km_info = get_prior_info(&lines, &priors_state.borrow_priors().km);
+    let ki_info = get_prior_info(&lines, &priors_state.borrow_priors().ki);
+    let kcat_info = get_prior_info(&lines, &priors_state.borrow_priors().kcat);
+    let enzyme_info = get_prior_info(&lines, &priors_state.borrow_priors().conc_enzyme);
+    let drain_info = get_prior_info(&lines, &priors_state.borrow_priors().drain);
     let mut min_concentrations = std::collections::HashMap::new();
     for conc in priors_state
         .borrow_priors()
@@ -487,6 +1281,7 @@ pub fn gather_diagnostics_priors(priors_state: &PriorsState) -> Vec<Diagnostic>
     }
 
     km_info
+        .chain(ki_info)
         .chain(kcat_info)
         .chain(enzyme_info)
         .chain(drain_info)
@@ -496,14 +1291,17 @@ pub fn gather_diagnostics_priors(priors_state: &PriorsState) -> Vec<Diagnostic>
                 km_ref.mean(),
                 min_concentrations.get(&(&km_ref.metabolite, &km_ref.compartment)),
             ) {
-                let result_line = span_to_line_number(priors_state.borrow_file_str(), km) - 1;
+                let result_line = lines.line_number(km).saturating_sub(1);
                 let span = km.span();
                 (
                     result_line,
                     span,
                     None,
                     if prior_mean > *conc_mean {
-                        Some("Km > mean of unbalanced concentration")
+                        Some((
+                            Rule::KmAboveConcentration,
+                            "Km > mean of unbalanced concentration",
+                        ))
                     } else {
                         None
                     },
@@ -514,13 +1312,16 @@ pub fn gather_diagnostics_priors(priors_state: &PriorsState) -> Vec<Diagnostic>
         }))
         .chain([&priors_state.borrow_priors().dgf].iter().map(|m_prior| {
             if let Some(prior) = m_prior {
-                let result_line = span_to_line_number(priors_state.borrow_file_str(), prior) - 1;
+                let result_line = lines.line_number(prior).saturating_sub(1);
                 let span = prior.span();
                 (
                     result_line,
                     span,
                     prior.get_ref().incomplete(),
-                    prior.get_ref().inconsistent(),
+                    prior
+                        .get_ref()
+                        .inconsistent()
+                        .map(|warn| (Rule::InconsistentPrior, warn)),
                 )
             } else {
                 (0, (0, 0), None, None)
@@ -542,11 +1343,11 @@ pub fn gather_diagnostics_priors(priors_state: &PriorsState) -> Vec<Diagnostic>
                         },
                     },
                     severity: Some(lsp_types::DiagnosticSeverity::ERROR),
-                    code: Some(lsp_types::NumberOrString::Number(1)),
+                    code: Some(Rule::IncompletePrior.diagnostic_code()),
                     message: err.to_string(),
                     ..Default::default()
                 }),
-                warn.map(|warn| Diagnostic {
+                warn.map(|(warn_rule, warn)| Diagnostic {
                     range: lsp_types::Range {
                         start: Position {
                             line: result_line as u32,
@@ -558,7 +1359,7 @@ pub fn gather_diagnostics_priors(priors_state: &PriorsState) -> Vec<Diagnostic>
                         },
                     },
                     severity: Some(lsp_types::DiagnosticSeverity::WARNING),
-                    code: Some(lsp_types::NumberOrString::Number(1)),
+                    code: Some(warn_rule.diagnostic_code()),
                     message: warn.to_string(),
                     ..Default::default()
                 }),
@@ -571,7 +1372,8 @@ pub fn gather_diagnostics_priors(priors_state: &PriorsState) -> Vec<Diagnostic>
 
 #[cfg(test)]
 mod tests {
-    use super::KineticModelState;
+    use super::*;
+    use crate::maud_data::MaudConfig;
 
     #[test]
     fn finds_line_of_met_symbol() {
@@ -580,7 +1382,148 @@ mod tests {
                 .unwrap()
                 .join("tests/mock/ecoli_kinetic_model.toml"),
         );
-        assert_eq!(kinetic_model_state.find_symbol_line("g3p"), Some(9));
-        assert_eq!(kinetic_model_state.find_symbol_line("g6p"), Some(2))
+        assert_eq!(kinetic_model_state.find_symbol_lines("g3p"), [9]);
+        assert_eq!(kinetic_model_state.find_symbol_lines("g6p"), [2])
+    }
+
+    #[test]
+    fn line_index_agrees_with_scanning_the_file() {
+        let kinetic_state = KineticModelState::from_path(
+            std::env::current_dir()
+                .unwrap()
+                .join("tests/mock/ecoli_kinetic_model.toml"),
+        );
+        let text = kinetic_state.borrow_file_str();
+        let lines = LineIndex::new(text);
+        let kinetic_model = kinetic_state.borrow_kinetic_model();
+        for reac in kinetic_model.reactions.iter() {
+            assert_eq!(
+                lines.line_number(reac.span()),
+                span_to_line_number(text, reac.span())
+            );
+        }
+        for enz in kinetic_model.enzymes.iter() {
+            assert_eq!(
+                lines.line_number(&enz.id),
+                span_to_line_number(text, &enz.id)
+            );
+        }
+    }
+
+    #[test]
+    fn edits_only_change_the_results_that_depend_on_them() {
+        let root_dir = std::env::current_dir().unwrap().join("tests/mock");
+        let config: MaudConfig =
+            toml::from_str(&std::fs::read_to_string(root_dir.join("config.toml")).unwrap())
+                .unwrap();
+        let mut project = ProjectState::try_from_config(&root_dir, &config).unwrap();
+        let (kinetic_uri, priors_uri) = (
+            project.kinetic_model_uri.clone(),
+            project.priors_uri.clone(),
+        );
+        let kinetic_id = project.result_id(&kinetic_uri);
+        let priors_id = project.result_id(&priors_uri);
+        let config_id = project.result_id(&project.config_uri.clone());
+        let before = project.document_diagnostics(&kinetic_uri).unwrap();
+        let text = project.document_text(&priors_uri).unwrap();
+        // saving without changes does not change anything
+        assert!(!project.update_document(&priors_uri, text.clone()));
+        assert_eq!(project.result_id(&priors_uri), priors_id);
+        // removing the kcat prior of PGI is reported in the kinetic model
+        let without_pgi = text.replace("reaction = \"PGI\"", "reaction = \"TPI\"");
+        assert!(project.update_document(&priors_uri, without_pgi));
+        assert_ne!(project.result_id(&priors_uri), priors_id);
+        assert_ne!(project.result_id(&kinetic_uri), kinetic_id);
+        assert_eq!(project.result_id(&project.config_uri.clone()), config_id);
+        let after = project.document_diagnostics(&kinetic_uri).unwrap();
+        assert!(after.len() > before.len());
+    }
+
+    #[test]
+    fn isoenzymes_each_need_their_kcat_and_kms() {
+        let kinetic_state = KineticModelState::try_from_str(
+            r#"
+metabolite = [
+  {id = "g6p", name = "D-Glucose 6-phosphate", inchi_key = "A"},
+  {id = "f6p", name = "D-Fructose 6-phosphate", inchi_key = "B"},
+]
+enzyme = [
+  {id = "pgi1", name = "pgi1", subunits = 1},
+  {id = "pgi2", name = "pgi2", subunits = 1},
+]
+enzyme_reaction = [
+  {enzyme_id = "pgi1", reaction_id = "PGI"},
+  {enzyme_id = "pgi2", reaction_id = "PGI"},
+]
+
+[[reaction]]
+id = "PGI"
+name = "Glucose-6-phosphate isomerase"
+stoichiometry = {g6p_c = -1, f6p_c = 1}
+mechanism = "reversible_michaelis_menten"
+"#
+            .to_string(),
+        )
+        .unwrap();
+        let pair = |a: &str, b: &str| (a.to_string(), b.to_string());
+        let coverage = PriorCoverage {
+            kcat: HashSet::from([pair("pgi1", "PGI")]),
+            km: HashSet::from([
+                pair("pgi1", "g6p_c"),
+                pair("pgi1", "f6p_c"),
+                pair("pgi2", "g6p_c"),
+            ]),
+            ..Default::default()
+        };
+        let messages: Vec<String> = gather_diagnostics(&kinetic_state, &coverage, &[])
+            .into_iter()
+            .map(|diag| diag.message)
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "Missing kcat of reaction PGI for enzymes [\"pgi2\"].",
+                "Missing kms of enzyme pgi2 for reaction PGI: [\"f6p_c\"]."
+            ]
+        );
+    }
+
+    #[test]
+    fn reloading_keeps_open_documents_and_new_result_ids() {
+        let root_dir = std::env::current_dir().unwrap().join("tests/mock");
+        let mut project = ProjectState::load(&root_dir, "config.toml").unwrap();
+        assert!(project.has_config_file("config.toml"));
+        let priors_uri = project.priors_uri.clone();
+        let priors_id = project.result_id(&priors_uri);
+        let text = project.document_text(&priors_uri).unwrap() + "\n# edited\n";
+        project
+            .open_documents
+            .insert(priors_uri.clone(), text.clone());
+        project.update_document(&priors_uri, text.clone());
+        let edited_id = project.result_id(&priors_uri);
+        project.reload(ProjectState::load(&root_dir, "config.toml").unwrap());
+        assert_eq!(project.document_text(&priors_uri).unwrap(), text);
+        assert_eq!(project.priors_state.borrow_file_str(), &text);
+        let reloaded_id = project.result_id(&priors_uri);
+        assert!(![priors_id, edited_id].contains(&reloaded_id));
+        assert!(ProjectState::load(&root_dir, "missing.toml").is_err());
+    }
+
+    #[test]
+    fn revision_only_changes_with_the_inputs() {
+        let root_dir = std::env::current_dir().unwrap().join("tests/mock");
+        let mut project = ProjectState::load(&root_dir, "config.toml").unwrap();
+        let revision = project.revision();
+        let priors_uri = project.priors_uri.clone();
+        let text = project.document_text(&priors_uri).unwrap();
+        // saving without changes
+        project.update_document(&priors_uri, text.clone());
+        let other_uri = Url::from_file_path(root_dir.join("notes.toml")).unwrap();
+        project.update_document(&other_uri, "a = 1".to_string());
+        // an invalid document is not used
+        project.update_document(&priors_uri, "kcat = [".to_string());
+        assert_eq!(project.revision(), revision);
+        project.update_document(&priors_uri, text + "\n# edited\n");
+        assert!(project.revision() > revision);
     }
 }
@@ -1,3 +1,6 @@
+use crate::csv_data::ColumnKind;
+use crate::formatting::split_comment;
+
 /// Parse a line and return the symbol under the cursor.
 pub fn extract_symbol(line: &str, column: usize) -> Option<&str> {
     let (before, after) = line.split_at(column);
@@ -26,3 +29,285 @@ pub fn extract_symbol(line: &str, column: usize) -> Option<&str> {
         None
     }
 }
+
+/// Parse a line and return the metabolite in compartment (`g6p_c`) under the cursor.
+///
+/// Unlike [`extract_symbol`], the identifier is not split on underscores.
+pub fn extract_met_comp(line: &str, column: usize) -> Option<&str> {
+    let is_id = |c: char| c.is_alphanumeric() || c == '_';
+    let column = column.min(line.len());
+    let start = line
+        .get(..column)?
+        .rfind(|c: char| !is_id(c))
+        .map_or(0, |i| i + 1);
+    let end = line
+        .get(column..)?
+        .find(|c: char| !is_id(c))
+        .map_or(line.len(), |i| column + i);
+    let word = line.get(start..end)?;
+    (word.contains('_') && !word.starts_with('_') && !word.ends_with('_')).then_some(word)
+}
+
+/// Find the byte range of the TOML entry that encloses `offset`.
+///
+/// This is either the inline table (`{...}`) around it or, if there is none,
+/// the `[[table]]` block that contains it (from its header up to the next header).
+pub fn entry_span(text: &str, offset: usize) -> (usize, usize) {
+    match inline_table_span(text, offset) {
+        Some(span) => span,
+        None => table_block_span(text, offset),
+    }
+}
+
+/// Byte range to delete in order to remove the entry enclosing `offset`.
+///
+/// Inline tables sitting on their own line are removed with the whole line,
+/// so that the file is left without blank lines or dangling commas.
+pub fn entry_removal_span(text: &str, offset: usize) -> (usize, usize) {
+    let (start, end) = entry_span(text, offset);
+    if text[start..].starts_with("[[") {
+        return (start, end);
+    }
+    let line_start = text[..start].rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let line_end = text[end..]
+        .find('\n')
+        .map(|i| end + i + 1)
+        .unwrap_or(text.len());
+    let before = &text[line_start..start];
+    let after = text[end..line_end].trim_start().trim_start_matches(',');
+    let after = after.trim();
+    if before.trim().is_empty() && (after.is_empty() || after.starts_with('#')) {
+        (line_start, line_end)
+    } else {
+        let rest = &text[end..];
+        let comma = rest
+            .find(|c: char| !c.is_whitespace())
+            .filter(|&i| rest[i..].starts_with(','))
+            .map(|i| {
+                let after_comma = &rest[i + 1..];
+                i + 1 + after_comma.len() - after_comma.trim_start_matches(' ').len()
+            })
+            .unwrap_or(0);
+        (start, end + comma)
+    }
+}
+
+fn inline_table_span(text: &str, offset: usize) -> Option<(usize, usize)> {
+    let mut depth = 0usize;
+    let mut start = None;
+    let scan_end = if text[offset..].starts_with('{') {
+        offset + 1
+    } else {
+        offset
+    };
+    for (i, c) in text[..scan_end].char_indices().rev() {
+        match c {
+            '}' => depth += 1,
+            '{' if depth == 0 => {
+                start = Some(i);
+                break;
+            }
+            '{' => depth -= 1,
+            '[' if text[..i].ends_with('\n') || i == 0 => return None,
+            _ => (),
+        }
+    }
+    let start = start?;
+    let mut depth = 0usize;
+    let mut in_str = false;
+    for (i, c) in text[start..].char_indices() {
+        match c {
+            '"' => in_str = !in_str,
+            '{' if !in_str => depth += 1,
+            '}' if !in_str => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some((start, start + i + 1));
+                }
+            }
+            _ => (),
+        }
+    }
+    None
+}
+
+fn table_block_span(text: &str, offset: usize) -> (usize, usize) {
+    let mut start = 0;
+    let mut end = text.len();
+    let mut line_start = 0;
+    for line in text.split_inclusive('\n') {
+        let is_header = line.trim_start().starts_with('[');
+        if line_start <= offset {
+            if is_header {
+                start = line_start;
+            }
+        } else if is_header {
+            end = line_start;
+            break;
+        }
+        line_start += line.len();
+    }
+    (start, end)
+}
+
+/// Find the byte spans of every reference to `id` in a Maud TOML file.
+///
+/// References are either string values equal to the identifier (`"g6p"`) or
+/// metabolite-in-compartment keys of stoichiometries (`g6p_c = -1`), in which
+/// case only the metabolite or the compartment part is returned. Comments are
+/// ignored. [`reference_kind`] tells what each of them refers to.
+pub fn find_references(text: &str, id: &str) -> Vec<(usize, usize)> {
+    if id.is_empty() {
+        return Vec::new();
+    }
+    text.match_indices(id)
+        .map(|(start, _)| (start, start + id.len()))
+        .filter(|&(start, _)| {
+            // a comment of the line starts before the reference
+            let line_start = text[..start].rfind('\n').map_or(0, |i| i + 1);
+            split_comment(&text[line_start..start]).1.is_none()
+        })
+        .filter(|&(start, end)| {
+            let before = text[..start].chars().next_back();
+            let after = &text[end..];
+            let quoted = before == Some('"') && after.starts_with('"');
+            quoted
+                || (matches!(before, Some('{' | ',' | '"' | ' ' | '\t'))
+                    && is_compartment_suffix(after))
+                || (before == Some('_') && is_key_end(after))
+        })
+        .collect()
+}
+
+/// What a reference found by [`find_references`] refers to, from the key of its
+/// value or, for `id`, from the table it is in. `None` if the context does not tell.
+pub fn reference_kind(text: &str, (start, end): (usize, usize)) -> Option<ColumnKind> {
+    let before = text[..start].chars().next_back();
+    if !(before == Some('"') && text[end..].starts_with('"')) {
+        // part of a metabolite-in-compartment key, e.g. `g6p_c = -1`
+        return Some(if before == Some('_') {
+            ColumnKind::Compartment
+        } else {
+            ColumnKind::Metabolite
+        });
+    }
+    let key = key_of_value(text, start)?;
+    match ColumnKind::from_header(key) {
+        ColumnKind::Other if key == "id" => match enclosing_table(text, start)? {
+            "metabolite" => Some(ColumnKind::Metabolite),
+            "reaction" => Some(ColumnKind::Reaction),
+            "enzyme" => Some(ColumnKind::Enzyme),
+            "compartment" => Some(ColumnKind::Compartment),
+            _ => None,
+        },
+        kind @ (ColumnKind::Metabolite
+        | ColumnKind::Compartment
+        | ColumnKind::MetComp
+        | ColumnKind::Enzyme
+        | ColumnKind::Reaction
+        | ColumnKind::Experiment) => Some(kind),
+        _ => None,
+    }
+}
+
+/// Key of the string value that starts at `start`, e.g. `enzyme_id` in
+/// `enzyme_id = "E1"`, or `ids` for an element of `ids = ["g6p", "f6p"]`.
+fn key_of_value(text: &str, start: usize) -> Option<&str> {
+    let before = text[..start].strip_suffix('"')?.trim_end();
+    let before = match before.strip_suffix('=') {
+        Some(before) => before,
+        None if before.ends_with([',', '[']) => {
+            let open = before.rfind('[')?;
+            text[..open].trim_end().strip_suffix('=')?
+        }
+        None => return None,
+    }
+    .trim_end();
+    let key_start = before
+        .rfind(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '"')))
+        .map_or(0, |i| i + 1);
+    Some(before[key_start..].trim_matches('"'))
+}
+
+/// Name of the `[[table]]` or top-level array of tables (`enzyme = [`) that
+/// contains `offset`.
+fn enclosing_table(text: &str, offset: usize) -> Option<&str> {
+    text[..offset]
+        .lines()
+        .rev()
+        .enumerate()
+        .filter(|(_, line)| !line.starts_with(char::is_whitespace))
+        .find_map(|(i, line)| {
+            if let Some(header) = line.strip_prefix('[') {
+                return header.trim_start_matches('[').split(']').next();
+            }
+            let (key, value) = line.split_once('=')?;
+            let value = value.trim();
+            // arrays closed on a line before the reference do not contain it
+            (value.starts_with('[') && (i == 0 || !value.ends_with(']'))).then(|| key.trim())
+        })
+        .map(str::trim)
+}
+
+/// Whether `rest` ends a key, like the `" =` after `"g6p_c`.
+fn is_key_end(rest: &str) -> bool {
+    rest.trim_start_matches('"').trim_start().starts_with('=')
+}
+
+/// Whether `rest` looks like `_<compartment> =` (possibly quoted).
+fn is_compartment_suffix(rest: &str) -> bool {
+    let Some(rest) = rest.strip_prefix('_') else {
+        return false;
+    };
+    let comp_len = rest
+        .find(|c: char| !c.is_ascii_alphanumeric())
+        .unwrap_or(rest.len());
+    comp_len > 0
+        && rest[comp_len..]
+            .trim_start_matches('"')
+            .trim_start()
+            .starts_with('=')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn references_are_told_apart_by_kind() {
+        let text = r#"enzyme = [{name = 'pgi #1', id = "PGI", subunits = 1}]
+
+[[reaction]]
+id = "PGI"
+stoichiometry = {g6p_c = -1, f6p_c = 1}
+
+[[enzyme_reaction]]
+enzyme_id = "PGI"
+reaction_id = "PGI" # not "PGI"
+"#;
+        // a `#` in a string does not start a comment
+        let kinds: Vec<_> = find_references(text, "PGI")
+            .into_iter()
+            .map(|span| reference_kind(text, span))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                Some(ColumnKind::Enzyme),
+                Some(ColumnKind::Reaction),
+                Some(ColumnKind::Enzyme),
+                Some(ColumnKind::Reaction)
+            ]
+        );
+        let compartments = find_references(text, "c");
+        assert_eq!(compartments.len(), 2);
+        assert!(compartments
+            .iter()
+            .all(|&span| reference_kind(text, span) == Some(ColumnKind::Compartment)));
+        let priors = r#"[dgf]
+ids = ["g6p", "f6p"]
+"#;
+        let span = find_references(priors, "f6p")[0];
+        assert_eq!(reference_kind(priors, span), None);
+    }
+}
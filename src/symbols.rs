@@ -0,0 +1,376 @@
+//! Indices of the entities of a kinetic model and of the priors by identifier,
+//! built once per parse instead of scanning the model on every request.
+//!
+//! Identifiers are only unique within a kind of entity: an enzyme may be named
+//! like its reaction. Lookups are by kind, and [`crate::state::KineticModelState::find_symbol`]
+//! returns every entity that matches.
+use std::borrow::Borrow;
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+use toml::Spanned;
+
+use crate::maud_data::{Compartment, Enzyme, KineticModel, Metabolite, Reaction};
+use crate::metabolic::MetabolicMetComp;
+use crate::priors::{ConcEnzyme, ConcUnbalanced, Drain, KcatPrior, KiPrior, KmPrior, Priors};
+
+/// Items linked to an id, none if it is not in the map.
+fn slice<'m, K: Borrow<str> + Hash + Eq, T>(map: &'m HashMap<K, Vec<T>>, id: &str) -> &'m [T] {
+    map.get(id).map_or(&[], Vec::as_slice)
+}
+
+/// Entities of a kinetic model by kind and id, with the links between them.
+pub struct SymbolIndex<'a> {
+    metabolites: HashMap<&'a str, &'a Metabolite<'a>>,
+    reactions: HashMap<&'a str, &'a Reaction<'a>>,
+    enzymes: HashMap<&'a str, &'a Enzyme<'a>>,
+    compartments: HashMap<&'a str, &'a Compartment<'a>>,
+    /// Compartments referred to anywhere in the model, even if they are not defined.
+    compartment_ids: HashSet<&'a str>,
+    /// Rows of `metabolite_in_compartment` by metabolite and compartment.
+    met_comp_rows: HashMap<(&'a str, &'a str), usize>,
+    /// Reactions of each metabolite in compartment, like `g6p_c`.
+    reactions_of_met_comp: HashMap<&'a str, Vec<&'a Reaction<'a>>>,
+    /// Reactions of each metabolite, in any compartment.
+    reactions_of_metabolite: HashMap<&'a str, Vec<&'a Reaction<'a>>>,
+    reactions_of_enzyme: HashMap<&'a str, Vec<&'a str>>,
+    enzymes_of_reaction: HashMap<&'a str, Vec<&'a str>>,
+    model: &'a KineticModel<'a>,
+}
+
+impl<'a> SymbolIndex<'a> {
+    pub fn new(model: &'a KineticModel<'a>) -> Self {
+        // the first definition wins, duplicates are reported elsewhere
+        fn by_id<'a, T>(items: &'a [T], id: impl Fn(&'a T) -> &'a str) -> HashMap<&'a str, &'a T> {
+            let mut map = HashMap::new();
+            for item in items {
+                map.entry(id(item)).or_insert(item);
+            }
+            map
+        }
+        let mut index = SymbolIndex {
+            metabolites: by_id(&model.metabolites, |met| met.id.get_ref()),
+            reactions: by_id(&model.reactions, |reac| reac.id.get_ref()),
+            enzymes: by_id(&model.enzymes, |enz| enz.id.get_ref()),
+            compartments: by_id(&model.compartments, |comp| comp.id.get_ref()),
+            compartment_ids: HashSet::new(),
+            met_comp_rows: HashMap::new(),
+            reactions_of_met_comp: HashMap::new(),
+            reactions_of_metabolite: HashMap::new(),
+            reactions_of_enzyme: HashMap::new(),
+            enzymes_of_reaction: HashMap::new(),
+            model,
+        };
+        index
+            .compartment_ids
+            .extend(index.compartments.keys().copied());
+        for (i, row) in model.metabolite_in_compartment.iter().enumerate() {
+            index.compartment_ids.insert(row.compartment_id);
+            index
+                .met_comp_rows
+                .entry((row.metabolite_id.get_ref(), row.compartment_id))
+                .or_insert(i);
+        }
+        for reac in model.reactions.iter() {
+            for met_comp in reac.stoichiometry.keys() {
+                index
+                    .reactions_of_met_comp
+                    .entry(met_comp)
+                    .or_default()
+                    .push(reac);
+                let Some((met, comp)) = met_comp.rsplit_once('_') else {
+                    continue;
+                };
+                index.compartment_ids.insert(comp);
+                let reactions = index.reactions_of_metabolite.entry(met).or_default();
+                // a metabolite may be in more than one compartment of a reaction
+                if !reactions
+                    .last()
+                    .is_some_and(|last| std::ptr::eq(*last, reac))
+                {
+                    reactions.push(reac);
+                }
+            }
+        }
+        for er in model.enzyme_reaction.iter() {
+            let (enzyme, reaction) = (*er.enzyme_id.get_ref(), *er.reaction_id.get_ref());
+            let reactions = index.reactions_of_enzyme.entry(enzyme).or_default();
+            // duplicated rows are reported elsewhere
+            if reactions.contains(&reaction) {
+                continue;
+            }
+            reactions.push(reaction);
+            index
+                .enzymes_of_reaction
+                .entry(reaction)
+                .or_default()
+                .push(enzyme);
+        }
+        index
+    }
+
+    pub fn metabolite(&self, id: &str) -> Option<&'a Metabolite<'a>> {
+        self.metabolites.get(id).copied()
+    }
+
+    pub fn reaction(&self, id: &str) -> Option<&'a Reaction<'a>> {
+        self.reactions.get(id).copied()
+    }
+
+    pub fn enzyme(&self, id: &str) -> Option<&'a Enzyme<'a>> {
+        self.enzymes.get(id).copied()
+    }
+
+    /// Number of kinds of entity (metabolites, reactions, enzymes and compartments)
+    /// that have an entity named `id`.
+    pub fn kinds_named(&self, id: &str) -> usize {
+        [
+            self.metabolites.contains_key(id),
+            self.reactions.contains_key(id),
+            self.enzymes.contains_key(id),
+            self.compartments.contains_key(id),
+        ]
+        .into_iter()
+        .filter(|named| *named)
+        .count()
+    }
+
+    /// Metabolite in a compartment, like `g6p_c`, if both the metabolite and the
+    /// compartment are in the model.
+    pub fn met_comp(&self, id: &str) -> Option<MetabolicMetComp<'a>> {
+        let (met_id, comp_id) = id.rsplit_once('_')?;
+        let metabolite = self.metabolite(met_id)?;
+        let compartment_id = *self.compartment_ids.get(comp_id)?;
+        Some(MetabolicMetComp {
+            metabolite,
+            compartment_id,
+            compartment: self.compartments.get(compartment_id).copied(),
+            row: self
+                .met_comp_rows
+                .get(&(met_id, comp_id))
+                .map(|i| &self.model.metabolite_in_compartment[*i]),
+        })
+    }
+
+    /// Reactions with a metabolite in compartment (`g6p_c`) in their stoichiometry.
+    pub fn reactions_of_met_comp(&self, met_comp: &str) -> &[&'a Reaction<'a>] {
+        slice(&self.reactions_of_met_comp, met_comp)
+    }
+
+    /// Reactions with a metabolite in any compartment in their stoichiometry.
+    pub fn reactions_of_metabolite(&self, metabolite: &str) -> &[&'a Reaction<'a>] {
+        slice(&self.reactions_of_metabolite, metabolite)
+    }
+
+    /// Reactions catalyzed by an enzyme, from the `enzyme_reaction` table.
+    pub fn reactions_of_enzyme(&self, enzyme: &str) -> &[&'a str] {
+        slice(&self.reactions_of_enzyme, enzyme)
+    }
+
+    /// Enzymes that catalyze a reaction, from the `enzyme_reaction` table.
+    pub fn enzymes_of_reaction(&self, reaction: &str) -> &[&'a str] {
+        slice(&self.enzymes_of_reaction, reaction)
+    }
+
+    /// Whether `enzyme_reaction` pairs an enzyme with a reaction, which then has a kcat.
+    pub fn catalyzes(&self, enzyme: &str, reaction: &str) -> bool {
+        self.reactions_of_enzyme(enzyme).contains(&reaction)
+    }
+
+    /// Whether a reaction of an enzyme involves a metabolite in compartment (`g6p_c`),
+    /// which then has a km.
+    pub fn has_km(&self, enzyme: &str, met_comp: &str) -> bool {
+        self.reactions_of_enzyme(enzyme)
+            .iter()
+            .filter_map(|reaction| self.reaction(reaction))
+            .any(|reaction| reaction.stoichiometry.contains_key(met_comp))
+    }
+}
+
+/// Priors of the entities of the kinetic model, in the order of the priors file.
+#[derive(Default)]
+pub struct PriorIndex<'a> {
+    kcat_of_enzyme: HashMap<&'a str, Vec<&'a Spanned<KcatPrior>>>,
+    kcat_of_reaction: HashMap<&'a str, Vec<&'a Spanned<KcatPrior>>>,
+    km_of_enzyme: HashMap<&'a str, Vec<&'a Spanned<KmPrior>>>,
+    /// By metabolite in compartment (`g6p_c`), which the priors write in two parts.
+    km_of_met_comp: HashMap<String, Vec<&'a Spanned<KmPrior>>>,
+    ki_of_enzyme: HashMap<&'a str, Vec<&'a Spanned<KiPrior>>>,
+    ki_of_met_comp: HashMap<String, Vec<&'a Spanned<KiPrior>>>,
+    conc_enzyme_of_enzyme: HashMap<&'a str, Vec<&'a Spanned<ConcEnzyme>>>,
+    conc_unbalanced_of_met_comp: HashMap<String, Vec<&'a Spanned<ConcUnbalanced>>>,
+    drain_of_reaction: HashMap<&'a str, Vec<&'a Spanned<Drain>>>,
+}
+
+impl<'a> PriorIndex<'a> {
+    pub fn new(priors: &'a Priors) -> Self {
+        let mut index = PriorIndex::default();
+        for kcat in priors.kcat.iter() {
+            let prior = kcat.get_ref();
+            index
+                .kcat_of_enzyme
+                .entry(&prior.enzyme)
+                .or_default()
+                .push(kcat);
+            index
+                .kcat_of_reaction
+                .entry(&prior.reaction)
+                .or_default()
+                .push(kcat);
+        }
+        for km in priors.km.iter() {
+            let prior = km.get_ref();
+            index
+                .km_of_enzyme
+                .entry(&prior.enzyme)
+                .or_default()
+                .push(km);
+            index
+                .km_of_met_comp
+                .entry(format!("{}_{}", prior.metabolite, prior.compartment))
+                .or_default()
+                .push(km);
+        }
+        for ki in priors.ki.iter() {
+            let prior = ki.get_ref();
+            index
+                .ki_of_enzyme
+                .entry(&prior.enzyme)
+                .or_default()
+                .push(ki);
+            index
+                .ki_of_met_comp
+                .entry(format!("{}_{}", prior.metabolite, prior.compartment))
+                .or_default()
+                .push(ki);
+        }
+        for conc in priors.conc_enzyme.iter() {
+            index
+                .conc_enzyme_of_enzyme
+                .entry(&conc.get_ref().enzyme)
+                .or_default()
+                .push(conc);
+        }
+        for conc in priors.conc_unbalanced.iter() {
+            let prior = conc.get_ref();
+            index
+                .conc_unbalanced_of_met_comp
+                .entry(format!("{}_{}", prior.metabolite, prior.compartment))
+                .or_default()
+                .push(conc);
+        }
+        for drain in priors.drain.iter() {
+            index
+                .drain_of_reaction
+                .entry(&drain.get_ref().reaction)
+                .or_default()
+                .push(drain);
+        }
+        index
+    }
+
+    pub fn kcats_of_enzyme(&self, enzyme: &str) -> &[&'a Spanned<KcatPrior>] {
+        slice(&self.kcat_of_enzyme, enzyme)
+    }
+
+    pub fn kcats_of_reaction(&self, reaction: &str) -> &[&'a Spanned<KcatPrior>] {
+        slice(&self.kcat_of_reaction, reaction)
+    }
+
+    pub fn kms_of_enzyme(&self, enzyme: &str) -> &[&'a Spanned<KmPrior>] {
+        slice(&self.km_of_enzyme, enzyme)
+    }
+
+    pub fn kms_of_met_comp(&self, met_comp: &str) -> &[&'a Spanned<KmPrior>] {
+        slice(&self.km_of_met_comp, met_comp)
+    }
+
+    pub fn kis_of_enzyme(&self, enzyme: &str) -> &[&'a Spanned<KiPrior>] {
+        slice(&self.ki_of_enzyme, enzyme)
+    }
+
+    pub fn kis_of_met_comp(&self, met_comp: &str) -> &[&'a Spanned<KiPrior>] {
+        slice(&self.ki_of_met_comp, met_comp)
+    }
+
+    pub fn concentrations_of_enzyme(&self, enzyme: &str) -> &[&'a Spanned<ConcEnzyme>] {
+        slice(&self.conc_enzyme_of_enzyme, enzyme)
+    }
+
+    pub fn conc_unbalanced_of_met_comp(&self, met_comp: &str) -> &[&'a Spanned<ConcUnbalanced>] {
+        slice(&self.conc_unbalanced_of_met_comp, met_comp)
+    }
+
+    pub fn drains_of_reaction(&self, reaction: &str) -> &[&'a Spanned<Drain>] {
+        slice(&self.drain_of_reaction, reaction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::metabolic::Metabolic;
+    use crate::state::{KineticModelState, PriorsState};
+
+    const MODEL: &str = r#"
+metabolite = [
+  {id = "g6p", name = "D-Glucose 6-phosphate", inchi_key = "A"},
+  {id = "f6p", name = "D-Fructose 6-phosphate", inchi_key = "B"},
+]
+enzyme = [{id = "PGI", name = "Glucose-6-phosphate isomerase", subunits = 1}]
+enzyme_reaction = [{enzyme_id = "PGI", reaction_id = "PGI"}]
+
+[[reaction]]
+id = "PGI"
+name = "Glucose-6-phosphate isomerase"
+stoichiometry = {g6p_c = -1, f6p_c = 1, f6p_m = 1}
+mechanism = "reversible_michaelis_menten"
+"#;
+
+    #[test]
+    fn ambiguous_ids_return_every_candidate() {
+        let state = KineticModelState::try_from_str(MODEL.to_string()).unwrap();
+        let entities = state.find_symbol("PGI");
+        let kinds: Vec<&str> = entities.iter().map(|entity| entity.kind()).collect();
+        assert_eq!(kinds, ["reaction", "enzyme"]);
+        assert!(entities.iter().all(|entity| entity.identifier() == "PGI"));
+        assert_eq!(
+            state.find_symbol("f6p_c")[0].kind(),
+            "metabolite in compartment"
+        );
+        assert!(state.find_symbol("f6p_x").is_empty());
+    }
+
+    #[test]
+    fn links_between_entities_are_indexed() {
+        let state = KineticModelState::try_from_str(MODEL.to_string()).unwrap();
+        let symbols = state.borrow_symbols();
+        assert_eq!(symbols.reactions_of_enzyme("PGI"), ["PGI"]);
+        assert_eq!(symbols.enzymes_of_reaction("PGI"), ["PGI"]);
+        // once, even if it is in two compartments of the reaction
+        assert_eq!(symbols.reactions_of_metabolite("f6p").len(), 1);
+        assert_eq!(symbols.reactions_of_met_comp("f6p_m").len(), 1);
+        assert!(symbols.reactions_of_met_comp("g6p_m").is_empty());
+    }
+
+    #[test]
+    fn priors_are_indexed_by_metabolite_in_compartment() {
+        let state = PriorsState::try_from_str(
+            r#"
+km = [{metabolite = "g6p", compartment = "c", enzyme = "PGI", exploc = 3.0, scale = 0.2}]
+ki = [{metabolite = "f6p", compartment = "c", enzyme = "PGI", exploc = 1.0, scale = 0.2}]
+conc_unbalanced = [
+  {metabolite = "g6p", compartment = "c", experiment = "a", exploc = 2.0, scale = 0.1},
+  {metabolite = "g6p", compartment = "m", experiment = "a", exploc = 2.0, scale = 0.1},
+]
+"#
+            .to_string(),
+        )
+        .unwrap();
+        let index = state.borrow_prior_index();
+        assert_eq!(index.kms_of_met_comp("g6p_c").len(), 1);
+        assert!(index.kms_of_met_comp("g6p_m").is_empty());
+        assert_eq!(index.kis_of_enzyme("PGI").len(), 1);
+        assert_eq!(index.kis_of_met_comp("f6p_c").len(), 1);
+        assert_eq!(index.conc_unbalanced_of_met_comp("g6p_m").len(), 1);
+    }
+}
@@ -0,0 +1,54 @@
+//! TCP transport of a server shared by several clients.
+//!
+//! `lsp_server` binds a new listener for every connection it accepts, so
+//! `--listen` accepts the clients itself and serves each stream on its own
+//! thread, with the same reader and writer threads as `lsp_server`.
+use std::io::{self, BufReader};
+use std::net::TcpStream;
+use std::thread::{self, JoinHandle};
+
+use crossbeam_channel::bounded;
+use lsp_server::{Connection, Message};
+use lsp_types::notification::{Exit, Notification as _};
+
+/// Reader and writer threads of a client stream.
+pub struct SocketThreads {
+    reader: JoinHandle<io::Result<()>>,
+    writer: JoinHandle<io::Result<()>>,
+}
+
+impl SocketThreads {
+    /// Wait for the client to exit and for its last messages to be written.
+    pub fn join(self) -> io::Result<()> {
+        let read = self.reader.join().expect("the reader thread panicked");
+        let written = self.writer.join().expect("the writer thread panicked");
+        read.and(written)
+    }
+}
+
+/// Connection over an accepted stream.
+pub fn socket(stream: TcpStream) -> io::Result<(Connection, SocketThreads)> {
+    let (reader_sender, receiver) = bounded::<Message>(0);
+    let mut read_stream = BufReader::new(stream.try_clone()?);
+    let reader = thread::spawn(move || {
+        while let Some(msg) = Message::read(&mut read_stream)? {
+            let is_exit = matches!(&msg, Message::Notification(n) if n.method == Exit::METHOD);
+            // the server stopped listening after a shutdown
+            if reader_sender.send(msg).is_err() || is_exit {
+                break;
+            }
+        }
+        Ok(())
+    });
+    let (sender, writer_receiver) = bounded::<Message>(0);
+    let mut write_stream = stream;
+    let writer = thread::spawn(move || {
+        writer_receiver
+            .into_iter()
+            .try_for_each(|msg| msg.write(&mut write_stream))
+    });
+    Ok((
+        Connection { sender, receiver },
+        SocketThreads { reader, writer },
+    ))
+}
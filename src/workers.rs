@@ -0,0 +1,134 @@
+//! Threads that handle the requests of the client in the background.
+//!
+//! Requests only read the project, so each of them gets a snapshot of it and
+//! a slow one, like the diagnostics of a large model, does not block hovers.
+//! Changes to the documents are still applied in order by the main loop.
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+use crossbeam_channel::Sender;
+use lsp_server::RequestId;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Fixed number of threads running jobs in the order they are sent.
+pub struct WorkerPool {
+    jobs: Option<Sender<Job>>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl WorkerPool {
+    pub fn new(size: usize) -> Self {
+        let (jobs, receiver) = crossbeam_channel::unbounded::<Job>();
+        let threads = (0..size)
+            .map(|i| {
+                let receiver = receiver.clone();
+                std::thread::Builder::new()
+                    .name(format!("maud-lsp-worker-{i}"))
+                    .spawn(move || {
+                        for job in receiver {
+                            job();
+                        }
+                    })
+                    .expect("Unable to spawn a worker thread")
+            })
+            .collect();
+        WorkerPool {
+            jobs: Some(jobs),
+            threads,
+        }
+    }
+
+    /// One worker per core, but at least two so that a slow request does not
+    /// block the rest.
+    pub fn default_size() -> usize {
+        std::thread::available_parallelism().map_or(2, |cores| cores.get().clamp(2, 8))
+    }
+
+    pub fn execute(&self, job: impl FnOnce() + Send + 'static) {
+        if let Some(jobs) = &self.jobs {
+            jobs.send(Box::new(job))
+                .expect("The worker threads have stopped");
+        }
+    }
+}
+
+impl Drop for WorkerPool {
+    /// Wait for the pending jobs, so that every request gets its response.
+    fn drop(&mut self) {
+        self.jobs.take();
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Requests being handled, with whether the client cancelled them.
+#[derive(Clone, Default)]
+pub struct InFlight {
+    requests: Arc<Mutex<HashMap<RequestId, bool>>>,
+}
+
+impl InFlight {
+    pub fn start(&self, id: RequestId) {
+        self.requests.lock().unwrap().insert(id, false);
+    }
+
+    /// Cancel a request, unless it already got its response.
+    pub fn cancel(&self, id: &RequestId) {
+        if let Some(cancelled) = self.requests.lock().unwrap().get_mut(id) {
+            *cancelled = true;
+        }
+    }
+
+    pub fn is_cancelled(&self, id: &RequestId) -> bool {
+        self.requests
+            .lock()
+            .unwrap()
+            .get(id)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Stop tracking a request before responding, returning whether it was cancelled.
+    pub fn finish(&self, id: &RequestId) -> bool {
+        self.requests.lock().unwrap().remove(id).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn jobs_run_concurrently() {
+        let pool = WorkerPool::new(2);
+        let (blocked, unblock) = mpsc::channel::<()>();
+        let (done, finished) = mpsc::channel();
+        // the first job waits for the second one
+        pool.execute(move || {
+            unblock.recv().unwrap();
+        });
+        pool.execute(move || {
+            blocked.send(()).unwrap();
+            done.send("second").unwrap();
+        });
+        assert_eq!(finished.recv().unwrap(), "second");
+    }
+
+    #[test]
+    fn only_requests_in_flight_are_cancelled() {
+        let in_flight = InFlight::default();
+        let (first, second) = (RequestId::from(1), RequestId::from(2));
+        in_flight.start(first.clone());
+        in_flight.cancel(&first);
+        in_flight.cancel(&second);
+        assert!(in_flight.is_cancelled(&first));
+        assert!(in_flight.finish(&first));
+        assert!(!in_flight.finish(&first));
+        in_flight.start(second.clone());
+        assert!(!in_flight.finish(&second));
+    }
+}
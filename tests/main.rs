@@ -5,9 +5,15 @@ use std::path::PathBuf;
 use crate::support::Project;
 
 use lsp_types::{
-    notification::DidSaveTextDocument,
-    request::{GotoDefinition, HoverRequest},
-    DidSaveTextDocumentParams, GotoDefinitionParams, HoverParams, PartialResultParams, Position,
+    notification::{DidChangeConfiguration, DidSaveTextDocument},
+    request::{
+        CallHierarchyIncomingCalls, CallHierarchyPrepare, CodeActionRequest, Completion,
+        DocumentDiagnosticRequest, ExecuteCommand, Formatting, GotoDefinition, HoverRequest,
+    },
+    CallHierarchyIncomingCallsParams, CallHierarchyPrepareParams, CodeActionContext,
+    CodeActionParams, CompletionParams, DidChangeConfigurationParams, DidSaveTextDocumentParams,
+    DocumentDiagnosticParams, DocumentFormattingParams, ExecuteCommandParams, FormattingOptions,
+    GotoDefinitionParams, HoverParams, PartialResultParams, Position, Range,
     TextDocumentPositionParams, WorkDoneProgressParams,
 };
 
@@ -34,9 +40,55 @@ fn goestodef_of_metabolites() {
     assert!(res.to_string().contains('2'));
 }
 
+#[test]
+fn goestodef_of_unknown_symbol_is_null() {
+    let server = Project::from_kinetic_model(
+        std::env::current_dir()
+            .unwrap()
+            .join(PathBuf::from("tests/mock")),
+    )
+    .server();
+    std::thread::sleep(std::time::Duration::from_secs(1));
+
+    // `allostery` is a key, not an entity of the model
+    let res = server.send_request::<GotoDefinition>(GotoDefinitionParams {
+        text_document_position_params: TextDocumentPositionParams::new(
+            server.doc_id("ecoli_kinetic_model.toml"),
+            Position::new(15, 2),
+        ),
+        work_done_progress_params: WorkDoneProgressParams::default(),
+        partial_result_params: PartialResultParams::default(),
+    });
+    assert_eq!(res, serde_json::Value::Null);
+}
+
+#[test]
+fn goestodef_of_config_file_reference() {
+    let server = Project::from_kinetic_model(
+        std::env::current_dir()
+            .unwrap()
+            .join(PathBuf::from("tests/mock")),
+    )
+    .server();
+    // waiting a bit for the server to initialize
+    std::thread::sleep(std::time::Duration::from_secs(1));
+
+    let res = server.send_request::<GotoDefinition>(GotoDefinitionParams {
+        text_document_position_params: TextDocumentPositionParams::new(
+            server.doc_id("config.toml"),
+            Position::new(5, 25),
+        ),
+        work_done_progress_params: WorkDoneProgressParams::default(),
+        partial_result_params: PartialResultParams::default(),
+    });
+    assert!(res["uri"]
+        .as_str()
+        .unwrap()
+        .ends_with("tests/mock/ecoli_kinetic_model.toml"));
+}
+
 #[test]
 fn goestodef_of_metabolite_reactant() {
-    assert!(false);
     let server = Project::from_kinetic_model(
         std::env::current_dir()
             .unwrap()
@@ -47,14 +99,14 @@ fn goestodef_of_metabolite_reactant() {
     std::thread::sleep(std::time::Duration::from_secs(1));
     let res = server.send_request::<GotoDefinition>(GotoDefinitionParams {
         text_document_position_params: TextDocumentPositionParams::new(
-            server.doc_id("ecoli_kinetic_model3.toml"),
+            server.doc_id("ecoli_kinetic_model.toml"),
             Position::new(38, 19),
         ),
         work_done_progress_params: WorkDoneProgressParams::default(),
         partial_result_params: PartialResultParams::default(),
     });
-    // lines are 0-indexed!
-    assert!(res.to_string().contains("\"line\":2"));
+    // f6p_c goes to its metabolite_in_compartment row, lines are 0-indexed!
+    assert!(res.to_string().contains("\"line\":91"));
 }
 
 #[test]
@@ -103,7 +155,6 @@ fn hovers_metabolites() {
 
 #[test]
 fn hovers_metabolite_reactant() {
-    assert!(false);
     let server = Project::from_kinetic_model(
         std::env::current_dir()
             .unwrap()
@@ -115,11 +166,67 @@ fn hovers_metabolite_reactant() {
     let res = server.send_request::<HoverRequest>(HoverParams {
         text_document_position_params: TextDocumentPositionParams::new(
             server.doc_id("ecoli_kinetic_model.toml"),
-            Position::new(27, 19),
+            Position::new(25, 18),
         ),
         work_done_progress_params: WorkDoneProgressParams::default(),
     });
-    assert!(res.to_string().contains("g6p"));
+    let res_str = res.to_string();
+    assert!(res_str.contains("metabolite in compartment"));
+    assert!(res_str.contains("g6p_c"));
+    assert!(res_str.contains("cytosol"));
+    assert!(res_str.contains("unbalanced"));
+}
+
+#[test]
+fn hovers_are_brief_after_the_settings_change() {
+    let server = Project::from_kinetic_model(
+        std::env::current_dir()
+            .unwrap()
+            .join(PathBuf::from("tests/mock")),
+    )
+    .server();
+    // waiting a bit for the server to initialize
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    let hover_pgi = || {
+        server
+            .send_request::<HoverRequest>(HoverParams {
+                text_document_position_params: TextDocumentPositionParams::new(
+                    server.doc_id("ecoli_kinetic_model.toml"),
+                    Position::new(23, 7),
+                ),
+                work_done_progress_params: WorkDoneProgressParams::default(),
+            })
+            .to_string()
+    };
+    assert!(hover_pgi().contains("| prior |"));
+    server.notification::<DidChangeConfiguration>(DidChangeConfigurationParams {
+        settings: serde_json::json!({"maud-lsp": {"hover": "brief"}}),
+    });
+    let res = hover_pgi();
+    assert!(res.contains("**reaction** `PGI`"));
+    assert!(!res.contains("| prior |"));
+}
+
+#[test]
+fn hovers_config_key() {
+    let server = Project::from_kinetic_model(
+        std::env::current_dir()
+            .unwrap()
+            .join(PathBuf::from("tests/mock")),
+    )
+    .server();
+    // waiting a bit for the server to initialize
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    let res = server.send_request::<HoverRequest>(HoverParams {
+        text_document_position_params: TextDocumentPositionParams::new(
+            server.doc_id("config.toml"),
+            Position::new(18, 3),
+        ),
+        work_done_progress_params: WorkDoneProgressParams::default(),
+    });
+    let res_str = res.to_string();
+    assert!(res_str.contains("max_num_steps"));
+    assert!(res_str.contains("an integer"));
 }
 
 #[test]
@@ -182,3 +289,301 @@ fn notifications_do_not_panic() {
         text: None,
     });
 }
+
+#[test]
+fn removes_duplicated_prior() {
+    let server = Project::from_kinetic_model(
+        std::env::current_dir()
+            .unwrap()
+            .join(PathBuf::from("tests/mock")),
+    )
+    .server();
+    // waiting a bit for the server to initialize
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    let res = server.send_request::<CodeActionRequest>(CodeActionParams {
+        text_document: server.doc_id("priors.toml"),
+        range: Range::new(Position::new(10, 4), Position::new(10, 4)),
+        context: CodeActionContext::default(),
+        work_done_progress_params: WorkDoneProgressParams::default(),
+        partial_result_params: PartialResultParams::default(),
+    });
+    let res_str = res.to_string();
+    assert!(res_str.contains("Remove duplicate km prior"));
+    // the whole line is removed
+    assert!(res_str.contains("\"start\":{\"character\":0,\"line\":10}"));
+    assert!(res_str.contains("\"end\":{\"character\":0,\"line\":11}"));
+}
+
+#[test]
+fn sorts_and_converts_kcat_priors() {
+    let server = Project::from_kinetic_model(
+        std::env::current_dir()
+            .unwrap()
+            .join(PathBuf::from("tests/mock")),
+    )
+    .server();
+    // waiting a bit for the server to initialize
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    let res = server.send_request::<CodeActionRequest>(CodeActionParams {
+        text_document: server.doc_id("priors.toml"),
+        range: Range::new(Position::new(2, 4), Position::new(2, 4)),
+        context: CodeActionContext::default(),
+        work_done_progress_params: WorkDoneProgressParams::default(),
+        partial_result_params: PartialResultParams::default(),
+    });
+    let res_str = res.to_string();
+    assert!(res_str.contains("Sort kcat by enzyme, reaction"));
+    assert!(res_str.contains("Convert kcat to [[kcat]] tables"));
+    // FBP goes first
+    assert!(res_str.contains("\"newText\":\"  {enzyme = \\\"E1\\\", reaction = \\\"FBP\\\""));
+}
+
+#[test]
+fn hovers_and_checks_user_inits() {
+    let server = Project::from_kinetic_model(
+        std::env::current_dir()
+            .unwrap()
+            .join(PathBuf::from("tests/mock")),
+    )
+    .server();
+    // waiting a bit for the server to initialize
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    let res = server.send_request::<HoverRequest>(HoverParams {
+        text_document_position_params: TextDocumentPositionParams::new(
+            server.doc_id("inits.toml"),
+            Position::new(1, 31),
+        ),
+        work_done_progress_params: WorkDoneProgressParams::default(),
+    });
+    assert!(res.to_string().contains("PGI"));
+    let res = server.send_request::<DocumentDiagnosticRequest>(DocumentDiagnosticParams {
+        text_document: server.doc_id("inits.toml"),
+        identifier: None,
+        previous_result_id: None,
+        work_done_progress_params: WorkDoneProgressParams::default(),
+        partial_result_params: PartialResultParams::default(),
+    });
+    assert_eq!(res["items"], serde_json::json!([]));
+}
+
+#[test]
+fn pulls_diagnostics_of_priors() {
+    let server = Project::from_kinetic_model(
+        std::env::current_dir()
+            .unwrap()
+            .join(PathBuf::from("tests/mock")),
+    )
+    .server();
+    // waiting a bit for the server to initialize
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    let res = server.send_request::<DocumentDiagnosticRequest>(DocumentDiagnosticParams {
+        text_document: server.doc_id("priors.toml"),
+        identifier: None,
+        previous_result_id: None,
+        work_done_progress_params: WorkDoneProgressParams::default(),
+        partial_result_params: PartialResultParams::default(),
+    });
+    assert_eq!(res["kind"], "full");
+    assert!(res.to_string().contains("Duplicate km prior"));
+    let res = server.send_request::<DocumentDiagnosticRequest>(DocumentDiagnosticParams {
+        text_document: server.doc_id("priors.toml"),
+        identifier: None,
+        previous_result_id: res["resultId"].as_str().map(String::from),
+        work_done_progress_params: WorkDoneProgressParams::default(),
+        partial_result_params: PartialResultParams::default(),
+    });
+    assert_eq!(res["kind"], "unchanged");
+}
+
+#[test]
+fn hovers_reaction_in_measurements_csv() {
+    let server = Project::from_kinetic_model(
+        std::env::current_dir()
+            .unwrap()
+            .join(PathBuf::from("tests/mock")),
+    )
+    .server();
+    // waiting a bit for the server to initialize
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    let res = server.send_request::<HoverRequest>(HoverParams {
+        text_document_position_params: TextDocumentPositionParams::new(
+            server.doc_id("measurements.csv"),
+            Position::new(2, 6),
+        ),
+        work_done_progress_params: WorkDoneProgressParams::default(),
+    });
+    let res_str = res.to_string();
+    assert!(res_str.contains("Glucose-6-phosphate isomerase"));
+    assert!(res_str.contains("mechanism"));
+}
+
+#[test]
+fn completes_targets_in_measurements_csv() {
+    let server = Project::from_kinetic_model(
+        std::env::current_dir()
+            .unwrap()
+            .join(PathBuf::from("tests/mock")),
+    )
+    .server();
+    // waiting a bit for the server to initialize
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    let res = server.send_request::<Completion>(CompletionParams {
+        text_document_position: TextDocumentPositionParams::new(
+            server.doc_id("measurements.csv"),
+            Position::new(1, 5),
+        ),
+        work_done_progress_params: WorkDoneProgressParams::default(),
+        partial_result_params: PartialResultParams::default(),
+        context: None,
+    });
+    let labels: Vec<&str> = res
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter_map(|item| item["label"].as_str())
+        .collect();
+    // metabolites in compartments starting with "g"
+    assert_eq!(labels, ["g3p_c", "g6p_c"]);
+}
+
+#[test]
+fn incoming_calls_of_metabolite_are_producing_reactions() {
+    let server = Project::from_kinetic_model(
+        std::env::current_dir()
+            .unwrap()
+            .join(PathBuf::from("tests/mock")),
+    )
+    .server();
+    // waiting a bit for the server to initialize
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    let items = server.send_request::<CallHierarchyPrepare>(CallHierarchyPrepareParams {
+        text_document_position_params: TextDocumentPositionParams::new(
+            server.doc_id("ecoli_kinetic_model.toml"),
+            Position::new(38, 19),
+        ),
+        work_done_progress_params: WorkDoneProgressParams::default(),
+    });
+    assert_eq!(items[0]["name"], "f6p_c");
+    let res = server.send_request::<CallHierarchyIncomingCalls>(CallHierarchyIncomingCallsParams {
+        item: serde_json::from_value(items[0].clone()).unwrap(),
+        work_done_progress_params: WorkDoneProgressParams::default(),
+        partial_result_params: PartialResultParams::default(),
+    });
+    assert_eq!(res[0]["from"]["name"], "PGI");
+}
+
+#[test]
+fn exports_network_as_graphml() {
+    let server = Project::from_kinetic_model(
+        std::env::current_dir()
+            .unwrap()
+            .join(PathBuf::from("tests/mock")),
+    )
+    .server();
+    // waiting a bit for the server to initialize
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    let res = server.send_request::<ExecuteCommand>(ExecuteCommandParams {
+        command: "maud-lsp.exportNetwork".to_string(),
+        arguments: vec![serde_json::json!("graphml")],
+        work_done_progress_params: WorkDoneProgressParams::default(),
+    });
+    let graphml = res.as_str().unwrap();
+    assert!(graphml.contains("<node id=\"PGI\">"));
+    assert!(graphml.contains("<edge source=\"g6p_c\" target=\"PGI\">"));
+}
+
+#[test]
+fn formats_priors() {
+    let server = Project::from_kinetic_model(
+        std::env::current_dir()
+            .unwrap()
+            .join(PathBuf::from("tests/mock")),
+    )
+    .server();
+    // waiting a bit for the server to initialize
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    let res = server.send_request::<Formatting>(DocumentFormattingParams {
+        text_document: server.doc_id("priors.toml"),
+        options: FormattingOptions::default(),
+        work_done_progress_params: WorkDoneProgressParams::default(),
+    });
+    let new_texts: Vec<&str> = res
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter_map(|edit| edit["newText"].as_str())
+        .collect();
+    // the drain priors are spaced
+    assert!(new_texts
+        .iter()
+        .any(|text| text.contains("{reaction = \"g3pdrain\", experiment")));
+}
+
+#[test]
+fn serves_a_client_over_tcp() {
+    use lsp_server::{Message, Notification, Request};
+    use std::io::BufReader;
+    use std::net::TcpListener;
+
+    // the client listens and the server connects to it
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_maud-lsp"))
+        .args(["--connect", &addr])
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+    let (stream, _) = listener.accept().unwrap();
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut notifier = stream.try_clone().unwrap();
+    let mut writer = stream;
+    let mut response = |req: Request| {
+        let id = req.id.clone();
+        Message::Request(req).write(&mut writer).unwrap();
+        loop {
+            match Message::read(&mut reader).unwrap() {
+                Some(Message::Response(resp)) if resp.id == id => {
+                    assert!(resp.error.is_none(), "error response: {:?}", resp.error);
+                    // a null result, like that of shutdown, is missing
+                    return resp.result.unwrap_or_default();
+                }
+                Some(_) => continue,
+                None => panic!("the server closed the connection"),
+            }
+        }
+    };
+    let root =
+        lsp_types::Url::from_directory_path(std::env::current_dir().unwrap().join("tests/mock"))
+            .unwrap();
+    let init = response(Request::new(
+        1.into(),
+        "initialize".to_string(),
+        serde_json::json!({"capabilities": {}, "rootUri": root}),
+    ));
+    assert_eq!(init["capabilities"]["hoverProvider"], true);
+    Message::Notification(Notification::new(
+        "initialized".to_string(),
+        serde_json::json!({}),
+    ))
+    .write(&mut notifier)
+    .unwrap();
+    let hover = response(Request::new(
+        2.into(),
+        "textDocument/hover".to_string(),
+        HoverParams {
+            text_document_position_params: TextDocumentPositionParams::new(
+                lsp_types::TextDocumentIdentifier::new(
+                    root.join("ecoli_kinetic_model.toml").unwrap(),
+                ),
+                Position::new(23, 7),
+            ),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        },
+    ));
+    assert!(hover.to_string().contains("**reaction** `PGI`"));
+    response(Request::new(3.into(), "shutdown".to_string(), ()));
+    Message::Notification(Notification::new("exit".to_string(), ()))
+        .write(&mut notifier)
+        .unwrap();
+    assert!(child.wait().unwrap().success());
+}
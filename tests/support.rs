@@ -8,7 +8,7 @@ use std::{
 use crossbeam_channel::{after, select, Receiver};
 use lsp_server::{Connection, Message, Notification, Request};
 use lsp_types::{notification::Exit, request::Shutdown, TextDocumentIdentifier, Url};
-use maud_lsp::{main_loop, Config};
+use maud_lsp::{main_loop, Config, Session};
 use serde::Serialize;
 use serde_json::{to_string_pretty, Value};
 
@@ -43,11 +43,14 @@ impl Server {
         let config = Config {
             caps: <lsp_types::ClientCapabilities as Default>::default(),
             root_dir: root_dir.clone(),
+            settings: Default::default(),
+            log: Default::default(),
+            trace: Default::default(),
         };
         let config_to_main = config.clone();
         let _thread = jod_thread::Builder::new()
             .name("test server".to_string())
-            .spawn(move || main_loop(connection, config_to_main).unwrap())
+            .spawn(move || main_loop(connection, config_to_main, Session::default()).unwrap())
             .expect("failed to spawn a thread");
 
         Server {